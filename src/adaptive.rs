@@ -0,0 +1,38 @@
+//! Decides which pixels still need more samples, by estimating how noisy each one is from the samples taken so far.
+
+use crate::vec::Vector;
+
+/// Estimates how noisy a pixel is after taking `n` samples, given the sum of its samples' colors and squared brightness.
+/// Returns the standard error of the pixel's brightness, which is infinite until there are enough samples to tell.
+pub fn pixel_error(sum: Vector, sum_sq: f32, n: u32) -> f32 {
+    if n < 2 {
+        return f32::INFINITY;
+    }
+
+    let n = n as f32;
+    let mean = sum.dot(Vector::from_s(1.0 / 3.0, 3)) / n;
+    let var = ((sum_sq / n - mean * mean) * n / (n - 1.0)).max(0.0);
+    (var / n).sqrt()
+}
+
+/// Estimates how noisy the image is, given the sum of every sample's color and squared brightness and the number of samples for each pixel.
+/// Returns the standard error of each pixel's brightness, averaged over all pixels.
+pub fn noise(sum: &[Vector], sum_sq: &[f32], counts: &[u32]) -> f32 {
+    let mut total = 0.0;
+
+    for ((c, sq), n) in sum.iter().zip(sum_sq.iter()).zip(counts.iter()) {
+        total += pixel_error(*c, *sq, *n);
+    }
+
+    total / sum.len() as f32
+}
+
+/// Stops sampling every pixel in `active` whose error, worked out from its running totals, is already below `threshold`.
+/// Pixels that have stopped are never started again.
+pub fn settle(active: &mut [bool], sum: &[Vector], sum_sq: &[f32], counts: &[u32], threshold: f32) {
+    for i in 0..active.len() {
+        if active[i] && pixel_error(sum[i], sum_sq[i], counts[i]) < threshold {
+            active[i] = false;
+        }
+    }
+}
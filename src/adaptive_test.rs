@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use crate::adaptive::*;
+    use crate::vec::*;
+
+    /// Returns the running totals of a pixel that took the gray samples `s`.
+    fn totals(s: &[f32]) -> (Vector, f32, u32) {
+        let sum = s
+            .iter()
+            .fold(Vector::zero(3), |a, x| a + Vector::from_s(*x, 3));
+        let sum_sq = s.iter().map(|x| x * x).sum();
+        (sum, sum_sq, s.len() as u32)
+    }
+
+    #[test]
+    fn error_test() {
+        // one sample says nothing about noise
+        let (sum, sq, n) = totals(&[0.5]);
+        assert_eq!(pixel_error(sum, sq, n), f32::INFINITY);
+
+        // identical samples have no error
+        let (sum, sq, n) = totals(&[0.5; 8]);
+        assert!(pixel_error(sum, sq, n) < 1e-6);
+
+        // alternating samples of 0 and 1 have a sample variance of 1/3, so 4 of them have a standard error of sqrt(1/3 / 4)
+        let (sum, sq, n) = totals(&[0.0, 1.0, 0.0, 1.0]);
+        assert!((pixel_error(sum, sq, n) - (1.0f32 / 12.0).sqrt()).abs() < 1e-5);
+
+        // the image's noise is the average over its pixels
+        let (s0, q0, n0) = totals(&[0.5; 4]);
+        let (s1, q1, n1) = totals(&[0.0, 1.0, 0.0, 1.0]);
+        let e = noise(&[s0, s1], &[q0, q1], &[n0, n1]);
+        assert!((e - (1.0f32 / 12.0).sqrt() / 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn settle_test() {
+        let px = [
+            totals(&[0.5, 0.5, 0.5, 0.5]),
+            totals(&[0.0, 1.0, 0.0, 1.0]),
+            totals(&[0.5]),
+            totals(&[0.5, 0.5]),
+        ];
+        let sum: Vec<Vector> = px.iter().map(|p| p.0).collect();
+        let sum_sq: Vec<f32> = px.iter().map(|p| p.1).collect();
+        let counts: Vec<u32> = px.iter().map(|p| p.2).collect();
+
+        // the flat pixel stops, the noisy one and the one with a single sample keep going,
+        // and a pixel that already stopped stays stopped
+        let mut active = [true, true, true, false];
+        settle(&mut active, &sum, &sum_sq, &counts, 0.01);
+        assert_eq!(active, [false, true, true, false]);
+    }
+}
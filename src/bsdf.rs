@@ -0,0 +1,141 @@
+//! Defines how surfaces reflect the light that reaches them.
+//!
+//! Every model is scaled so that a white diffuse surface lit head on reflects all the light it receives,
+//! which is how lights in the scene have always been measured.
+
+use crate::vec::*;
+
+use std::f32::consts::PI;
+
+/// Defines how much light a surface reflects from one direction into another.
+pub trait Bsdf {
+    /// Returns how much of the light arriving from direction `wi` is reflected towards direction `wo` by a surface
+    /// with normal `n` and base color `color`. All directions are unit length and point away from the surface.
+    /// Includes the cosine of the angle the light arrives at, so this can be multiplied straight into the light's color.
+    fn eval(&self, n: Vector, wi: Vector, wo: Vector, color: Vector) -> Vector;
+}
+
+/// A diffuse surface, which looks equally bright from all directions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lambert;
+
+impl Bsdf for Lambert {
+    fn eval(&self, n: Vector, wi: Vector, _wo: Vector, color: Vector) -> Vector {
+        color * Vector::from_s(n.dot(wi).max(0.0), 3)
+    }
+}
+
+/// A diffuse surface with white highlights around the mirror direction of the light.
+/// Highlights get tighter as `exponent` goes up, and there are none if it's 0 or less.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Phong {
+    pub exponent: f32,
+}
+
+impl Bsdf for Phong {
+    fn eval(&self, n: Vector, wi: Vector, wo: Vector, color: Vector) -> Vector {
+        let diff = Lambert.eval(n, wi, wo, color);
+        if self.exponent <= 0.0 || n.dot(wi) <= 0.0 {
+            return diff;
+        }
+
+        let r = Vector::refl(wi, n).norm();
+        let spec = r.dot(wo).max(0.0).powf(self.exponent);
+        diff + Vector::from_s(spec, 3)
+    }
+}
+
+/// Like `Phong`, but highlights are based on the angle between the normal and the half way vector between
+/// the light and the viewer, which keeps highlights looking right at grazing angles.
+/// Highlights are scaled by `specular` and get tighter as `exponent` goes up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlinnPhong {
+    pub exponent: f32,
+    pub specular: f32,
+}
+
+impl Bsdf for BlinnPhong {
+    fn eval(&self, n: Vector, wi: Vector, wo: Vector, color: Vector) -> Vector {
+        let cos = n.dot(wi);
+        let diff = Lambert.eval(n, wi, wo, color);
+        if cos <= 0.0 || self.exponent <= 0.0 {
+            return diff;
+        }
+
+        let h = (wi + wo).norm();
+        let spec = self.specular * n.dot(h).max(0.0).powf(self.exponent) * cos;
+        diff + Vector::from_s(spec, 3)
+    }
+}
+
+/// A physically based microfacet model: the GGX distribution of microfacet normals, Smith shadowing and masking,
+/// and Schlick's approximation of Fresnel reflectance, combined with a diffuse base.
+/// `roughness` goes from 0 (polished) to 1 (rough), and `metallic` from 0 (dielectrics like plastic, with white
+/// highlights over a colored diffuse base) to 1 (metals, with colored highlights and no diffuse base).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ggx {
+    pub roughness: f32,
+    pub metallic: f32,
+}
+
+impl Ggx {
+    /// Returns the GGX distribution of microfacet normals for a surface of width `a`, where `nh` is the cosine
+    /// between the normal and the microfacet normal.
+    pub fn distribution(a: f32, nh: f32) -> f32 {
+        let a2 = a * a;
+        let d = nh * nh * (a2 - 1.0) + 1.0;
+        a2 / (PI * d * d)
+    }
+
+    /// Returns the fraction of microfacets visible from a direction at cosine `c` to the normal, for a surface of width `a`.
+    pub fn smith(a: f32, c: f32) -> f32 {
+        let a2 = a * a;
+        2.0 * c / (c + (a2 + (1.0 - a2) * c * c).sqrt())
+    }
+}
+
+impl Bsdf for Ggx {
+    fn eval(&self, n: Vector, wi: Vector, wo: Vector, color: Vector) -> Vector {
+        let (nl, nv) = (n.dot(wi), n.dot(wo));
+        if nl <= 0.0 || nv <= 0.0 {
+            return Vector::zero(3);
+        }
+
+        // perfectly smooth surfaces make the distribution infinitely thin, so keep a little roughness
+        let a = (self.roughness * self.roughness).max(1e-3);
+        let h = (wi + wo).norm();
+
+        // dielectrics reflect about 4% head on, metals reflect their own color
+        let f0 = Vector::lerp(color, Vector::from_s(0.04, 3), self.metallic);
+        let fw = (1.0 - wo.dot(h).clamp(0.0, 1.0)).powi(5);
+        let f = f0 + (Vector::from_s(1.0, 3) - f0) * Vector::from_s(fw, 3);
+
+        let spec = Ggx::distribution(a, n.dot(h).max(0.0)) * Ggx::smith(a, nl) * Ggx::smith(a, nv)
+            / (4.0 * nl * nv);
+        let diff =
+            (Vector::from_s(1.0, 3) - f) * color * Vector::from_s((1.0 - self.metallic) / PI, 3);
+
+        // scaled by π to match the lights, which are measured for a white diffuse surface
+        (diff + f * Vector::from_s(spec, 3)) * Vector::from_s(nl * PI, 3)
+    }
+}
+
+/// Reflection model used by a material.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Model {
+    Lambert(Lambert),
+    Phong(Phong),
+    BlinnPhong(BlinnPhong),
+    Ggx(Ggx),
+}
+
+impl Bsdf for Model {
+    fn eval(&self, n: Vector, wi: Vector, wo: Vector, color: Vector) -> Vector {
+        match self {
+            Model::Lambert(b) => b.eval(n, wi, wo, color),
+            Model::Phong(b) => b.eval(n, wi, wo, color),
+            Model::BlinnPhong(b) => b.eval(n, wi, wo, color),
+            Model::Ggx(b) => b.eval(n, wi, wo, color),
+        }
+    }
+}
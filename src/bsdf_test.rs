@@ -0,0 +1,119 @@
+#[cfg(test)]
+mod tests {
+    use crate::bsdf::*;
+    use crate::vec::*;
+
+    use std::f32::consts::PI;
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-2
+    }
+
+    /// Returns the fraction of light arriving from all over the hemisphere that `b` reflects towards `wo`.
+    fn albedo(b: &dyn Bsdf, wo: Vector) -> f32 {
+        let n = Vector::from_3(0.0, 0.0, 1.0);
+        let steps = 256;
+        let mut sum = 0.0;
+
+        // midpoint rule over cos(theta) and phi, which covers equal solid angles with each cell
+        for i in 0..steps {
+            for j in 0..steps {
+                let z = (i as f32 + 0.5) / steps as f32;
+                let phi = 2.0 * PI * (j as f32 + 0.5) / steps as f32;
+                let r = (1.0 - z * z).sqrt();
+                let wi = Vector::from_3(r * phi.cos(), r * phi.sin(), z);
+
+                // eval is scaled by π, so undo that to get the actual reflectance
+                sum += b.eval(n, wi, wo, Vector::from_s(1.0, 3)).x() / PI;
+            }
+        }
+
+        sum * 2.0 * PI / (steps * steps) as f32
+    }
+
+    #[test]
+    fn lambert_test() {
+        let n = Vector::from_3(0.0, 0.0, 1.0);
+        let wi = Vector::from_3(0.6, 0.0, 0.8);
+        let c = Lambert.eval(n, wi, n, Vector::from_3(1.0, 0.5, 0.0));
+        assert!(approx(c.x(), 0.8) && approx(c.y(), 0.4) && approx(c.z(), 0.0));
+
+        // light from behind the surface isn't reflected
+        assert_eq!(Lambert.eval(n, -wi, n, Vector::from_s(1.0, 3)).x(), 0.0);
+
+        assert!(approx(albedo(&Lambert, n), 1.0));
+        assert!(approx(albedo(&Phong { exponent: 0.0 }, n), 1.0));
+    }
+
+    #[test]
+    fn ggx_test() {
+        let wo = Vector::from_3(0.3, 0.0, 0.95).norm();
+
+        // metals never reflect more light than they receive, and lose more of it to shadowing between microfacets as they get rougher
+        let mut last = 1.01;
+        for roughness in [0.2, 0.5, 0.9] {
+            let a = albedo(
+                &Ggx {
+                    roughness,
+                    metallic: 1.0,
+                },
+                wo,
+            );
+            assert!(
+                a > 0.3 && a < last,
+                "roughness {} gave albedo {}",
+                roughness,
+                a
+            );
+            last = a;
+        }
+
+        // reflection works the same with the light and the viewer swapped
+        let b = Ggx {
+            roughness: 0.4,
+            metallic: 0.0,
+        };
+        let n = Vector::from_3(0.0, 0.0, 1.0);
+        let wi = Vector::from_3(-0.5, 0.2, 0.8).norm();
+        let c = Vector::from_s(0.7, 3);
+        assert!(approx(
+            b.eval(n, wi, wo, c).x() / wi.z(),
+            b.eval(n, wo, wi, c).x() / wo.z()
+        ));
+    }
+
+    #[test]
+    fn phong_test() {
+        let n = Vector::from_3(0.0, 0.0, 1.0);
+        let b = Phong { exponent: 50.0 };
+        let wi = Vector::from_3(0.6, 0.0, 0.8);
+        let mirror = Vector::from_3(-0.6, 0.0, 0.8);
+
+        // highlights are white whatever the surface color, and brightest in the mirror direction
+        let c = b.eval(n, wi, mirror, Vector::from_3(1.0, 0.0, 0.0));
+        assert!(approx(c.x(), 1.8) && approx(c.y(), 1.0) && approx(c.z(), 1.0));
+
+        // light from behind the surface gives no highlight either
+        let behind = Vector::from_3(0.6, 0.0, -0.8);
+        assert_eq!(
+            b.eval(n, behind, -mirror, Vector::from_s(1.0, 3)),
+            Vector::zero(3)
+        );
+    }
+
+    #[test]
+    fn blinn_phong_test() {
+        let n = Vector::from_3(0.0, 0.0, 1.0);
+        let b = BlinnPhong {
+            exponent: 50.0,
+            specular: 1.0,
+        };
+        let wi = Vector::from_3(0.6, 0.0, 0.8);
+        let mirror = Vector::from_3(-0.6, 0.0, 0.8);
+
+        // highlights are brightest in the mirror direction
+        let black = Vector::zero(3);
+        assert!(approx(b.eval(n, wi, mirror, black).x(), 0.8));
+        assert!(b.eval(n, wi, n, black).x() < 0.8);
+    }
+}
@@ -0,0 +1,157 @@
+//! Reads the command line, and applies the scene overrides given on it on top of a scene file.
+
+use std::path::Path;
+
+use crate::scene::{self, Table, Value};
+
+pub const USAGE: &str = "usage: oxide <scene file> [options]
+
+options:
+  -o, --output <file>     where to write the image, named after the scene file by default
+  --width <pixels>        image width, same as --set output.width=<pixels>
+  --height <pixels>       image height, same as --set output.height=<pixels>
+  --threads <n|auto>      threads to render with, same as --set render.threads=<n>
+  --samples <n>           samples per pixel, same as --set render.samples=<n>
+  --format <png|ppm>      image format, same as --set output.format=<format>
+  --set <key>=<value>     overrides any scene value, like render.max_reflections=5 (can be repeated)
+  --export <file>         writes the scene out as canonical TOML instead of rendering it (animations as their first frame)
+  -h, --help              prints this message";
+
+/// What to do, as given on the command line.
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    /// The scene file to read.
+    pub input: String,
+    /// The image to write, whose extension picks the format if it's one oxide writes. Named after `input` if not set.
+    pub output: Option<String>,
+    /// The file to write the scene out to instead of rendering it.
+    pub export: Option<String>,
+    /// Scene values to replace, as dotted paths to the key and the value to put there, in the order given.
+    pub overrides: Vec<(String, Value)>,
+    /// Whether the usage message was asked for.
+    pub help: bool,
+}
+
+impl Args {
+    /// Returns the start of every output file's name: the output path, or the scene file's, without its extension.
+    /// Only the last extension is dropped, so `scenes/a.b.toml` renders to `scenes/a.b.png`.
+    pub fn prefix(&self) -> String {
+        let path = Path::new(self.output.as_ref().unwrap_or(&self.input));
+        let known = matches!(extension(path).as_deref(), Some("png" | "ppm"));
+
+        // an output name with some other extension, like "render.final", is kept as it is
+        match self.output.is_none() || known {
+            true => path.with_extension("").to_string_lossy().into_owned(),
+            false => path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// Returns the lowercase extension of `path`, if it has one.
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// Reads the command line arguments `args`, not including the program name.
+/// Returns a message saying what's wrong if they don't make sense.
+pub fn parse(args: &[String]) -> Result<Args, String> {
+    let mut a = Args::default();
+    let mut input = None;
+    let mut format = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        // long options take their value as the next argument, or after an "=" in the same one
+        let (flag, inline) = match arg.split_once('=') {
+            Some((f, v)) if arg.starts_with("--") => (f, Some(v.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| iter.next().cloned())
+                .ok_or_else(|| format!("{flag} needs a value"))
+        };
+
+        // shorthands for the scene values batch renders change the most
+        let key = match flag {
+            "--width" => Some("output.width"),
+            "--height" => Some("output.height"),
+            "--threads" => Some("render.threads"),
+            "--samples" => Some("render.samples"),
+            _ => None,
+        };
+        if let Some(k) = key {
+            a.overrides.push((k.to_string(), to_value(&value()?)));
+            continue;
+        }
+
+        match flag {
+            "-h" | "--help" => a.help = true,
+            "-o" | "--output" => a.output = Some(value()?),
+            "--export" => a.export = Some(value()?),
+            "--format" => format = Some(value()?.to_ascii_lowercase()),
+            "--set" => {
+                let v = value()?;
+                let (k, v) = v.split_once('=').ok_or_else(|| {
+                    format!("--set needs a key and a value, like key=value, not \"{v}\"")
+                })?;
+                a.overrides.push((k.trim().to_string(), to_value(v.trim())));
+            }
+            f if f.starts_with('-') && f.len() > 1 => return Err(format!("unknown option {f}")),
+            _ if input.is_some() => return Err(format!("unexpected argument \"{arg}\"")),
+            _ => input = Some(arg.clone()),
+        }
+    }
+
+    if a.help {
+        return Ok(a);
+    }
+    a.input = input.ok_or("no scene file given")?;
+
+    // the output's extension picks the format, unless one was asked for
+    let ext = a.output.as_deref().and_then(|o| extension(Path::new(o)));
+    if let Some(f) = format.or(ext.filter(|e| e == "png" || e == "ppm")) {
+        a.overrides
+            .push(("output.format".to_string(), Value::String(f)));
+    }
+
+    Ok(a)
+}
+
+/// Reads an override's value as it would be read from a TOML scene file, so numbers, booleans and arrays keep their types.
+/// Anything that isn't valid TOML is taken as a string, so `--set world.background=sky.hdr` needs no quotes.
+pub fn to_value(s: &str) -> Value {
+    scene::parse(Path::new("override.toml"), &format!("v = {s}"))
+        .ok()
+        .and_then(|t| t.get("v").cloned())
+        .unwrap_or_else(|| Value::String(s.to_string()))
+}
+
+/// Puts each value in `overrides` into the scene tree `cfg` at its dotted path, replacing what was there.
+/// Tables along the way are made if they're missing.
+/// Returns a message saying what's wrong if a path runs through a value that isn't a table.
+pub fn apply(mut cfg: Value, overrides: &[(String, Value)]) -> Result<Value, String> {
+    for (key, v) in overrides {
+        let parts: Vec<&str> = key.split('.').collect();
+        let mut t = &mut cfg;
+
+        for (i, p) in parts.iter().enumerate() {
+            let table = t.as_table_mut().ok_or_else(|| {
+                format!("can't set {key}, {} isn't a table", parts[..i].join("."))
+            })?;
+
+            if i == parts.len() - 1 {
+                table.insert(p.to_string(), v.clone());
+                break;
+            }
+            t = table
+                .entry(p.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+        }
+    }
+
+    Ok(cfg)
+}
@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+    use crate::cli::*;
+
+    use crate::scene::{self, Value};
+    use std::path::Path;
+
+    fn args(s: &str) -> Result<Args, String> {
+        let v: Vec<String> = s.split_whitespace().map(String::from).collect();
+        parse(&v)
+    }
+
+    #[test]
+    fn parse_test() {
+        let a = args("scenes/a.b.toml --width 640 --height=480 --threads auto --set render.max_reflections=5").unwrap();
+        assert_eq!(a.input, "scenes/a.b.toml");
+        assert_eq!(
+            a.overrides,
+            vec![
+                ("output.width".to_string(), Value::Integer(640)),
+                ("output.height".to_string(), Value::Integer(480)),
+                (
+                    "render.threads".to_string(),
+                    Value::String("auto".to_string())
+                ),
+                ("render.max_reflections".to_string(), Value::Integer(5)),
+            ]
+        );
+        assert_eq!(a.prefix(), "scenes/a.b");
+
+        // the output's extension picks the format, unless --format says otherwise
+        let a = args("a.toml -o out/r.ppm").unwrap();
+        assert_eq!(a.prefix(), "out/r");
+        assert_eq!(a.overrides[0].1, Value::String("ppm".to_string()));
+        let a = args("a.toml -o out/r.ppm --format PNG").unwrap();
+        assert_eq!(a.overrides[0].1, Value::String("png".to_string()));
+        assert_eq!(args("a.toml -o out/r.v2").unwrap().prefix(), "out/r.v2");
+
+        assert!(args("a.toml --samples").is_err());
+        assert!(args("a.toml b.toml").is_err());
+        assert!(args("a.toml --bogus 1").is_err());
+        assert!(args("--width 5").is_err());
+        assert!(args("--help").unwrap().help);
+    }
+
+    #[test]
+    fn apply_test() {
+        assert_eq!(to_value("0.5"), Value::Float(0.5));
+        assert_eq!(to_value("[1, 2]").as_array().unwrap().len(), 2);
+        assert_eq!(to_value("sky.hdr"), Value::String("sky.hdr".to_string()));
+
+        let cfg =
+            scene::parse(Path::new("a.toml"), "[render]\nsamples = 4\nthreads = 2\n").unwrap();
+        let cfg = apply(
+            cfg,
+            &[
+                ("render.samples".to_string(), Value::Integer(64)),
+                ("objects.ball.radius".to_string(), Value::Float(2.0)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(cfg["render"]["samples"], Value::Integer(64));
+        assert_eq!(cfg["render"]["threads"], Value::Integer(2));
+        assert_eq!(cfg["objects"]["ball"]["radius"], Value::Float(2.0));
+
+        // a path can't run through a value that isn't a table
+        let err = apply(cfg, &[("render.samples.x".to_string(), Value::Integer(1))]);
+        assert_eq!(
+            err,
+            Err("can't set render.samples.x, render.samples isn't a table".to_string())
+        );
+    }
+}
@@ -1,12 +1,21 @@
-//! Defines how the .toml config file is read and interpreted.
+//! Defines how a scene is read out of its scene file into the structs the renderer uses.
+//! Loading, combining and checking scene files is left to `scene`.
 
-use oxide::opts::*;
-use oxide::ray::{Light, LightType, Material, Plane, RayInteraction, Sphere};
-use oxide::vec::Vector;
+use crate::bsdf::{BlinnPhong, Ggx, Lambert, Model, Phong};
+use crate::env::EnvMap;
+use crate::gltf::{self, Placement};
+use crate::medium::{Density, Medium, Volume};
+use crate::mesh::{Mesh, Texture, Triangle};
+use crate::opts::*;
+use crate::ray::{Light, LightType, Material, Plane, RayInteraction, Sphere};
+use crate::scene::{self, get_v3, to_f32, Table, Value};
+use crate::sdf::{Sdf, Shape};
+use crate::vec::Vector;
+use crate::{cli, denoise, ply, stl};
 use std::collections::HashMap;
 use std::fs;
-use std::thread;
-use toml::Value;
+use std::path::Path;
+use std::sync::Arc;
 
 type Triple = (
     Config,
@@ -14,84 +23,609 @@ type Triple = (
     Vec<Light>,
 );
 
-/// Turns a .toml scene configuration file into a config struct.
+/// Returns `v` scaled to unit length. Vectors that already are, to within rounding, are kept exactly as written,
+/// so scenes that are written out and read back in don't drift a little further each time.
+fn unit(v: Vector) -> Vector {
+    match (v.dot(v) - 1.0).abs() < 1e-6 {
+        true => v,
+        false => v.norm(),
+    }
+}
+
+/// Reads an optional float from `parent`, falling back to `default` if it isn't set.
+fn get_f_or(parent: &Value, s: &str, default: f32) -> f32 {
+    parent.get(s).map_or(default, to_f32)
+}
+
+/// Reads an optional integer from `parent`, falling back to `default` if it isn't set.
+fn get_i_or(parent: &Value, s: &str, default: i64) -> i64 {
+    parent.get(s).map_or(default, |v| {
+        v.as_integer().expect("could not cast into integer")
+    })
+}
+
+/// Reads an optional boolean from `parent`, falling back to `default` if it isn't set.
+fn get_b_or(parent: &Value, s: &str, default: bool) -> bool {
+    parent.get(s).map_or(default, |v| {
+        v.as_bool().expect("could not cast into boolean")
+    })
+}
+
+/// Turns a table describing a signed distance field into a tree of shapes.
+/// Any shape can be moved away from the origin with an optional `center` key.
+fn get_shape(sv: &Value) -> Shape {
+    let get_f = |s| to_f32(&sv[s]);
+    let get_list = || -> Vec<Shape> {
+        let list = sv["shapes"].as_array().expect("could not cast into array");
+        // combining nothing has no surface, and smooth unions and differences start from the first shape
+        if list.is_empty() {
+            panic!(
+                "sdf {} needs at least one shape in \"shapes\"",
+                sv["type"].as_str().unwrap_or("")
+            );
+        }
+        list.iter().map(get_shape).collect()
+    };
+    let get_child = || Box::new(get_shape(&sv["shape"]));
+
+    let shape = match sv["type"].as_str().expect("could not cast into string") {
+        "sphere" => Shape::Sphere { r: get_f("radius") },
+        "box" => Shape::Cuboid {
+            b: get_v3(sv, "size"),
+        },
+        "torus" => {
+            let r = sv["radii"].as_array().expect("could not cast into array");
+            Shape::Torus {
+                r: (to_f32(&r[0]), to_f32(&r[1])),
+            }
+        }
+        "capsule" => Shape::Capsule {
+            a: get_v3(sv, "a"),
+            b: get_v3(sv, "b"),
+            r: get_f("radius"),
+        },
+        "union" => Shape::Union(get_list()),
+        "smooth_union" => Shape::SmoothUnion {
+            k: get_f("k"),
+            s: get_list(),
+        },
+        "intersection" => Shape::Intersect(get_list()),
+        "difference" => Shape::Subtract(get_list()),
+        "twist" => Shape::Twist {
+            k: get_f("k"),
+            s: get_child(),
+        },
+        "repeat" => Shape::Repeat {
+            period: get_v3(sv, "period"),
+            s: get_child(),
+        },
+        _ => panic!("found unknown sdf shape type!"),
+    };
+
+    match sv.get("center") {
+        Some(_) => Shape::Translate {
+            off: get_v3(sv, "center"),
+            s: Box::new(shape),
+        },
+        None => shape,
+    }
+}
+
+/// Turns the table describing how the density of a medium varies into a density field.
+/// The medium has the same density everywhere if there's no table.
+fn get_density(fv: Option<&Value>) -> Density {
+    let fv = match fv {
+        Some(fv) => fv,
+        None => return Density::Constant,
+    };
+
+    match fv["type"].as_str().expect("could not cast into string") {
+        "constant" => Density::Constant,
+        "noise" => Density::Noise {
+            scale: get_f_or(fv, "scale", 1.0),
+            octaves: get_i_or(fv, "octaves", 4).max(1) as u32,
+            threshold: get_f_or(fv, "threshold", 0.0),
+            seed: get_i_or(fv, "seed", 0) as u32,
+        },
+        "grid" => {
+            let r = fv["resolution"]
+                .as_array()
+                .expect("could not cast into array");
+            let r: Vec<usize> = r
+                .iter()
+                .map(|v| v.as_integer().expect("could not cast into integer") as usize)
+                .collect();
+            let values = to_floats(&fv["values"]);
+            assert!(
+                r.len() == 3 && r.iter().all(|&n| n >= 2),
+                "density grids need at least 2 values along each axis"
+            );
+            assert_eq!(
+                values.len(),
+                r[0] * r[1] * r[2],
+                "density grid size doesn't match its resolution"
+            );
+
+            Density::Grid {
+                res: (r[0], r[1], r[2]),
+                values,
+                min: get_v3(fv, "min"),
+                max: get_v3(fv, "max"),
+            }
+        }
+        _ => panic!("found unknown density type!"),
+    }
+}
+
+/// Turns a number or an array of numbers into a list of floats.
+fn to_floats(v: &Value) -> Vec<f32> {
+    match v {
+        Value::Array(a) => a.iter().flat_map(to_floats).collect(),
+        _ => vec![to_f32(v)],
+    }
+}
+
+/// Returns the value of an animated table `t` at `frame`, in the same shape as the keyframe values.
+/// Keyframes are placed with either `frame` or `time` (in seconds, turned into frames with `fps`).
+/// Values are interpolated linearly by default, or smoothly through every key if `interpolation` is "cubic".
+fn interpolate(t: &Table, frame: f32, fps: f32) -> Value {
+    let mut keys: Vec<(f32, Vec<f32>)> = t["keys"]
+        .as_array()
+        .expect("could not cast into array")
+        .iter()
+        .map(|k| {
+            let f = match k.get("time") {
+                Some(_) => get_f_or(k, "time", 0.0) * fps,
+                None => to_floats(&k["frame"])[0],
+            };
+            (f, to_floats(&k["value"]))
+        })
+        .collect();
+    keys.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("invalid keyframe"));
+    assert!(!keys.is_empty(), "animated value has no keys");
+
+    let cubic = match t
+        .get("interpolation")
+        .map(|i| i.as_str().expect("could not cast into string"))
+    {
+        None | Some("linear") => false,
+        Some("cubic") => true,
+        _ => panic!("unknown interpolation"),
+    };
+
+    // hold the first and last values before and after the animation
+    let n = keys.len();
+    let i = keys
+        .iter()
+        .rposition(|k| k.0 <= frame)
+        .unwrap_or(0)
+        .min(n.saturating_sub(2));
+    let vals: Vec<f32> = if n == 1 || frame <= keys[0].0 {
+        keys[0].1.clone()
+    } else if frame >= keys[n - 1].0 {
+        keys[n - 1].1.clone()
+    } else {
+        let (f0, f1) = (keys[i].0, keys[i + 1].0);
+        let s = (frame - f0) / (f1 - f0);
+
+        // the slope at each key points from the key before it to the key after it
+        let slope = |k: usize, c: usize| {
+            let (a, b) = (k.saturating_sub(1), (k + 1).min(n - 1));
+            (keys[b].1[c] - keys[a].1[c]) / (keys[b].0 - keys[a].0) * (f1 - f0)
+        };
+
+        (0..keys[i].1.len())
+            .map(|c| {
+                let (p0, p1) = (keys[i].1[c], keys[i + 1].1[c]);
+                if cubic {
+                    // cubic hermite spline
+                    let (s2, s3) = (s * s, s * s * s);
+                    (2.0 * s3 - 3.0 * s2 + 1.0) * p0
+                        + (s3 - 2.0 * s2 + s) * slope(i, c)
+                        + (-2.0 * s3 + 3.0 * s2) * p1
+                        + (s3 - s2) * slope(i + 1, c)
+                } else {
+                    p0 + (p1 - p0) * s
+                }
+            })
+            .collect()
+    };
+
+    // give back the same kind of value the keys hold, so the rest of the config reads it like a static value
+    match &t["keys"][0]["value"] {
+        Value::Array(_) => Value::Array(vals.iter().map(|f| Value::Float(*f as f64)).collect()),
+        Value::Integer(_) => Value::Integer(vals[0].round() as i64),
+        _ => Value::Float(vals[0] as f64),
+    }
+}
+
+/// Replaces every animated value in `v` with its value at `frame`.
+/// Any table with a `keys` array is an animated value.
+fn resolve(v: Value, frame: f32, fps: f32) -> Value {
+    match v {
+        Value::Table(t) if t.get("keys").is_some_and(|k| k.is_array()) => {
+            interpolate(&t, frame, fps)
+        }
+        Value::Table(t) => Value::Table(
+            t.into_iter()
+                .map(|(k, v)| (k, resolve(v, frame, fps)))
+                .collect(),
+        ),
+        Value::Array(a) => Value::Array(a.into_iter().map(|v| resolve(v, frame, fps)).collect()),
+        v => v,
+    }
+}
+
+/// Finds `file`, named in the scene file at `path`, relative to the scene file.
+/// Files named in included scene files have already been made relative to those files when loading.
+/// Files are kept as full paths so scenes written back out can be put anywhere.
+fn find_file(path: &str, file: &str) -> String {
+    let full = fs::canonicalize(
+        Path::new(path)
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(file),
+    )
+    .unwrap_or_else(|_| panic!("could not read {}", file));
+    full.to_str().expect("invalid path").to_string()
+}
+
+/// Reads the triangles of a mesh from its table `ov`, found in the scene file at `path`, scaled by `scale` and moved by `offset`.
+/// Meshes are either read from a .ply or .stl `file`, or written out in the table: 9 numbers in `positions` for the corners
+/// of each triangle, with optional `normals`, `colors` and `uvs` (texture coordinates, 6 numbers per triangle) matching them.
+fn get_triangles(path: &str, ov: &Value) -> Vec<Triangle> {
+    let scale = Vector::from_s(get_f_or(ov, "scale", 1.0), 3);
+    let off = match ov.get("offset") {
+        Some(_) => get_v3(ov, "offset"),
+        None => Vector::zero(3),
+    };
+
+    if let Some(fv) = ov.get("file") {
+        let file = find_file(path, fv.as_str().expect("could not cast into string"));
+        let ext = Path::new(&file)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        let mut tris = match ext.as_deref() {
+            Some("ply") => ply::load(&file),
+            Some("stl") => stl::load(&file),
+            _ => panic!(
+                "unsupported mesh file {}, only .ply and .stl are supported",
+                file
+            ),
+        };
+        assert!(!tris.is_empty(), "mesh file {} has no triangles", file);
+        for t in &mut tris {
+            t.p = t.p.map(|p| p * scale + off);
+        }
+        return tris;
+    }
+
+    let pos = to_floats(&ov["positions"]);
+    let normals = ov.get("normals").map(to_floats);
+    let colors = ov.get("colors").map(to_floats);
+    let uvs = ov.get("uvs").map(to_floats);
+
+    let n = pos.len() / 9;
+    assert!(
+        n > 0 && pos.len() == n * 9,
+        "mesh positions should be 9 numbers per triangle"
+    );
+    assert!(
+        normals.as_ref().is_none_or(|v| v.len() == n * 9),
+        "mesh normals should be 9 numbers per triangle"
+    );
+    assert!(
+        colors.as_ref().is_none_or(|v| v.len() == n * 9),
+        "mesh colors should be 9 numbers per triangle"
+    );
+    assert!(
+        uvs.as_ref().is_none_or(|v| v.len() == n * 6),
+        "mesh uvs should be 6 numbers per triangle"
+    );
+
+    let v3 = |f: &[f32], i: usize| Vector::from_3(f[i * 3], f[i * 3 + 1], f[i * 3 + 2]);
+    (0..n)
+        .map(|t| {
+            let c = [t * 3, t * 3 + 1, t * 3 + 2];
+            Triangle {
+                p: c.map(|i| v3(&pos, i) * scale + off),
+                n: normals.as_ref().map(|f| c.map(|i| unit(v3(f, i)))),
+                uv: uvs.as_ref().map(|f| c.map(|i| (f[i * 2], f[i * 2 + 1]))),
+                color: colors.as_ref().map(|f| c.map(|i| v3(f, i))),
+            }
+        })
+        .collect()
+}
+
+/// Returns a camera position and target that show everything between `min` and `max`, looking at it from the front
+/// and a little above.
+fn frame_bounds(min: Vector, max: Vector) -> (Vector, Vector) {
+    let center = (min + max) * Vector::from_s(0.5, 3);
+    let r = (max - min).dot(max - min).sqrt() / 2.0;
+    (center + Vector::from_3(0.0, 0.5 * r, -1.6 * r), center)
+}
+
+/// Returns how far an object moves while the shutter is open, `secs` seconds long, given its table `ov` as it is
+/// when the shutter opens and `cv` as it is when it closes.
+/// Objects move along their optional `velocity` (in units per second), plus however far keyframes move their `key` position.
+fn get_motion(ov: &Value, cv: &Value, key: &str, secs: f32) -> Vector {
+    let mut motion = match ov.get("velocity") {
+        Some(_) => get_v3(ov, "velocity") * Vector::from_s(secs, 3),
+        None => Vector::zero(3),
+    };
+
+    if ov.get(key).is_some() {
+        motion = motion + get_v3(cv, key) - get_v3(ov, key);
+    }
+
+    motion
+}
+
+/// Turns a scene file into a config struct, along with the objects and lights in the scene.
 /// The file `test_scene.toml` is pretty self-documenting, so check that for details.
-pub fn read_cfg(path: &str) -> Option<Triple> {
-    let cfg_str = fs::read_to_string(path).expect("could not read config file");
-    let cfg = cfg_str
-        .parse::<Value>()
-        .expect("could not parse config file");
-
-    // The base .toml file value should be a Table value type
-    let cfg_base = cfg
-        .try_into::<toml::map::Map<String, Value>>()
-        .expect("could not cast into map");
+/// Animated values are read at `frame`, or at the first frame of the animation if that's `None`.
+/// Each of `overrides` replaces the value at its dotted path, like `render.samples`, before anything is read.
+/// Returns a message saying what's wrong if one of them can't be put in the scene.
+pub fn read_cfg(
+    path: &str,
+    frame: Option<u32>,
+    overrides: &[(String, Value)],
+) -> Result<Triple, String> {
+    let cfg = scene::load(Path::new(path)).unwrap_or_else(|e| panic!("{}", e));
+    let cfg = cli::apply(cfg, overrides)?;
 
     let mut c = Config {
         ..Default::default()
     }; // using struct update syntax to fill out field here
 
+    // the frame range has to be known before anything else is read, since any other value may be animated
+    c.animation = cfg.get("animation").map(|av| {
+        let start = get_i_or(av, "start", 1) as u32;
+        Animation {
+            start,
+            end: (get_i_or(av, "end", start as i64) as u32).max(start),
+            fps: get_f_or(av, "fps", 24.0),
+        }
+    });
+
+    let (frame, fps) = match c.animation {
+        Some(a) => (frame.unwrap_or(a.start), a.fps),
+        None => (frame.unwrap_or(1), 24.0),
+    };
+
+    // the rest of the scene is only checked once it's resolved, which needs the shutter, so check it on its own
+    let shutter = match cfg.get("render").and_then(|rv| rv.get("shutter")) {
+        Some(sv) => match sv.as_array().map(Vec::as_slice) {
+            Some([o, c]) if [o, c].iter().all(|v| v.is_float() || v.is_integer()) => {
+                (to_f32(o), to_f32(c))
+            }
+            _ => panic!("invalid scene:\n  render: \"shutter\" should be an array of 2 numbers"),
+        },
+        None => (0.0, 0.0),
+    };
+    assert!(shutter.1 >= shutter.0, "shutter closes before it opens");
+
+    // the scene is read as it is when the shutter opens, and again when it closes to find out how far things move
+    let cfg_open = resolve(cfg.clone(), frame as f32 + shutter.0, fps);
+    if let Err(errs) = scene::validate(&cfg_open) {
+        panic!("invalid scene:\n  {}", errs.join("\n  "));
+    }
+    let cfg_close = scene::instance_prefabs(resolve(cfg, frame as f32 + shutter.1, fps));
+    let shutter_secs = (shutter.1 - shutter.0) / fps;
+
+    // The base scene value should be a Table value type
+    let cfg_base = scene::instance_prefabs(cfg_open)
+        .into_table()
+        .expect("could not cast into map");
+
     // keep materials we've seen in a hash map for quick access
     let mut mats = HashMap::new();
     let mut objs: Vec<Box<dyn RayInteraction + Send + Sync>> = vec![];
     let mut lights = vec![];
 
-    // turn a .toml array into a Vector with 3 elements
-    let get_v3 = |parent: &Value, s| -> Vector {
-        let vals = parent[s].as_array().expect("could not cast into array");
-        Vector::from_3(
-            vals[0].as_float().expect("could not cast into float") as f32,
-            vals[1].as_float().expect("could not cast into float") as f32,
-            vals[2].as_float().expect("could not cast into float") as f32,
-        )
-    };
+    // names are kept around so output passes can tell which index belongs to which object or material
+    let mut object_names = vec![];
+    let mut volumes = vec![];
+    let mut material_names = vec![];
+
+    // a glTF file's camera replaces the scene's, but the world is only read after the objects
+    let mut gltf_camera = None;
 
     for (k, v) in cfg_base {
         match k.as_str() {
             "materials" => {
-                let mat_map = v
-                    .try_into::<toml::map::Map<String, Value>>()
-                    .expect("could not cast material into map");
+                let mat_map = v.into_table().expect("could not cast material into map");
                 for (name, mv) in mat_map {
                     let color = get_v3(&mv, "color");
-                    let spec = mv["spec"].as_float().expect("could not cast into float") as f32;
-                    let refl = mv["refl"].as_float().expect("could not cast into float") as f32;
+                    // highlights are off if there's no specular exponent, or if it's 0 or less
+                    let spec = get_f_or(&mv, "spec", 0.0);
+                    let model = mv
+                        .get("model")
+                        .map(|m| m.as_str().expect("could not cast into string"));
 
-                    mats.insert(name, Material { color, spec, refl });
+                    // rough surfaces blur their reflections too, so ggx materials are rough unless told otherwise
+                    let roughness = match model {
+                        Some("ggx") => get_f_or(&mv, "roughness", 0.5),
+                        _ => get_f_or(&mv, "roughness", 0.0),
+                    }
+                    .clamp(0.0, 1.0);
+
+                    let bsdf = match model {
+                        None | Some("phong") => Model::Phong(Phong { exponent: spec }),
+                        Some("lambert") => Model::Lambert(Lambert),
+                        Some("blinn_phong") => Model::BlinnPhong(BlinnPhong {
+                            exponent: spec,
+                            specular: get_f_or(&mv, "specular", 1.0),
+                        }),
+                        Some("ggx") => Model::Ggx(Ggx {
+                            roughness,
+                            metallic: get_f_or(&mv, "metallic", 0.0).clamp(0.0, 1.0),
+                        }),
+                        _ => panic!("unknown material model"),
+                    };
+                    let refl = to_f32(&mv["refl"]);
+                    let emission = match mv.get("emission") {
+                        Some(_) => get_v3(&mv, "emission"),
+                        None => Vector::zero(3),
+                    };
+                    let transmission = match mv.get("transmission") {
+                        Some(_) => get_v3(&mv, "transmission").clamp(0.0, 1.0),
+                        None => Vector::zero(3),
+                    };
+
+                    let id = material_names.len();
+                    material_names.push(name.clone());
+
+                    mats.insert(
+                        name,
+                        Material {
+                            color,
+                            bsdf,
+                            refl,
+                            roughness,
+                            emission,
+                            transmission,
+                            id,
+                        },
+                    );
                 }
             }
             "objects" => {
-                let obj_map = v
-                    .try_into::<toml::map::Map<String, Value>>()
-                    .expect("could not cast object into map");
-                for (_, ov) in obj_map {
+                let obj_map = v.into_table().expect("could not cast object into map");
+                for (name, ov) in obj_map {
+                    let cv = &cfg_close["objects"][name.as_str()];
                     let t = ov["type"].as_str().expect("could not cast into string");
+
+                    // volumes aren't surfaces, so they're kept apart from other objects and don't show up in output passes
+                    if t == "volume" {
+                        volumes.push(Volume {
+                            shape: get_shape(&ov["shape"]),
+                            medium: Medium {
+                                absorption: get_v3(&ov, "absorption"),
+                                scattering: get_v3(&ov, "scattering"),
+                                g: get_f_or(&ov, "anisotropy", 0.0).clamp(-0.99, 0.99),
+                                density: get_f_or(&ov, "density", 1.0),
+                                field: get_density(ov.get("field")),
+                            },
+                            step: get_f_or(&ov, "step", 0.1).max(1e-3),
+                        });
+                        continue;
+                    }
+
+                    // glTF files hold any number of meshes, named "<object>.<mesh>", along with their own materials
+                    if t == "gltf" {
+                        let file = find_file(
+                            path,
+                            ov["file"].as_str().expect("could not cast into string"),
+                        );
+                        let place = Placement {
+                            offset: match ov.get("offset") {
+                                Some(_) => get_v3(&ov, "offset"),
+                                None => Vector::zero(3),
+                            },
+                            scale: get_f_or(&ov, "scale", 1.0),
+                            light_scale: get_f_or(&ov, "light_scale", 1.0),
+                            first_material: material_names.len(),
+                            motion: get_motion(&ov, cv, "offset", shutter_secs),
+                        };
+                        let scene = gltf::load(Path::new(&file), &place);
+
+                        if get_b_or(&ov, "camera", false) {
+                            gltf_camera = match scene.camera {
+                                Some(cam) => Some((cam.pos, cam.target, Some(cam.yfov))),
+                                None => scene
+                                    .meshes
+                                    .iter()
+                                    .map(|(_, m)| m.bounds())
+                                    .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+                                    .map(|(min, max)| {
+                                        let (pos, target) = frame_bounds(min, max);
+                                        (pos, target, None)
+                                    }),
+                            };
+                        }
+                        if get_b_or(&ov, "lights", true) {
+                            lights.extend(scene.lights);
+                        }
+
+                        material_names
+                            .extend(scene.materials.iter().map(|m| format!("{}.{}", name, m)));
+                        for (mesh_name, mesh) in scene.meshes {
+                            object_names.push(format!("{}.{}", name, mesh_name));
+                            objs.push(Box::new(mesh));
+                        }
+                        continue;
+                    }
+
+                    object_names.push(name);
                     match t {
                         "sphere" => {
                             let c = get_v3(&ov, "center");
-                            let r =
-                                ov["radius"].as_float().expect("could not cast into float") as f32;
+                            let r = to_f32(&ov["radius"]);
                             let mat =
                                 mats[ov["material"].as_str().expect("could not cast into string")];
 
-                            objs.push(Box::new(Sphere { c, r, mat }));
+                            let motion = get_motion(&ov, cv, "center", shutter_secs);
+
+                            objs.push(Box::new(Sphere { c, r, mat, motion }));
                         }
                         "plane" => {
                             let p = get_v3(&ov, "point");
-                            let n = get_v3(&ov, "normal").norm();
+                            let n = unit(get_v3(&ov, "normal"));
+                            let mat =
+                                mats[ov["material"].as_str().expect("could not cast into string")];
+
+                            let motion = get_motion(&ov, cv, "point", shutter_secs);
+
+                            objs.push(Box::new(Plane { p, n, mat, motion }));
+                        }
+                        "sdf" => {
+                            let shape = get_shape(&ov["shape"]);
+                            let mat =
+                                mats[ov["material"].as_str().expect("could not cast into string")];
+                            let step = get_f_or(&ov, "step", 1.0);
+                            let max_steps = get_i_or(&ov, "max_steps", 256) as u32;
+                            let max_dist = get_f_or(&ov, "max_dist", 100.0);
+
+                            // the whole shape moves with the center of its outermost shape
+                            let (sv, scv) = (&ov["shape"], &cv["shape"]);
+                            let mut motion = get_motion(&ov, cv, "center", shutter_secs);
+                            if sv.get("center").is_some() {
+                                motion = motion + get_v3(scv, "center") - get_v3(sv, "center");
+                            }
+
+                            objs.push(Box::new(Sdf {
+                                shape,
+                                mat,
+                                step,
+                                max_steps,
+                                max_dist,
+                                motion,
+                            }));
+                        }
+                        "mesh" => {
                             let mat =
                                 mats[ov["material"].as_str().expect("could not cast into string")];
+                            let texture = ov.get("texture").map(|tv| {
+                                let file = tv.as_str().expect("could not cast into string");
+                                Arc::new(Texture::load(&find_file(path, file)))
+                            });
+                            let motion = get_motion(&ov, cv, "offset", shutter_secs);
 
-                            objs.push(Box::new(Plane { p, n, mat }));
+                            objs.push(Box::new(Mesh::new(
+                                get_triangles(path, &ov),
+                                mat,
+                                texture,
+                                motion,
+                            )));
                         }
                         _ => panic!("found unknown object type!"),
                     }
                 }
             }
             "lights" => {
-                let light_map = v
-                    .try_into::<toml::map::Map<String, Value>>()
-                    .expect("could not cast light into map");
+                let light_map = v.into_table().expect("could not cast light into map");
                 for (_, lv) in light_map {
                     let kind = match lv["type"].as_str().expect("could not cast into string") {
                         "ambient" => LightType::Ambient,
@@ -105,33 +639,82 @@ pub fn read_cfg(path: &str) -> Option<Triple> {
                 }
             }
             "world" => {
-                let f_arr = v["fog"].as_array().expect("could not cast into array");
-                let fog = (
-                    f_arr[0].as_float().expect("could not cast into float") as f32,
-                    f_arr[1].as_float().expect("could not cast into float") as f32,
-                );
-                assert!(fog.1 > fog.0);
+                let fog = match v.get("fog") {
+                    Some(fv) => {
+                        assert!(fv.is_table(), "could not cast fog into table");
+                        Fog {
+                            density: get_f_or(fv, "density", 0.0).max(0.0),
+                            color: get_v3(fv, "color"),
+                            height: get_f_or(fv, "height", 0.0),
+                            falloff: get_f_or(fv, "falloff", 0.0),
+                        }
+                    }
+                    None => Fog::default(),
+                };
+
+                // the background is either a constant color or the path to an environment map
+                let (background, environment) = match v["background"].as_str() {
+                    Some(file) => {
+                        // environment maps are found relative to the scene file
+                        let env_path = find_file(path, file);
+                        let intensity = get_f_or(&v, "background_intensity", 1.0);
+
+                        (Vector::zero(3), Some(EnvMap::load(&env_path, intensity)))
+                    }
+                    None => (get_v3(&v, "background"), None),
+                };
+
+                let cam_pos = get_v3(&v, "camera_position");
+                let camera_target = match v.get("camera_target") {
+                    Some(_) => get_v3(&v, "camera_target"),
+                    None => cam_pos + Vector::from_3(0.0, 0.0, 1.0), // straight ahead
+                };
 
                 c.world = World {
-                    cam_pos: get_v3(&v, "camera_position"),
-                    background: get_v3(&v, "background"),
+                    cam_pos,
+                    camera_target,
+                    fov: get_f_or(&v, "fov", 90.0).clamp(1.0, 179.0),
+                    background,
+                    environment,
                     fog,
+                    ..Default::default()
                 }
             }
             "render" => {
-                let threads;
-                if v["threads"].is_integer() {
-                    threads = v["threads"]
-                        .as_integer()
-                        .expect("could not cast into integer")
-                        as usize;
-                } else if v["threads"].as_str().unwrap() == "auto" {
-                    threads = thread::available_parallelism()
-                        .expect("could not discover number of threads")
-                        .get();
-                } else {
-                    panic!("unknown thread string value");
-                }
+                // "auto" is kept as it is, so scenes written back out still use every core of whatever runs them
+                let threads = match v["threads"].as_str() {
+                    Some("auto") => None,
+                    Some(_) => panic!("unknown thread string value"),
+                    None => Some(
+                        v["threads"]
+                            .as_integer()
+                            .expect("could not cast into integer") as usize,
+                    ),
+                };
+
+                let filter = match v
+                    .get("filter")
+                    .map(|f| f.as_str().expect("could not cast into string"))
+                {
+                    None | Some("box") => Filter::Box,
+                    Some("tent") => Filter::Tent,
+                    Some("gaussian") => Filter::Gaussian,
+                    Some("mitchell") => Filter::Mitchell,
+                    Some("lanczos") => Filter::Lanczos,
+                    _ => panic!("unknown filter"),
+                };
+
+                let sampler = match v
+                    .get("sampler")
+                    .map(|f| f.as_str().expect("could not cast into string"))
+                {
+                    None | Some("random") => SamplerType::Random,
+                    Some("stratified") => SamplerType::Stratified,
+                    Some("halton") => SamplerType::Halton,
+                    Some("sobol") => SamplerType::Sobol,
+                    Some("blue_noise") => SamplerType::BlueNoise,
+                    _ => panic!("unknown sampler"),
+                };
 
                 c.render = Render {
                     max_reflections: v["max_reflections"]
@@ -139,6 +722,20 @@ pub fn read_cfg(path: &str) -> Option<Triple> {
                         .expect("could not cast into integer")
                         as u32,
                     threads,
+                    samples: get_i_or(&v, "samples", 1).max(1) as u32,
+                    time_limit: get_f_or(&v, "time_limit", 0.0),
+                    noise_threshold: get_f_or(&v, "noise_threshold", 0.0),
+                    adaptive_threshold: get_f_or(&v, "adaptive_threshold", 0.0),
+                    sampler,
+                    filter,
+                    filter_radius: get_f_or(&v, "filter_radius", filter.default_radius()),
+                    shutter,
+                    adaptive_min_samples: get_i_or(&v, "adaptive_min_samples", 4).max(2) as u32,
+                    light_samples: get_i_or(&v, "light_samples", 16) as u32,
+                    env_samples: get_i_or(&v, "env_samples", 16) as u32,
+                    gloss_samples: get_i_or(&v, "gloss_samples", 8).max(1) as u32,
+                    ao_samples: get_i_or(&v, "ao_samples", 0) as u32,
+                    ao_distance: get_f_or(&v, "ao_distance", 1.0),
                 }
             }
             "output" => {
@@ -155,6 +752,26 @@ pub fn read_cfg(path: &str) -> Option<Triple> {
                     panic!("invalid bit depth");
                 }
 
+                let mut passes = vec![];
+                if let Some(pv) = v.get("passes") {
+                    for p in pv.as_array().expect("could not cast into array") {
+                        passes.push(match p.as_str().expect("could not cast into string") {
+                            "depth" => Pass::Depth,
+                            "normal" => Pass::Normal,
+                            "albedo" => Pass::Albedo,
+                            "object" => Pass::Object,
+                            "material" => Pass::Material,
+                            "ao" => Pass::Ao,
+                            _ => panic!("unknown output pass"),
+                        });
+                    }
+                }
+
+                // shorthand for adding the ambient occlusion pass
+                if get_b_or(&v, "ao_pass", false) && !passes.contains(&Pass::Ao) {
+                    passes.push(Pass::Ao);
+                }
+
                 c.output = Output {
                     format: fmt,
                     width: v["width"]
@@ -164,11 +781,51 @@ pub fn read_cfg(path: &str) -> Option<Triple> {
                         .as_integer()
                         .expect("could not cast into integer") as usize,
                     bits,
+                    passes,
+                    preview_passes: get_i_or(&v, "preview_passes", 0) as u32,
+                    preview_seconds: get_f_or(&v, "preview_seconds", 0.0),
+                    progress_file: v
+                        .get("progress_file")
+                        .map(|f| f.as_str().expect("could not cast into string").to_string()),
+                    sample_heatmap: get_b_or(&v, "sample_heatmap", false),
+                    stats: get_b_or(&v, "stats", false),
+                }
+            }
+            "animation" => {} // already read above
+            "post" => {
+                c.post = Post {
+                    denoise: get_b_or(&v, "denoise", false),
+                    denoise_iterations: get_i_or(&v, "denoise_iterations", 5)
+                        .clamp(0, denoise::MAX_ITERATIONS as i64)
+                        as u32,
+                    denoise_color: get_f_or(&v, "denoise_color", 2.0),
+                    denoise_normal: get_f_or(&v, "denoise_normal", 0.3),
+                    denoise_depth: get_f_or(&v, "denoise_depth", 0.1),
+                    denoise_albedo: get_f_or(&v, "denoise_albedo", 0.1),
                 }
             }
             _ => panic!("unknown key!"),
         }
     }
 
-    Some((c, objs, lights))
+    if c.output.passes.contains(&Pass::Ao) && c.render.ao_samples == 0 {
+        panic!("ambient occlusion pass requested but ao_samples is 0");
+    }
+
+    if let Some((pos, target, yfov)) = gltf_camera {
+        c.world.cam_pos = pos;
+        c.world.camera_target = target;
+
+        // glTF cameras are set by their vertical field of view, which depends on the shape of the image
+        if let Some(y) = yfov {
+            let aspect = c.output.width as f32 / c.output.height as f32;
+            c.world.fov = (2.0 * ((y / 2.0).tan() * aspect).atan()).to_degrees();
+        }
+    }
+
+    c.world.object_names = object_names;
+    c.world.volumes = volumes;
+    c.world.material_names = material_names;
+
+    Ok((c, objs, lights))
 }
@@ -0,0 +1,245 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+    use crate::export;
+
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Writes each of `files` into a fresh directory named after `test`, and returns the directory.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("oxide_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    /// Reads the scene at `path` and writes it back out.
+    fn export(path: &Path) -> String {
+        let (cfg, objs, lights) = read_cfg(path.to_str().unwrap(), None, &[]).unwrap();
+        export::to_toml(&cfg, &objs, &lights)
+    }
+
+    #[test]
+    fn format_test() {
+        let toml = r#"
+            [output]
+            format = "png"
+            width = 64
+            height = 48
+            bits = 8
+
+            [render]
+            max_reflections = 2
+            threads = 1
+            samples = 4
+
+            [world]
+            camera_position = [0, 1, -5]
+            background = [0.1, 0.2, 0.3]
+
+            [materials.white]
+            color = [1, 1, 1]
+            refl = 0.25
+
+            [objects.ball]
+            type = "sphere"
+            center = [0, 1, 0]
+            radius = 1
+            material = "white"
+
+            [lights.sun]
+            type = "point"
+            color = [1, 1, 1]
+            position = [1, 4, -1]
+        "#;
+        let json = r#"{
+            "output": { "format": "png", "width": 64, "height": 48, "bits": 8 },
+            "render": { "max_reflections": 2, "threads": 1, "samples": 4 },
+            "world": { "camera_position": [0, 1, -5], "background": [0.1, 0.2, 0.3] },
+            "materials": { "white": { "color": [1, 1, 1], "refl": 0.25 } },
+            "objects": { "ball": { "type": "sphere", "center": [0, 1, 0], "radius": 1, "material": "white" } },
+            "lights": { "sun": { "type": "point", "color": [1, 1, 1], "position": [1, 4, -1] } }
+        }"#;
+        let yaml = "
+output: { format: png, width: 64, height: 48, bits: 8 }
+render: { max_reflections: 2, threads: 1, samples: 4 }
+world:
+  camera_position: [0, 1, -5]
+  background: [0.1, 0.2, 0.3]
+materials:
+  white: { color: [1, 1, 1], refl: 0.25 }
+objects:
+  ball: { type: sphere, center: [0, 1, 0], radius: 1, material: white }
+lights:
+  sun: { type: point, color: [1, 1, 1], position: [1, 4, -1] }
+";
+        let dir = write_files(
+            "format_test",
+            &[("a.toml", toml), ("a.json", json), ("a.yaml", yaml)],
+        );
+
+        // a scene reads the same whatever it's written in
+        let t = export(&dir.join("a.toml"));
+        assert_eq!(export(&dir.join("a.json")), t);
+        assert_eq!(export(&dir.join("a.yaml")), t);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Reads the scene at `path`, writes it out, reads that back in and writes it out again,
+    /// returning both exports.
+    fn round_trip(path: &Path, dir: &Path) -> (String, String) {
+        let first = export(path);
+        let copy = dir.join("exported.toml");
+        fs::write(&copy, &first).unwrap();
+        (first, export(&copy))
+    }
+
+    #[test]
+    fn include_test() {
+        let tri = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+1 0 0
+0 1 0
+3 0 1 2
+";
+        let props = r#"
+            [materials.white]
+            color = [1, 1, 1]
+            refl = 0
+
+            [objects.tri]
+            type = "mesh"
+            file = "tri.ply"
+            material = "white"
+        "#;
+        let scene = r#"
+            include = "props/props.toml"
+
+            [output]
+            format = "png"
+            width = 64
+            height = 48
+            bits = 8
+
+            [render]
+            max_reflections = 2
+            threads = 1
+
+            [world]
+            camera_position = [0, 1, -5]
+            background = [0.1, 0.2, 0.3]
+        "#;
+        let dir = write_files(
+            "include_test",
+            &[
+                ("props/props.toml", props),
+                ("props/tri.ply", tri),
+                ("scene.toml", scene),
+            ],
+        );
+
+        // a mesh named in an included file is found next to that file
+        let (_, objs, _) = read_cfg(dir.join("scene.toml").to_str().unwrap(), None, &[]).unwrap();
+        assert_eq!(objs.len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let scene = r#"
+            [output]
+            format = "png"
+            width = 64
+            height = 48
+            bits = 8
+
+            [render]
+            max_reflections = 2
+            threads = 1
+            shutter = [0.0, 0.5]
+
+            [world]
+            camera_position = [0.3, 1.1, -5.0]
+            camera_target = [0.0, 0.7, 0.0]
+            background = [0.1, 0.2, 0.3]
+            fog = { density = 0.03, color = [0.9, 0.9, 1.0], height = 1.0, falloff = 0.3 }
+
+            [materials.white]
+            color = [0.9, 0.9, 0.9]
+            refl = 0.1
+
+            [materials.gold]
+            model = "ggx"
+            color = [1.0, 0.8, 0.3]
+            refl = 0.0
+            roughness = 0.3
+            metallic = 1.0
+
+            [objects.floor]
+            type = "plane"
+            point = [0.0, -0.3, 0.0]
+            normal = [0.0, 4.0, -1.0]
+            material = "white"
+
+            [objects.ramp]
+            type = "plane"
+            point = [0.0, 0.0, 9.0]
+            normal = [0.0, 0.9701425, -0.24253562]
+            material = "white"
+
+            [objects.ball]
+            type = "sphere"
+            center = [0.0, 1.0, 0.0]
+            radius = 0.7
+            velocity = [0.3, 0.1, 0.0]
+            material = "gold"
+
+            [objects.tri]
+            type = "mesh"
+            material = "white"
+            positions = [0, 0, 2, 1, 0, 2, 0, 1, 2.3]
+            normals = [0, 0, -3, 0.1, 0, -1, 0, 0.2, -1.7]
+            scale = 1.3
+
+            [objects.blob]
+            type = "sdf"
+            material = "gold"
+            shape = { type = "smooth_union", k = 0.3, center = [2.0, 0.5, 1.0], shapes = [
+                { type = "sphere", radius = 0.6 },
+                { type = "box", size = [0.4, 0.4, 0.4], center = [0.3, 0.2, 0.0] },
+            ] }
+
+            [lights.sun]
+            type = "directional"
+            color = [0.9, 0.9, 0.8]
+            position = [1.0, 3.0, -2.0]
+        "#;
+        let dir = write_files("round_trip_test", &[("scene.toml", scene)]);
+
+        // writing out a scene that was read from an export gives back the same file, down to the last digit
+        let (first, second) = round_trip(&dir.join("scene.toml"), &dir);
+        assert_eq!(first, second);
+        // normals that are already unit length are kept as written, others are only normalized once
+        assert!(first.contains("normal = [0.0, 0.9701425, -0.24253562]"));
+        assert!(first.contains("normal = [0.0, 0.97014254, -0.24253564]"));
+        let (first, second) = round_trip(Path::new("test_scene.toml"), &dir);
+        assert_eq!(first, second);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
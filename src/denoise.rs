@@ -0,0 +1,102 @@
+//! Defines a denoiser that cleans up noisy renders after the fact.
+//!
+//! Uses an edge-avoiding à-trous wavelet filter: the image is blurred several times with an increasingly sparse
+//! kernel, and the depth, normal and albedo of each pixel keep the blur from bleeding across object edges.
+
+use crate::opts::Post;
+use crate::vec::*;
+
+/// Per-pixel information about the first surface hit, used to find edges the denoiser shouldn't blur across.
+pub struct Guides<'a> {
+    /// Distance from the camera to the first hit, or infinity if nothing was hit.
+    pub depth: &'a [Vector],
+    /// Surface normal at the first hit.
+    pub normal: &'a [Vector],
+    /// Base color of the material at the first hit.
+    pub albedo: &'a [Vector],
+}
+
+/// Weights of the 5 taps of the B3 spline kernel along each axis.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// The most filtering passes the denoiser makes. The last one blurs across 2^10 = 1024 pixels, wider than most images.
+pub const MAX_ITERATIONS: u32 = 10;
+
+/// Returns the squared distance between `a` and `b`.
+fn dist2(a: Vector, b: Vector) -> f32 {
+    let d = a - b;
+    d.dot(d)
+}
+
+/// Returns how different two depths are relative to the nearer one. Empty space only matches empty space.
+fn depth_diff(a: f32, b: f32) -> f32 {
+    match (a.is_finite(), b.is_finite()) {
+        (true, true) => (a - b).abs() / a.min(b).max(1e-3),
+        (false, false) => 0.0,
+        _ => f32::INFINITY,
+    }
+}
+
+/// Denoises the `w` by `h` image `color`, guided by the surface information in `g`.
+/// Returns the filtered image.
+pub fn denoise(color: &[Vector], g: &Guides, w: usize, h: usize, cfg: &Post) -> Vec<Vector> {
+    assert_eq!(color.len(), w * h);
+    assert_eq!(g.depth.len(), w * h);
+    assert_eq!(g.normal.len(), w * h);
+    assert_eq!(g.albedo.len(), w * h);
+
+    let mut src = color.to_vec();
+    let mut dst = vec![Vector::zero(3); w * h];
+
+    let inv_c = 1.0 / (cfg.denoise_color * cfg.denoise_color);
+    let inv_n = 1.0 / (cfg.denoise_normal * cfg.denoise_normal);
+    let inv_d = 1.0 / (cfg.denoise_depth * cfg.denoise_depth);
+    let inv_a = 1.0 / (cfg.denoise_albedo * cfg.denoise_albedo);
+
+    for it in 0..cfg.denoise_iterations.min(MAX_ITERATIONS) {
+        // the kernel taps spread further apart every iteration, so large blurs stay cheap
+        let step = 1i32 << it;
+
+        // color differences shrink as the image gets smoother, so tighten the color threshold to match
+        let inv_c = inv_c * (1 << (2 * it)) as f32;
+
+        for y in 0..h as i32 {
+            for x in 0..w as i32 {
+                let p = (y as usize) * w + x as usize;
+
+                let mut sum = Vector::zero(3);
+                let mut wsum = 0.0;
+
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    for (i, kx) in KERNEL.iter().enumerate() {
+                        let qx = x + (i as i32 - 2) * step;
+                        let qy = y + (j as i32 - 2) * step;
+
+                        if qx < 0 || qy < 0 || qx >= w as i32 || qy >= h as i32 {
+                            continue;
+                        }
+
+                        let q = (qy as usize) * w + qx as usize;
+
+                        let dd = depth_diff(g.depth[p].x(), g.depth[q].x());
+                        let e = dist2(src[p], src[q]) * inv_c
+                            + dist2(g.normal[p], g.normal[q]) * inv_n
+                            + dist2(g.albedo[p], g.albedo[q]) * inv_a
+                            + dd * dd * inv_d;
+
+                        let wt = kx * ky * (-e).exp();
+                        sum = sum + src[q] * Vector::from_s(wt, 3);
+                        wsum += wt;
+                    }
+                }
+
+                // the center tap always has a weight of at least 9 / 64, so this never divides by 0
+                dst[p] = sum * Vector::from_s(1.0 / wsum, 3);
+            }
+        }
+
+        std::mem::swap(&mut src, &mut dst);
+    }
+
+    src
+}
@@ -0,0 +1,119 @@
+#[cfg(test)]
+mod tests {
+    use crate::denoise::*;
+    use crate::opts::Post;
+    use crate::rng::Rng;
+    use crate::vec::*;
+
+    fn post() -> Post {
+        Post {
+            denoise: true,
+            denoise_iterations: 3,
+            denoise_color: 0.5,
+            denoise_normal: 0.3,
+            denoise_depth: 0.1,
+            denoise_albedo: 0.1,
+        }
+    }
+
+    #[test]
+    fn flat_test() {
+        // a noisy image of a single flat surface should get much smoother
+        let (w, h) = (16, 16);
+        let mut rng = Rng::new(0);
+        let color: Vec<Vector> = (0..w * h)
+            .map(|_| Vector::from_s(0.4 + 0.2 * rng.next_f32(), 3))
+            .collect();
+
+        let depth = vec![Vector::from_s(1.0, 3); w * h];
+        let normal = vec![Vector::from_3(0.0, 0.0, -1.0); w * h];
+        let albedo = vec![Vector::from_s(1.0, 3); w * h];
+        let g = Guides {
+            depth: &depth,
+            normal: &normal,
+            albedo: &albedo,
+        };
+
+        let spread = |img: &[Vector]| {
+            let max = img.iter().fold(f32::MIN, |m, v| m.max(v.x()));
+            let min = img.iter().fold(f32::MAX, |m, v| m.min(v.x()));
+            max - min
+        };
+
+        let out = denoise(&color, &g, w, h, &post());
+        assert!(spread(&out) < spread(&color) * 0.5);
+    }
+
+    #[test]
+    fn edge_test() {
+        // two surfaces at different depths shouldn't bleed into each other
+        let (w, h) = (16, 16);
+        let color: Vec<Vector> = (0..w * h)
+            .map(|i| Vector::from_s(if i % w < w / 2 { 0.0 } else { 1.0 }, 3))
+            .collect();
+        let depth: Vec<Vector> = (0..w * h)
+            .map(|i| Vector::from_s(if i % w < w / 2 { 1.0 } else { f32::INFINITY }, 3))
+            .collect();
+
+        let normal = vec![Vector::from_3(0.0, 0.0, -1.0); w * h];
+        let albedo = vec![Vector::from_s(1.0, 3); w * h];
+        let g = Guides {
+            depth: &depth,
+            normal: &normal,
+            albedo: &albedo,
+        };
+
+        let out = denoise(&color, &g, w, h, &post());
+        assert!(out[w / 2 - 1].x().abs() < 1e-5);
+        assert!((out[w / 2].x() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn albedo_test() {
+        // an edge between two base colors stays sharp however loose the color threshold is
+        let (w, h) = (16, 16);
+        let half = |a: f32, b: f32| -> Vec<Vector> {
+            (0..w * h)
+                .map(|i| Vector::from_s(if i % w < w / 2 { a } else { b }, 3))
+                .collect()
+        };
+        let color = half(0.0, 1.0);
+        let albedo = half(0.2, 0.8);
+        let depth = vec![Vector::from_s(1.0, 3); w * h];
+        let normal = vec![Vector::from_3(0.0, 0.0, -1.0); w * h];
+        let g = Guides {
+            depth: &depth,
+            normal: &normal,
+            albedo: &albedo,
+        };
+
+        let cfg = Post {
+            denoise_color: 100.0,
+            ..post()
+        };
+        let out = denoise(&color, &g, w, h, &cfg);
+        assert!(out[w / 2 - 1].x().abs() < 1e-5);
+        assert!((out[w / 2].x() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn iterations_test() {
+        // iterations past the limit are ignored instead of overflowing the tap spacing
+        let (w, h) = (4, 4);
+        let color = vec![Vector::from_s(0.5, 3); w * h];
+        let depth = vec![Vector::from_s(1.0, 3); w * h];
+        let normal = vec![Vector::from_3(0.0, 0.0, -1.0); w * h];
+        let g = Guides {
+            depth: &depth,
+            normal: &normal,
+            albedo: &color,
+        };
+
+        let cfg = Post {
+            denoise_iterations: 40,
+            ..post()
+        };
+        let out = denoise(&color, &g, w, h, &cfg);
+        assert!(out.iter().all(|c| (c.x() - 0.5).abs() < 1e-5));
+    }
+}
@@ -12,7 +12,7 @@ pub struct Color {
 }
 
 /// Checks if a pixel can be drawn on the current canvas. Returns the index to write to if the check passes.
-fn check_pixel(ppm: &Matrix<Color>, pixel: (i32, i32)) -> Option<usize> {
+fn check_pixel<T>(ppm: &Matrix<T>, pixel: (i32, i32)) -> Option<usize> {
     // convert bounds from [-n/2, n/2] to [0, n]
     let ax = pixel.0 + ppm.rlen as i32 / 2;
     let ay = -pixel.1 + ppm.clen as i32 / 2; // y direction needs to be flipped because the canvas y direction goes top to bottom
@@ -31,18 +31,25 @@ fn check_pixel(ppm: &Matrix<Color>, pixel: (i32, i32)) -> Option<usize> {
 /// Draws a pixel on `ppm`.
 /// `pixel` contains coordinates going from `-ppm.rlen/2` to `ppm.rlen/2` and `-ppm.clen/2` to `ppm.clen/2` respectively.
 /// Calls where `pixel.0` or `pixel.1` maps to a value outside the corresponding limit in `ppm` will be silently ignored.
-pub fn draw_pixel(ppm: &mut Matrix<Color>, pixel: (i32, i32), color: Color) {
+/// Works on any kind of pixel, so extra passes holding floats can be drawn the same way as the final image.
+pub fn draw_pixel<T>(ppm: &mut Matrix<T>, pixel: (i32, i32), color: T) {
     if let Some(idx) = check_pixel(ppm, pixel) {
         ppm.mat[idx] = color;
     }
 }
 
+/// Reads a pixel from `ppm`, using the same coordinates as `draw_pixel`.
+/// Returns `None` if the coordinates are outside the canvas.
+pub fn read_pixel<T: Copy>(ppm: &Matrix<T>, pixel: (i32, i32)) -> Option<T> {
+    check_pixel(ppm, pixel).map(|idx| ppm.mat[idx])
+}
+
 /// Maps a float vector to a concrete color type.
 pub fn map_color(c: Vector) -> Color {
     Color {
-        r: (c.get()[0] * u8::max_value() as f32) as u8,
-        g: (c.get()[1] * u8::max_value() as f32) as u8,
-        b: (c.get()[2] * u8::max_value() as f32) as u8,
+        r: (c.get()[0] * u8::MAX as f32) as u8,
+        g: (c.get()[1] * u8::MAX as f32) as u8,
+        b: (c.get()[2] * u8::MAX as f32) as u8,
     }
 }
 
@@ -0,0 +1,354 @@
+//! Defines environment maps, which surround the scene with an image instead of a constant background color.
+//!
+//! Maps are stored in equirectangular (latitude-longitude) layout and can be read from Radiance RGBE (.hdr) and
+//! portable float map (.pfm) files.
+
+use crate::mat::Matrix;
+use crate::vec::*;
+
+use std::f32::consts::PI;
+use std::fs;
+
+/// An equirectangular environment map, along with the tables needed to importance sample it.
+pub struct EnvMap {
+    /// The image, stored as rows going from the top (+y) of the sphere to the bottom.
+    pub img: Matrix<Vector>,
+    /// Scale factor applied to every lookup.
+    pub intensity: f32,
+    /// File the map was loaded from, if any.
+    pub source: Option<String>,
+    /// Cumulative distribution over rows, normalized so the last entry is 1.
+    row_cdf: Vec<f32>,
+    /// Cumulative distribution over columns within each row, normalized so the last entry of each row is 1.
+    col_cdf: Vec<f32>,
+    /// Probability of picking each pixel.
+    pix_pdf: Vec<f32>,
+}
+
+/// Returns the luminance of a linear RGB color.
+fn luminance(c: Vector) -> f32 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+/// Returns the index of the first entry in the sorted slice `cdf` that is larger than `u`.
+fn search(cdf: &[f32], u: f32) -> usize {
+    cdf.partition_point(|&c| c <= u).min(cdf.len() - 1)
+}
+
+impl EnvMap {
+    /// Loads an environment map from a .hdr or .pfm file, picking the format from the file extension.
+    pub fn load(path: &str, intensity: f32) -> EnvMap {
+        let buf = fs::read(path).expect("could not read environment map");
+
+        let img = if path.ends_with(".hdr") {
+            parse_hdr(&buf)
+        } else if path.ends_with(".pfm") {
+            parse_pfm(&buf)
+        } else {
+            panic!("unknown environment map format");
+        };
+
+        EnvMap {
+            source: Some(path.to_string()),
+            ..EnvMap::new(img, intensity)
+        }
+    }
+
+    /// Creates an environment map from an equirectangular image.
+    pub fn new(img: Matrix<Vector>, intensity: f32) -> EnvMap {
+        let (w, h) = (img.rlen, img.clen);
+        assert!(w > 0 && h > 0);
+
+        // weight pixels by brightness, and by the solid angle they cover since rows near the poles get squashed
+        let mut col_cdf = vec![0.0; w * h];
+        let mut row_cdf = vec![0.0; h];
+        let mut pix_pdf = vec![0.0; w * h];
+        let mut total = 0.0;
+
+        for y in 0..h {
+            let sin = (PI * (y as f32 + 0.5) / h as f32).sin();
+            let mut row = 0.0;
+
+            for x in 0..w {
+                let wt = luminance(img.mat[y * w + x]).max(0.0) * sin;
+                pix_pdf[y * w + x] = wt;
+                row += wt;
+                col_cdf[y * w + x] = row;
+            }
+
+            for c in &mut col_cdf[y * w..(y + 1) * w] {
+                *c = if row > 0.0 { *c / row } else { 1.0 };
+            }
+
+            total += row;
+            row_cdf[y] = total;
+        }
+
+        for c in &mut row_cdf {
+            *c = if total > 0.0 { *c / total } else { 1.0 };
+        }
+
+        for p in &mut pix_pdf {
+            *p = if total > 0.0 { *p / total } else { 0.0 };
+        }
+
+        EnvMap {
+            img,
+            intensity,
+            source: None,
+            row_cdf,
+            col_cdf,
+            pix_pdf,
+        }
+    }
+
+    /// Returns the image coordinates (from 0 to 1 on each axis) that direction `d` maps to.
+    pub fn to_uv(d: Vector) -> (f32, f32) {
+        let d = d.norm();
+        let u = 0.5 + d.x().atan2(d.z()) / (2.0 * PI);
+        let v = d.y().clamp(-1.0, 1.0).acos() / PI;
+
+        (u, v)
+    }
+
+    /// Returns the direction that the image coordinates `uv` map to. The inverse of `to_uv`.
+    pub fn from_uv(uv: (f32, f32)) -> Vector {
+        let (sin_p, cos_p) = ((uv.0 - 0.5) * 2.0 * PI).sin_cos();
+        let (sin_t, cos_t) = (uv.1 * PI).sin_cos();
+
+        Vector::from_3(sin_t * sin_p, cos_t, sin_t * cos_p)
+    }
+
+    /// Returns the light arriving from direction `d`, interpolated between the nearest pixels.
+    pub fn lookup(&self, d: Vector) -> Vector {
+        let (w, h) = (self.img.rlen, self.img.clen);
+        let (u, v) = EnvMap::to_uv(d);
+
+        let fx = u * w as f32 - 0.5;
+        let fy = (v * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+
+        let x0 = fx.floor();
+        let y0 = fy.floor();
+        let (tx, ty) = (fx - x0, fy - y0);
+
+        // wrap around horizontally, clamp vertically
+        let x0 = (x0 as i32).rem_euclid(w as i32) as usize;
+        let x1 = (x0 + 1) % w;
+        let y0 = y0 as usize;
+        let y1 = (y0 + 1).min(h - 1);
+
+        let px = |x, y| self.img.mat[y * w + x];
+        let top = Vector::lerp(px(x1, y0), px(x0, y0), tx);
+        let bot = Vector::lerp(px(x1, y1), px(x0, y1), tx);
+
+        Vector::lerp(bot, top, ty) * Vector::from_s(self.intensity, 3)
+    }
+
+    /// Picks a direction with probability roughly proportional to how much light arrives from it, using `u`,
+    /// a pair of uniformly distributed numbers in [0, 1).
+    /// Returns the direction, the light arriving from it, and the probability density (per unit solid angle) of picking it.
+    pub fn sample(&self, u: (f32, f32)) -> Option<(Vector, Vector, f32)> {
+        let (w, h) = (self.img.rlen, self.img.clen);
+
+        let y = search(&self.row_cdf, u.0);
+        let x = search(&self.col_cdf[y * w..(y + 1) * w], u.1);
+
+        let pdf = self.pix_pdf[y * w + x];
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        let v = (y as f32 + 0.5) / h as f32;
+        let d = EnvMap::from_uv(((x as f32 + 0.5) / w as f32, v));
+
+        // convert from the probability of picking a pixel to a density over the sphere of directions
+        let sin = (PI * v).sin();
+        let pdf = pdf * (w * h) as f32 / (2.0 * PI * PI * sin);
+
+        Some((
+            d,
+            self.img.mat[y * w + x] * Vector::from_s(self.intensity, 3),
+            pdf,
+        ))
+    }
+}
+
+/// Splits a header into the next whitespace separated token and the remaining bytes.
+fn next_token(buf: &[u8]) -> (&str, &[u8]) {
+    let start = buf
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(buf.len());
+    let end = buf[start..]
+        .iter()
+        .position(|b| b.is_ascii_whitespace())
+        .map_or(buf.len(), |e| start + e);
+
+    let tok = std::str::from_utf8(&buf[start..end]).expect("could not read image header");
+    (tok, &buf[end..])
+}
+
+/// Decodes a portable float map (.pfm) into a matrix of colors.
+pub fn parse_pfm(buf: &[u8]) -> Matrix<Vector> {
+    let (magic, rest) = next_token(buf);
+    let (w, rest) = next_token(rest);
+    let (h, rest) = next_token(rest);
+    let (scale, rest) = next_token(rest);
+
+    let channels = match magic {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => panic!("invalid .pfm image header"),
+    };
+
+    let w: usize = w.parse().expect("invalid .pfm image width");
+    let h: usize = h.parse().expect("invalid .pfm image height");
+    let scale: f32 = scale.parse().expect("invalid .pfm image scale");
+
+    // exactly one whitespace character separates the header from the data
+    let data = &rest[1..];
+    assert!(
+        data.len() >= w * h * channels * 4,
+        "truncated .pfm image content"
+    );
+
+    // a negative scale means the data is little endian
+    let read = |i: usize| -> f32 {
+        let b = [
+            data[i * 4],
+            data[i * 4 + 1],
+            data[i * 4 + 2],
+            data[i * 4 + 3],
+        ];
+        if scale < 0.0 {
+            f32::from_le_bytes(b)
+        } else {
+            f32::from_be_bytes(b)
+        }
+    };
+
+    let mut mat = Vec::with_capacity(w * h);
+
+    // rows are stored from the bottom of the image to the top
+    for y in (0..h).rev() {
+        for x in 0..w {
+            let i = (y * w + x) * channels;
+            mat.push(if channels == 3 {
+                Vector::from_3(read(i), read(i + 1), read(i + 2))
+            } else {
+                Vector::from_s(read(i), 3)
+            });
+        }
+    }
+
+    Matrix {
+        mat,
+        rlen: w,
+        clen: h,
+    }
+}
+
+/// Decodes a single RGBE pixel into a color.
+fn rgbe(p: &[u8]) -> Vector {
+    if p[3] == 0 {
+        return Vector::zero(3);
+    }
+
+    // the exponent is shared by all channels, and the mantissas are stored as 8 bit fractions
+    let f = 2.0f32.powi(p[3] as i32 - 136);
+    Vector::from_3(p[0] as f32 * f, p[1] as f32 * f, p[2] as f32 * f)
+}
+
+/// Decodes a Radiance RGBE (.hdr) image into a matrix of colors.
+/// Supports both flat and run length encoded scanlines.
+pub fn parse_hdr(buf: &[u8]) -> Matrix<Vector> {
+    assert!(
+        buf.starts_with(b"#?RADIANCE") || buf.starts_with(b"#?RGBE"),
+        "invalid .hdr image header"
+    );
+
+    // the header is a list of lines ended by an empty line, followed by the resolution
+    let mut pos = 0;
+    loop {
+        let end = buf[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .expect("truncated .hdr image header");
+        let line = &buf[pos..pos + end];
+        pos += end + 1;
+
+        if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+            panic!("unsupported .hdr pixel format");
+        }
+
+        if line.is_empty() {
+            break;
+        }
+    }
+
+    let (ny, rest) = next_token(&buf[pos..]);
+    let (h, rest) = next_token(rest);
+    let (nx, rest) = next_token(rest);
+    let (w, rest) = next_token(rest);
+
+    if ny != "-Y" || nx != "+X" {
+        panic!("unsupported .hdr image orientation");
+    }
+
+    let w: usize = w.parse().expect("invalid .hdr image width");
+    let h: usize = h.parse().expect("invalid .hdr image height");
+    let mut data = &rest[1..];
+
+    let mut mat = Vec::with_capacity(w * h);
+    let mut line = vec![0u8; w * 4];
+
+    for _ in 0..h {
+        let rle = (8..0x8000).contains(&w)
+            && data.len() >= 4
+            && data[0] == 2
+            && data[1] == 2
+            && ((data[2] as usize) << 8 | data[3] as usize) == w;
+
+        if rle {
+            data = &data[4..];
+
+            // each channel is stored separately as a sequence of runs and literal spans
+            for c in 0..4 {
+                let mut x = 0;
+                while x < w {
+                    let n = data[0] as usize;
+                    if n > 128 {
+                        let n = n - 128;
+                        assert!(x + n <= w, "invalid .hdr run length");
+                        for i in 0..n {
+                            line[(x + i) * 4 + c] = data[1];
+                        }
+                        data = &data[2..];
+                        x += n;
+                    } else {
+                        assert!(n > 0 && x + n <= w, "invalid .hdr run length");
+                        for i in 0..n {
+                            line[(x + i) * 4 + c] = data[1 + i];
+                        }
+                        data = &data[1 + n..];
+                        x += n;
+                    }
+                }
+            }
+        } else {
+            assert!(data.len() >= w * 4, "truncated .hdr image content");
+            line.copy_from_slice(&data[..w * 4]);
+            data = &data[w * 4..];
+        }
+
+        for p in line.chunks(4) {
+            mat.push(rgbe(p));
+        }
+    }
+
+    Matrix {
+        mat,
+        rlen: w,
+        clen: h,
+    }
+}
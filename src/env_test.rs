@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use crate::env::*;
+    use crate::mat::*;
+    use crate::vec::*;
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn uv_test() {
+        let d = Vector::from_3(0.3, -0.5, 0.8).norm();
+        let d2 = EnvMap::from_uv(EnvMap::to_uv(d));
+
+        assert!(approx(d.x(), d2.x()) && approx(d.y(), d2.y()) && approx(d.z(), d2.z()));
+
+        // straight ahead maps to the middle of the image
+        let (u, v) = EnvMap::to_uv(Vector::from_3(0.0, 0.0, 1.0));
+        assert!(approx(u, 0.5) && approx(v, 0.5));
+    }
+
+    #[test]
+    fn pfm_test() {
+        let mut buf = b"PF\n2 1\n-1.0\n".to_vec();
+        for f in [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0] {
+            buf.extend_from_slice(&f.to_le_bytes());
+        }
+
+        let img = parse_pfm(&buf);
+
+        assert_eq!((img.rlen, img.clen), (2, 1));
+        assert_eq!(img.mat[1], Vector::from_3(4.0, 5.0, 6.0));
+    }
+
+    #[test]
+    fn hdr_test() {
+        let mut buf = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+
+        // one run length encoded scanline: a run of 8 for red, literals for green, runs for blue and the exponent
+        buf.extend_from_slice(&[2, 2, 0, 8]);
+        buf.extend_from_slice(&[136, 128]);
+        buf.extend_from_slice(&[8, 0, 32, 64, 96, 128, 160, 192, 224]);
+        buf.extend_from_slice(&[136, 0]);
+        buf.extend_from_slice(&[136, 129]);
+
+        let img = parse_hdr(&buf);
+
+        assert_eq!((img.rlen, img.clen), (8, 1));
+        assert_eq!(img.mat[0], Vector::from_3(1.0, 0.0, 0.0));
+        assert_eq!(img.mat[2], Vector::from_3(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn sample_test() {
+        // a dark map with a single bright pixel should almost always be sampled at that pixel
+        let mut mat = vec![Vector::from_s(0.001, 3); 16 * 8];
+        mat[3 * 16 + 5] = Vector::from_s(100.0, 3);
+
+        let env = EnvMap::new(
+            Matrix {
+                mat,
+                rlen: 16,
+                clen: 8,
+            },
+            1.0,
+        );
+
+        let (d, c, pdf) = env.sample((0.5, 0.5)).unwrap();
+        let (u, v) = EnvMap::to_uv(d);
+
+        assert_eq!(((u * 16.0) as usize, (v * 8.0) as usize), (5, 3));
+        assert_eq!(c, Vector::from_s(100.0, 3));
+        assert!(pdf > 0.0);
+    }
+}
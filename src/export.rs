@@ -0,0 +1,504 @@
+//! Defines how scenes held in memory are written back out as .toml scene files.
+//!
+//! Scenes are written in a canonical form: every option is spelled out with its default filled in,
+//! tables are sorted by name, and numbers are written as the shortest decimal that reads back the same.
+//! Anything the renderer resolves while loading (animated values, prefabs, includes) is written as its result.
+//! Animated scenes are written as the single frame that was read, without their `[animation]` table or keyframes.
+
+use crate::bsdf::Model;
+use crate::medium::{Density, Volume};
+use crate::mesh::Texture;
+use crate::opts::*;
+use crate::output::{Output, PNG};
+use crate::ray::{Light, LightType, Material, Object, RayInteraction};
+use crate::scene::{self, Table, Value};
+use crate::sdf::Shape;
+use crate::vec::Vector;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Textures that have to be written to files of their own, each with the name of its file.
+type Images = Vec<(String, Arc<Texture>)>;
+
+/// Turns a float into a .toml number, without the noise that comes from widening it to 64 bits.
+pub fn num(x: f32) -> Value {
+    // toml writes negative zero as "--0.0", which can't be read back in
+    let x = if x == 0.0 { 0.0 } else { x };
+    match x.is_finite() {
+        true => Value::Float(x.to_string().parse().expect("could not cast into float")),
+        false => Value::Float(x as f64),
+    }
+}
+
+/// Turns a Vector with 3 elements into a .toml array.
+pub fn vec3(v: Vector) -> Value {
+    Value::Array(vec![num(v.x()), num(v.y()), num(v.z())])
+}
+
+/// Returns a .toml table holding `entries`.
+pub fn table(entries: Vec<(&str, Value)>) -> Value {
+    Value::Table(
+        entries
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+    )
+}
+
+/// Adds the velocity of an object that moves by `motion` while the shutter is open for `secs` seconds to its table `t`.
+/// Objects that don't move, or scenes without motion blur, don't get one.
+fn add_velocity(t: &mut Table, motion: Vector, secs: f32) {
+    if secs > 0.0 && motion != Vector::zero(3) {
+        t.insert(
+            "velocity".to_string(),
+            vec3(motion * Vector::from_s(1.0 / secs, 3)),
+        );
+    }
+}
+
+/// Describes a signed distance field shape as a table, the way scene files do.
+pub fn shape_toml(s: &Shape) -> Value {
+    let list = |s: &[Shape]| Value::Array(s.iter().map(shape_toml).collect());
+
+    match s {
+        Shape::Sphere { r } => table(vec![("type", "sphere".into()), ("radius", num(*r))]),
+        Shape::Cuboid { b } => table(vec![("type", "box".into()), ("size", vec3(*b))]),
+        Shape::Torus { r } => table(vec![
+            ("type", "torus".into()),
+            ("radii", Value::Array(vec![num(r.0), num(r.1)])),
+        ]),
+        Shape::Capsule { a, b, r } => table(vec![
+            ("type", "capsule".into()),
+            ("a", vec3(*a)),
+            ("b", vec3(*b)),
+            ("radius", num(*r)),
+        ]),
+        Shape::Translate { off, s } => {
+            // shapes only have one center, so moving a moved shape adds the two together
+            let mut t = shape_toml(s);
+            let c = match t.get("center") {
+                Some(c) => {
+                    let a = c.as_array().expect("could not cast into array");
+                    let f = |i: usize| a[i].as_float().expect("could not cast into float") as f32;
+                    *off + Vector::from_3(f(0), f(1), f(2))
+                }
+                None => *off,
+            };
+            t.as_table_mut()
+                .expect("could not cast into table")
+                .insert("center".to_string(), vec3(c));
+            t
+        }
+        Shape::Union(s) => table(vec![("type", "union".into()), ("shapes", list(s))]),
+        Shape::SmoothUnion { k, s } => table(vec![
+            ("type", "smooth_union".into()),
+            ("k", num(*k)),
+            ("shapes", list(s)),
+        ]),
+        Shape::Intersect(s) => table(vec![("type", "intersection".into()), ("shapes", list(s))]),
+        Shape::Subtract(s) => table(vec![("type", "difference".into()), ("shapes", list(s))]),
+        Shape::Twist { k, s } => table(vec![
+            ("type", "twist".into()),
+            ("k", num(*k)),
+            ("shape", shape_toml(s)),
+        ]),
+        Shape::Repeat { period, s } => table(vec![
+            ("type", "repeat".into()),
+            ("period", vec3(*period)),
+            ("shape", shape_toml(s)),
+        ]),
+    }
+}
+
+/// Describes an object as a table like the ones in the `[objects]` section of scene files, without its material.
+/// Objects moving while the shutter is open, for `secs` seconds, get the velocity they move at.
+/// A texture that didn't come from a file of its own is named `image` instead, and added to `images` to be written there.
+fn object_toml(obj: &dyn RayInteraction, secs: f32, image: &str, images: &mut Images) -> Table {
+    let mut t = Table::new();
+    let motion = match obj.describe() {
+        Object::Plane(pl) => {
+            t.insert("type".to_string(), "plane".into());
+            t.insert("point".to_string(), vec3(pl.p));
+            t.insert("normal".to_string(), vec3(pl.n));
+            pl.motion
+        }
+        Object::Sphere(s) => {
+            t.insert("type".to_string(), "sphere".into());
+            t.insert("center".to_string(), vec3(s.c));
+            t.insert("radius".to_string(), num(s.r));
+            s.motion
+        }
+        Object::Sdf(s) => {
+            t.insert("type".to_string(), "sdf".into());
+            t.insert("shape".to_string(), shape_toml(&s.shape));
+            t.insert("step".to_string(), num(s.step));
+            t.insert("max_steps".to_string(), Value::Integer(s.max_steps as i64));
+            t.insert("max_dist".to_string(), num(s.max_dist));
+            s.motion
+        }
+        Object::Mesh(m) => {
+            let flat = |vs: &mut dyn Iterator<Item = f32>| Value::Array(vs.map(num).collect());
+            let tris = m.triangles();
+
+            t.insert("type".to_string(), "mesh".into());
+            t.insert(
+                "positions".to_string(),
+                flat(
+                    &mut tris
+                        .iter()
+                        .flat_map(|tri| tri.p.iter().flat_map(|p| [p.x(), p.y(), p.z()])),
+                ),
+            );
+            if tris.iter().all(|tri| tri.n.is_some()) {
+                let ns = tris.iter().flat_map(|tri| tri.n.unwrap());
+                t.insert(
+                    "normals".to_string(),
+                    flat(&mut ns.flat_map(|n| [n.x(), n.y(), n.z()])),
+                );
+            }
+            if tris.iter().all(|tri| tri.uv.is_some()) {
+                let uvs = tris.iter().flat_map(|tri| tri.uv.unwrap());
+                t.insert("uvs".to_string(), flat(&mut uvs.flat_map(|(u, v)| [u, v])));
+            }
+            if tris.iter().all(|tri| tri.color.is_some()) {
+                let cs = tris.iter().flat_map(|tri| tri.color.unwrap());
+                t.insert(
+                    "colors".to_string(),
+                    flat(&mut cs.flat_map(|c| [c.x(), c.y(), c.z()])),
+                );
+            }
+            if let Some(tex) = &m.texture {
+                let file = tex.source.clone().unwrap_or_else(|| {
+                    images.push((image.to_string(), Arc::clone(tex)));
+                    image.to_string()
+                });
+                t.insert("texture".to_string(), file.into());
+            }
+            m.motion
+        }
+    };
+    add_velocity(&mut t, motion, secs);
+    t
+}
+
+/// Describes a material as a table, the way scene files do.
+fn material_toml(m: &Material) -> Value {
+    let mut t = vec![
+        ("color", vec3(m.color)),
+        ("refl", num(m.refl)),
+        ("roughness", num(m.roughness)),
+        ("emission", vec3(m.emission)),
+        ("transmission", vec3(m.transmission)),
+    ];
+
+    match m.bsdf {
+        Model::Lambert(_) => t.push(("model", "lambert".into())),
+        Model::Phong(p) => {
+            t.push(("model", "phong".into()));
+            t.push(("spec", num(p.exponent)));
+        }
+        Model::BlinnPhong(b) => {
+            t.push(("model", "blinn_phong".into()));
+            t.push(("spec", num(b.exponent)));
+            t.push(("specular", num(b.specular)));
+        }
+        Model::Ggx(g) => {
+            t.push(("model", "ggx".into()));
+            t.push(("metallic", num(g.metallic)));
+        }
+    }
+
+    table(t)
+}
+
+/// Describes a volume as a table, the way the `[objects]` section of scene files does.
+fn volume_toml(v: &Volume) -> Value {
+    let field = match &v.medium.field {
+        Density::Constant => table(vec![("type", "constant".into())]),
+        Density::Noise {
+            scale,
+            octaves,
+            threshold,
+            seed,
+        } => table(vec![
+            ("type", "noise".into()),
+            ("scale", num(*scale)),
+            ("octaves", Value::Integer(*octaves as i64)),
+            ("threshold", num(*threshold)),
+            ("seed", Value::Integer(*seed as i64)),
+        ]),
+        Density::Grid {
+            res,
+            values,
+            min,
+            max,
+        } => table(vec![
+            ("type", "grid".into()),
+            (
+                "resolution",
+                Value::Array(
+                    [res.0, res.1, res.2]
+                        .iter()
+                        .map(|r| Value::Integer(*r as i64))
+                        .collect(),
+                ),
+            ),
+            (
+                "values",
+                Value::Array(values.iter().map(|f| num(*f)).collect()),
+            ),
+            ("min", vec3(*min)),
+            ("max", vec3(*max)),
+        ]),
+    };
+
+    table(vec![
+        ("type", "volume".into()),
+        ("shape", shape_toml(&v.shape)),
+        ("absorption", vec3(v.medium.absorption)),
+        ("scattering", vec3(v.medium.scattering)),
+        ("anisotropy", num(v.medium.g)),
+        ("density", num(v.medium.density)),
+        ("step", num(v.step)),
+        ("field", field),
+    ])
+}
+
+/// Returns `name` if there is one, or a name made up from `prefix` and the index `i` out of `n` otherwise.
+/// Made up names are zero padded so they sort in the same order as their indices.
+fn name_or(name: Option<&String>, prefix: &str, i: usize, n: usize) -> String {
+    match name {
+        Some(name) => name.clone(),
+        None => format!("{}_{:0w$}", prefix, i, w = n.to_string().len()),
+    }
+}
+
+/// Writes the scene made of `cfg`, the objects `objs` and the lights `lights` out as the text of a .toml scene file,
+/// which reads back in as the same scene. Animated scenes are written as the frame `cfg` was read at.
+/// Objects and materials are named after the names in `cfg.world`, or made up names if there aren't any.
+/// Only materials used by objects are written, since those are the only ones the scene holds on to.
+/// Panics if a texture didn't come from a file of its own, like those embedded in glTF files, since it has nowhere
+/// to go. Use `write` for those.
+pub fn to_toml(
+    cfg: &Config,
+    objs: &[Box<dyn RayInteraction + Send + Sync>],
+    lights: &[Light],
+) -> String {
+    let (text, images) = scene_toml(cfg, objs, lights, "texture");
+    assert!(
+        images.is_empty(),
+        "scene has textures without files of their own, which need to be written next to the scene"
+    );
+    text
+}
+
+/// Writes the scene made of `cfg`, the objects `objs` and the lights `lights` to the .toml scene file at `path`,
+/// the same way as `to_toml`. Textures that didn't come from files of their own are written next to it
+/// as PNG images named "<scene>_<object>.png".
+pub fn write(
+    path: &Path,
+    cfg: &Config,
+    objs: &[Box<dyn RayInteraction + Send + Sync>],
+    lights: &[Light],
+) -> io::Result<()> {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("scene");
+    let (text, images) = scene_toml(cfg, objs, lights, stem);
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    for (file, tex) in images {
+        // textures hold the 8 bit colors they were read as, so they're written back out exactly
+        let bytes: Vec<u8> = tex
+            .img
+            .mat
+            .iter()
+            .flat_map(|c| [c.x(), c.y(), c.z()])
+            .map(|x| (x * 255.0).round().clamp(0.0, 255.0) as u8)
+            .collect();
+        let name = dir.join(file).to_str().expect("invalid path").to_string();
+        PNG::new(name, tex.img.rlen, tex.img.clen, 8).write(&bytes);
+    }
+
+    fs::write(path, text)
+}
+
+/// Writes the scene out as the text of a .toml scene file, along with the textures that need files of their own.
+/// Those are named after `prefix` and the object using them.
+fn scene_toml(
+    cfg: &Config,
+    objs: &[Box<dyn RayInteraction + Send + Sync>],
+    lights: &[Light],
+    prefix: &str,
+) -> (String, Images) {
+    let mut root = Table::new();
+    let (o, r, w, p) = (&cfg.output, &cfg.render, &cfg.world, &cfg.post);
+
+    let mut output = vec![
+        (
+            "format",
+            match o.format {
+                Format::Png => "png",
+                Format::Ppm => "ppm",
+            }
+            .into(),
+        ),
+        ("width", Value::Integer(o.width as i64)),
+        ("height", Value::Integer(o.height as i64)),
+        ("bits", Value::Integer(o.bits as i64)),
+        (
+            "passes",
+            Value::Array(o.passes.iter().map(|p| p.name().into()).collect()),
+        ),
+        ("preview_passes", Value::Integer(o.preview_passes as i64)),
+        ("preview_seconds", num(o.preview_seconds)),
+        ("sample_heatmap", Value::Boolean(o.sample_heatmap)),
+        ("stats", Value::Boolean(o.stats)),
+    ];
+    if let Some(f) = &o.progress_file {
+        output.push(("progress_file", f.as_str().into()));
+    }
+    root.insert("output".to_string(), table(output));
+
+    // scenes without animation run at 24 frames per second, so the shutter of an animated scene is stretched
+    // to stay open for just as long once it's written without its frame rate
+    let fps = cfg.animation.map_or(24.0, |a| a.fps);
+    let shutter = (r.shutter.0 * 24.0 / fps, r.shutter.1 * 24.0 / fps);
+    let secs = (r.shutter.1 - r.shutter.0) / fps;
+
+    root.insert(
+        "render".to_string(),
+        table(vec![
+            ("max_reflections", Value::Integer(r.max_reflections as i64)),
+            (
+                "threads",
+                match r.threads {
+                    Some(n) => Value::Integer(n as i64),
+                    None => "auto".into(),
+                },
+            ),
+            ("samples", Value::Integer(r.samples as i64)),
+            ("time_limit", num(r.time_limit)),
+            ("noise_threshold", num(r.noise_threshold)),
+            ("adaptive_threshold", num(r.adaptive_threshold)),
+            (
+                "adaptive_min_samples",
+                Value::Integer(r.adaptive_min_samples as i64),
+            ),
+            ("sampler", r.sampler.name().into()),
+            ("filter", r.filter.name().into()),
+            ("filter_radius", num(r.filter_radius)),
+            (
+                "shutter",
+                Value::Array(vec![num(shutter.0), num(shutter.1)]),
+            ),
+            ("light_samples", Value::Integer(r.light_samples as i64)),
+            ("env_samples", Value::Integer(r.env_samples as i64)),
+            ("gloss_samples", Value::Integer(r.gloss_samples as i64)),
+            ("ao_samples", Value::Integer(r.ao_samples as i64)),
+            ("ao_distance", num(r.ao_distance)),
+        ]),
+    );
+
+    let mut world = vec![
+        ("camera_position", vec3(w.cam_pos)),
+        ("camera_target", vec3(w.camera_target)),
+        ("fov", num(w.fov)),
+    ];
+
+    // scenes without fog leave it out, which is also how they're written
+    if w.fog.density > 0.0 {
+        world.push((
+            "fog",
+            table(vec![
+                ("density", num(w.fog.density)),
+                ("color", vec3(w.fog.color)),
+                ("height", num(w.fog.height)),
+                ("falloff", num(w.fog.falloff)),
+            ]),
+        ));
+    }
+    match w
+        .environment
+        .as_ref()
+        .and_then(|e| e.source.as_ref().map(|s| (e, s)))
+    {
+        Some((env, source)) => {
+            world.push(("background", source.as_str().into()));
+            world.push(("background_intensity", num(env.intensity)));
+        }
+        None => world.push(("background", vec3(w.background))),
+    }
+    root.insert("world".to_string(), table(world));
+
+    root.insert(
+        "post".to_string(),
+        table(vec![
+            ("denoise", Value::Boolean(p.denoise)),
+            (
+                "denoise_iterations",
+                Value::Integer(p.denoise_iterations as i64),
+            ),
+            ("denoise_color", num(p.denoise_color)),
+            ("denoise_normal", num(p.denoise_normal)),
+            ("denoise_depth", num(p.denoise_depth)),
+            ("denoise_albedo", num(p.denoise_albedo)),
+        ]),
+    );
+
+    let n_mats = w.material_names.len();
+    let mut materials = Table::new();
+    let mut objects = Table::new();
+    let mut images = vec![];
+
+    for (i, obj) in objs.iter().enumerate() {
+        let m = obj.base_material();
+        let mat_name = name_or(w.material_names.get(m.id), "material", m.id, n_mats);
+        materials
+            .entry(mat_name.clone())
+            .or_insert_with(|| material_toml(&m));
+
+        let name = name_or(w.object_names.get(i), "object", i, objs.len());
+        let image = format!("{}_{}.png", prefix, name);
+        let mut t = object_toml(obj.as_ref(), secs, &image, &mut images);
+        t.insert("material".to_string(), mat_name.into());
+        objects.insert(name, Value::Table(t));
+    }
+    for (i, v) in w.volumes.iter().enumerate() {
+        objects.insert(name_or(None, "volume", i, w.volumes.len()), volume_toml(v));
+    }
+    root.insert("materials".to_string(), Value::Table(materials));
+    root.insert("objects".to_string(), Value::Table(objects));
+
+    let mut light_map = Table::new();
+    for (i, l) in lights.iter().enumerate() {
+        let mut t = vec![("color", vec3(l.color))];
+        match l.kind {
+            LightType::Ambient => t.push(("type", "ambient".into())),
+            LightType::Directional(d) => {
+                t.push(("type", "directional".into()));
+                t.push(("position", vec3(d)));
+            }
+            LightType::Point(p) => {
+                t.push(("type", "point".into()));
+                t.push(("position", vec3(p)));
+            }
+        }
+        light_map.insert(name_or(None, "light", i, lights.len()), table(t));
+    }
+    root.insert("lights".to_string(), Value::Table(light_map));
+
+    let text = scene::to_toml(&Value::Table(root));
+
+    // put a blank line before every table header, which the toml writer only does some of the time
+    let mut out = String::new();
+    for line in text.lines() {
+        if line.starts_with('[') && !out.is_empty() && !out.ends_with("\n\n") {
+            out.push('\n');
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    (out, images)
+}
@@ -0,0 +1,182 @@
+#[cfg(test)]
+mod tests {
+    use crate::bsdf::*;
+    use crate::export::*;
+    use crate::mat::Matrix;
+    use crate::mesh::*;
+    use crate::opts::*;
+    use crate::ray::*;
+    use crate::sdf::*;
+    use crate::vec::*;
+
+    use crate::scene::{self, Value};
+
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    /// Reads the text of a written scene back in.
+    fn read(text: &str) -> Value {
+        scene::parse(Path::new("scene.toml"), text).expect("written scene doesn't parse")
+    }
+
+    fn material(id: usize) -> Material {
+        Material {
+            color: Vector::from_3(0.1, 0.2, 0.3),
+            bsdf: Model::Ggx(Ggx {
+                roughness: 0.4,
+                metallic: 1.0,
+            }),
+            refl: 0.5,
+            roughness: 0.4,
+            emission: Vector::zero(3),
+            transmission: Vector::zero(3),
+            id,
+        }
+    }
+
+    #[test]
+    fn num_test() {
+        // widening 0.1f32 straight to f64 gives 0.10000000149011612
+        assert_eq!(num(0.1), Value::Float(0.1));
+        assert_eq!(num(-2.5), Value::Float(-2.5));
+        assert_eq!(scene::to_toml(&table(vec![("x", num(-0.0))])), "x = 0.0\n");
+    }
+
+    #[test]
+    fn shape_test() {
+        let s = Shape::Translate {
+            off: Vector::from_3(1.0, 0.0, 0.0),
+            s: Box::new(Shape::Translate {
+                off: Vector::from_3(0.0, 2.0, 0.0),
+                s: Box::new(Shape::Sphere { r: 0.5 }),
+            }),
+        };
+        let t = shape_toml(&s);
+
+        // nested moves add up into a single center
+        assert_eq!(t["type"].as_str(), Some("sphere"));
+        assert_eq!(t["center"], vec3(Vector::from_3(1.0, 2.0, 0.0)));
+    }
+
+    #[test]
+    fn scene_test() {
+        let mut cfg = Config::default();
+        cfg.render.shutter = (0.0, 24.0);
+        cfg.world.cam_pos = Vector::zero(3);
+        cfg.world.camera_target = Vector::from_3(0.0, 0.0, 1.0);
+        cfg.world.background = Vector::zero(3);
+        cfg.world.material_names = vec!["metal".to_string(), "unused".to_string()];
+
+        let objs: Vec<Box<dyn RayInteraction + Send + Sync>> = vec![
+            Box::new(Sphere {
+                c: Vector::from_3(0.0, 1.0, 4.0),
+                r: 1.0,
+                mat: material(0),
+                motion: Vector::from_3(2.0, 0.0, 0.0),
+            }),
+            Box::new(Plane {
+                p: Vector::zero(3),
+                n: Vector::from_3(0.0, 1.0, 0.0),
+                mat: material(0),
+                motion: Vector::zero(3),
+            }),
+        ];
+        let lights = vec![Light {
+            kind: LightType::Ambient,
+            color: Vector::from_s(0.1, 3),
+        }];
+
+        let text = to_toml(&cfg, &objs, &lights);
+        let v = read(&text);
+
+        // objects without names get made up ones, and only materials in use are written
+        let sphere = &v["objects"]["object_0"];
+        assert_eq!(sphere["type"].as_str(), Some("sphere"));
+        assert_eq!(sphere["material"].as_str(), Some("metal"));
+        assert!(v["materials"].get("unused").is_none());
+        assert_eq!(v["materials"]["metal"]["model"].as_str(), Some("ggx"));
+
+        // the shutter is open for a second at 24 fps, so the sphere moves 2 units per second
+        assert_eq!(sphere["velocity"], vec3(Vector::from_3(2.0, 0.0, 0.0)));
+        assert!(v["objects"]["object_1"].get("velocity").is_none());
+
+        // defaults are spelled out
+        assert_eq!(v["render"]["sampler"].as_str(), Some("random"));
+        assert_eq!(v["lights"]["light_0"]["type"].as_str(), Some("ambient"));
+
+        // scenes that use every core keep doing so wherever they're read back
+        assert_eq!(v["render"]["threads"].as_str(), Some("auto"));
+        cfg.render.threads = Some(3);
+        let v = read(&to_toml(&cfg, &objs, &lights));
+        assert_eq!(v["render"]["threads"].as_integer(), Some(3));
+
+        // animations are written as a single frame, with the shutter open for just as long at 24 fps
+        cfg.animation = Some(Animation {
+            start: 1,
+            end: 10,
+            fps: 48.0,
+        });
+        cfg.render.shutter = (0.0, 48.0);
+        let v = read(&to_toml(&cfg, &objs, &lights));
+        assert!(v.get("animation").is_none());
+        assert_eq!(
+            v["render"]["shutter"],
+            Value::Array(vec![num(0.0), num(24.0)])
+        );
+        assert_eq!(
+            v["objects"]["object_0"]["velocity"],
+            vec3(Vector::from_3(2.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn texture_test() {
+        // a texture embedded in a glTF file, so it has no file of its own
+        let colors = vec![Vector::from_3(1.0, 0.0, 0.0), Vector::from_3(0.0, 0.2, 1.0)];
+        let texture = Texture {
+            img: Matrix {
+                mat: colors.clone(),
+                rlen: 2,
+                clen: 1,
+            },
+            source: None,
+        };
+        let tri = Triangle {
+            p: [
+                Vector::zero(3),
+                Vector::from_3(1.0, 0.0, 0.0),
+                Vector::from_3(0.0, 1.0, 0.0),
+            ],
+            n: None,
+            uv: Some([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]),
+            color: None,
+        };
+        let objs: Vec<Box<dyn RayInteraction + Send + Sync>> = vec![Box::new(Mesh::new(
+            vec![tri],
+            material(0),
+            Some(Arc::new(texture)),
+            Vector::zero(3),
+        ))];
+
+        let dir = std::env::temp_dir().join(format!("oxide_texture_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut cfg = Config::default();
+        cfg.world.cam_pos = Vector::zero(3);
+        cfg.world.camera_target = Vector::from_3(0.0, 0.0, 1.0);
+        cfg.world.background = Vector::zero(3);
+        write(&dir.join("scene.toml"), &cfg, &objs, &[]).unwrap();
+
+        // the texture is written next to the scene, which names it, and reads back as the same colors
+        let v = read(&fs::read_to_string(dir.join("scene.toml")).unwrap());
+        assert_eq!(
+            v["objects"]["object_0"]["texture"].as_str(),
+            Some("scene_object_0.png")
+        );
+        let file = dir.join("scene_object_0.png");
+        assert_eq!(Texture::load(file.to_str().unwrap()).img.mat, colors);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+//! Defines the film that samples are collected on, and the reconstruction filters used to turn them into pixels.
+//!
+//! Every sample is splatted into all pixels within the filter radius of where it was taken, weighted by the filter,
+//! and each pixel ends up as the weighted average of the samples around it.
+
+use crate::opts::Filter;
+use crate::vec::*;
+
+use std::f32::consts::PI;
+
+/// Returns the normalized sinc function, sin(πx) / πx.
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Returns the weight `f` gives to a sample `x` pixels away along one axis, when its radius is `r` pixels.
+/// Filters are separable, so the weight in 2D is the product of the weights along each axis.
+pub fn weight(f: Filter, r: f32, x: f32) -> f32 {
+    let x = x.abs();
+    if x > r {
+        return 0.0;
+    }
+
+    match f {
+        Filter::Box => 1.0,
+        Filter::Tent => 1.0 - x / r,
+        Filter::Gaussian => {
+            // shift the curve down so it reaches 0 at the radius instead of being cut off
+            let sigma = r / 3.0;
+            let g = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+            g(x) - g(r)
+        }
+        Filter::Mitchell => {
+            // Mitchell-Netravali with B = C = 1/3, stretched from its natural radius of 2 to `r`
+            let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+            let x = 2.0 * x / r;
+
+            if x < 1.0 {
+                ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                    + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                    + (6.0 - 2.0 * b))
+                    / 6.0
+            } else {
+                ((-b - 6.0 * c) * x * x * x
+                    + (6.0 * b + 30.0 * c) * x * x
+                    + (-12.0 * b - 48.0 * c) * x
+                    + (8.0 * b + 24.0 * c))
+                    / 6.0
+            }
+        }
+        Filter::Lanczos => sinc(x) * sinc(x / r),
+    }
+}
+
+/// Collects filtered samples for a `w` by `h` image.
+pub struct Film {
+    /// Width of the image.
+    pub w: usize,
+    /// Height of the image.
+    pub h: usize,
+    /// Filter to weight samples with.
+    pub filter: Filter,
+    /// Radius of the filter, in pixels.
+    pub radius: f32,
+    /// Weighted sum of the samples near each pixel.
+    sum: Vec<Vector>,
+    /// Sum of the weights of the samples near each pixel.
+    wsum: Vec<f32>,
+}
+
+impl Film {
+    /// Returns an empty film for a `w` by `h` image, which weights samples with `filter` over `radius` pixels.
+    pub fn new(w: usize, h: usize, filter: Filter, radius: f32) -> Film {
+        Film {
+            w,
+            h,
+            filter,
+            radius,
+            sum: vec![Vector::zero(3); w * h],
+            wsum: vec![0.0; w * h],
+        }
+    }
+
+    /// Adds sample `c`, taken `off` pixels away from the center of pixel `p`, to every pixel within the filter radius.
+    /// Both `p` and `off` go right and down from the top left of the image.
+    pub fn add(&mut self, p: (usize, usize), off: (f32, f32), c: Vector) {
+        // position of the sample, measured from the center of the top left pixel
+        let sx = p.0 as f32 + off.0;
+        let sy = p.1 as f32 + off.1;
+
+        let x0 = (sx - self.radius).ceil().max(0.0) as usize;
+        let y0 = (sy - self.radius).ceil().max(0.0) as usize;
+        let x1 = ((sx + self.radius).floor().max(-1.0) as isize).min(self.w as isize - 1);
+        let y1 = ((sy + self.radius).floor().max(-1.0) as isize).min(self.h as isize - 1);
+
+        for y in y0 as isize..=y1 {
+            let wy = weight(self.filter, self.radius, y as f32 - sy);
+            if wy == 0.0 {
+                continue;
+            }
+
+            for x in x0 as isize..=x1 {
+                let wt = wy * weight(self.filter, self.radius, x as f32 - sx);
+                if wt == 0.0 {
+                    continue;
+                }
+
+                let i = y as usize * self.w + x as usize;
+                self.sum[i] = self.sum[i] + c * Vector::from_s(wt, 3);
+                self.wsum[i] += wt;
+            }
+        }
+    }
+
+    /// Returns the weighted average of the samples around each pixel, in rows from top to bottom.
+    /// Pixels no sample reached are black.
+    pub fn resolve(&self) -> Vec<Vector> {
+        self.sum
+            .iter()
+            .zip(self.wsum.iter())
+            .map(|(c, &wt)| {
+                if wt.abs() > 1e-6 {
+                    *c * Vector::from_s(1.0 / wt, 3)
+                } else {
+                    Vector::zero(3)
+                }
+            })
+            .collect()
+    }
+}
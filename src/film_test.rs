@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use crate::film::*;
+    use crate::opts::Filter;
+    use crate::vec::*;
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn weight_test() {
+        for f in [
+            Filter::Box,
+            Filter::Tent,
+            Filter::Gaussian,
+            Filter::Mitchell,
+            Filter::Lanczos,
+        ] {
+            let r = f.default_radius();
+            assert!(weight(f, r, 0.0) > 0.0);
+            assert!(approx(weight(f, r, 0.3), weight(f, r, -0.3)));
+            assert_eq!(weight(f, r, r + 0.1), 0.0);
+        }
+
+        assert!(approx(weight(Filter::Tent, 1.0, 0.25), 0.75));
+
+        // mitchell and lanczos dip below 0 away from the center
+        assert!(weight(Filter::Mitchell, 2.0, 1.5) < 0.0);
+        assert!(weight(Filter::Lanczos, 2.0, 1.5) < 0.0);
+    }
+
+    #[test]
+    fn box_test() {
+        // the box filter just averages the samples inside each pixel
+        let mut film = Film::new(2, 1, Filter::Box, 0.5);
+        film.add((0, 0), (0.2, -0.1), Vector::from_s(1.0, 3));
+        film.add((0, 0), (-0.4, 0.3), Vector::from_s(0.0, 3));
+        film.add((1, 0), (0.0, 0.0), Vector::from_s(0.5, 3));
+
+        let img = film.resolve();
+        assert!(approx(img[0].x(), 0.5));
+        assert!(approx(img[1].x(), 0.5));
+    }
+
+    #[test]
+    fn splat_test() {
+        // a sample spreads into neighboring pixels, and a flat color stays flat
+        let mut film = Film::new(3, 3, Filter::Gaussian, 1.5);
+        film.add((1, 1), (0.3, 0.0), Vector::from_s(0.8, 3));
+
+        let img = film.resolve();
+        for c in &img {
+            assert!(approx(c.x(), 0.8));
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Works out how much fog light passes through on its way along a ray.
+//!
+//! Fog density falls off exponentially with height, so the amount of fog along a ray can be integrated exactly
+//! instead of being marched through.
+
+use crate::opts::Fog;
+use crate::vec::*;
+
+/// Returns the fraction of light that makes it through `fog` along a ray from `o` going `dist` units in direction `d`,
+/// which has to be unit length. `dist` can be infinite for rays that never hit anything.
+pub fn transmittance(fog: &Fog, o: Vector, d: Vector, dist: f32) -> f32 {
+    if fog.density <= 0.0 {
+        return 1.0;
+    }
+
+    // density at the start of the ray, which changes by a factor of e^-k for every unit travelled
+    let d0 = fog.density * (-fog.falloff * (o.y() - fog.height)).exp();
+    let k = fog.falloff * d.y();
+    if d0 == 0.0 {
+        return 1.0;
+    }
+
+    // integral of d0 * e^(-k * s) for s from 0 to dist
+    let depth = if k.abs() < 1e-5 {
+        d0 * dist
+    } else {
+        d0 * (1.0 - (-k * dist).exp()) / k
+    };
+
+    (-depth).exp()
+}
+
+/// Returns color `c` as seen through `fog` from `dist` units away, looking from `o` in direction `d`.
+/// `d` has to be unit length, and `dist` can be infinite for rays that never hit anything.
+pub fn apply(fog: &Fog, c: Vector, o: Vector, d: Vector, dist: f32) -> Vector {
+    let t = transmittance(fog, o, d, dist);
+    if t >= 1.0 {
+        return c;
+    }
+
+    Vector::lerp(c, fog.color, t)
+}
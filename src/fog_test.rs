@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use crate::fog::*;
+    use crate::opts::Fog;
+    use crate::vec::*;
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn fog(density: f32, falloff: f32) -> Fog {
+        Fog {
+            density,
+            color: Vector::from_s(0.5, 3),
+            height: 0.0,
+            falloff,
+        }
+    }
+
+    #[test]
+    fn uniform_test() {
+        let o = Vector::zero(3);
+        let d = Vector::from_3(0.0, 0.0, 1.0);
+
+        assert_eq!(transmittance(&fog(0.0, 0.0), o, d, f32::INFINITY), 1.0);
+        assert!(approx(
+            transmittance(&fog(0.5, 0.0), o, d, 2.0),
+            (-1.0f32).exp()
+        ));
+        assert_eq!(transmittance(&fog(0.5, 0.0), o, d, f32::INFINITY), 0.0);
+
+        // fully fogged rays take on the fog color
+        let c = apply(&fog(0.5, 0.0), Vector::from_s(1.0, 3), o, d, f32::INFINITY);
+        assert!(approx(c.x(), 0.5));
+    }
+
+    #[test]
+    fn height_test() {
+        let f = fog(0.5, 1.0);
+        let o = Vector::zero(3);
+
+        // level rays see the same density the whole way
+        let level = transmittance(&f, o, Vector::from_3(0.0, 0.0, 1.0), 2.0);
+        assert!(approx(level, (-1.0f32).exp()));
+
+        // rays going up thin out, and only ever go through a limited amount of fog
+        let up = Vector::from_3(0.0, 1.0, 0.0);
+        assert!(transmittance(&f, o, up, 2.0) > level);
+        assert!(approx(
+            transmittance(&f, o, up, f32::INFINITY),
+            (-0.5f32).exp()
+        ));
+
+        // rays going down go through more and more fog
+        assert!(transmittance(&f, o, -up, 2.0) < level);
+        assert_eq!(transmittance(&f, o, -up, f32::INFINITY), 0.0);
+    }
+}
@@ -0,0 +1,616 @@
+//! Imports glTF 2.0 scenes, from .gltf files with external or embedded buffers and from binary .glb files.
+//! Meshes, node transforms, metallic-roughness materials with base color textures, cameras and punctual lights
+//! are turned into their oxide counterparts.
+
+use crate::bsdf::{Ggx, Model};
+use crate::mesh::{Mesh, Texture, Triangle};
+use crate::ray::{Light, LightType, Material};
+use crate::vec::Vector;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+/// A 4x4 transform, indexed by row and then column.
+type Mat4 = [[f32; 4]; 4];
+
+const IDENTITY: Mat4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// Where an imported scene is placed in the oxide scene.
+pub struct Placement {
+    /// Moves everything in the file.
+    pub offset: Vector,
+    /// Scales everything in the file about its origin, before it's moved.
+    pub scale: f32,
+    /// Multiplies the color of every light in the file.
+    pub light_scale: f32,
+    /// Id given to the first material of the file, with the others numbered after it.
+    pub first_material: usize,
+    /// How far every mesh moves while the shutter is open.
+    pub motion: Vector,
+}
+
+/// A camera found in a glTF file.
+pub struct Camera {
+    pub pos: Vector,
+    pub target: Vector,
+    /// Vertical field of view in radians.
+    pub yfov: f32,
+}
+
+/// Everything read from a glTF file.
+pub struct Scene {
+    /// Meshes, named after their nodes, with a primitive number added for nodes with several primitives.
+    pub meshes: Vec<(String, Mesh)>,
+    /// Names of the materials, in the order of their ids.
+    pub materials: Vec<String>,
+    pub lights: Vec<Light>,
+    /// The first camera in the scene, if there is one.
+    pub camera: Option<Camera>,
+}
+
+/// Reads the glTF file at `path`, which may be a .gltf or .glb file.
+pub fn load(path: &Path, place: &Placement) -> Scene {
+    let bytes =
+        std::fs::read(path).unwrap_or_else(|e| panic!("couldn't read {}: {e}", path.display()));
+    parse(&bytes, path.parent().unwrap_or(Path::new("")), place)
+}
+
+/// Reads a glTF file from `bytes`, finding any files it refers to relative to `dir`.
+/// Binary .glb files are told apart from JSON by their header.
+pub fn parse(bytes: &[u8], dir: &Path, place: &Placement) -> Scene {
+    let (json, bin) = if bytes.starts_with(b"glTF") {
+        split_glb(bytes)
+    } else {
+        (bytes, None)
+    };
+    let doc: Value = serde_json::from_slice(json).expect("couldn't parse glTF JSON");
+
+    let version = doc["asset"]["version"].as_str().unwrap_or("");
+    if !version.starts_with('2') {
+        panic!("unsupported glTF version \"{version}\", only 2.0 is supported");
+    }
+
+    let buffers: Vec<Vec<u8>> = list(&doc, "buffers")
+        .iter()
+        .enumerate()
+        .map(|(i, b)| match b["uri"].as_str() {
+            Some(uri) => read_uri(uri, dir),
+            None if i == 0 => bin.clone().expect("buffer without uri outside a .glb file"),
+            None => panic!("buffer {i} has no uri"),
+        })
+        .collect();
+
+    let mut imp = Importer {
+        doc: &doc,
+        buffers,
+        dir,
+        place,
+        textures: HashMap::new(),
+        scene: Scene {
+            meshes: vec![],
+            materials: vec![],
+            lights: vec![],
+            camera: None,
+        },
+        names: HashSet::new(),
+        default_material: None,
+    };
+
+    for (i, m) in list(&doc, "materials").iter().enumerate() {
+        let name = m["name"]
+            .as_str()
+            .map_or(format!("material_{i}"), str::to_string);
+        imp.scene.materials.push(name);
+    }
+
+    for root in roots(&doc) {
+        imp.node(root, IDENTITY);
+    }
+    imp.scene
+}
+
+/// Splits a binary .glb file into its JSON and binary chunks.
+fn split_glb(bytes: &[u8]) -> (&[u8], Option<Vec<u8>>) {
+    let word = |at: usize| -> u32 {
+        let b = bytes.get(at..at + 4).expect("truncated .glb file");
+        u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+    };
+    if word(4) != 2 {
+        panic!("unsupported .glb version {}, only 2 is supported", word(4));
+    }
+
+    let end = (word(8) as usize).min(bytes.len());
+    let (mut json, mut bin) = (None, None);
+    let mut at = 12;
+    while at + 8 <= end {
+        let (len, kind) = (word(at) as usize, word(at + 4));
+        let data = bytes
+            .get(at + 8..at + 8 + len)
+            .expect("truncated .glb chunk");
+        match kind {
+            0x4E4F534A => json = Some(data),
+            0x004E4942 => bin = Some(data.to_vec()),
+            _ => {} // unknown chunks are skipped
+        }
+        at += 8 + len;
+    }
+
+    (json.expect(".glb file has no JSON chunk"), bin)
+}
+
+/// Returns the array at `key` of `v`, or an empty one if it's not there.
+fn list<'a>(v: &'a Value, key: &str) -> &'a [Value] {
+    v[key].as_array().map_or(&[], |a| a.as_slice())
+}
+
+/// Returns the number at `key` of `v`, or `default` if it's not there.
+fn float(v: &Value, key: &str, default: f32) -> f32 {
+    v[key].as_f64().map_or(default, |f| f as f32)
+}
+
+/// Returns the first `N` numbers of the array at `key` of `v`, or `default` if it's not there.
+fn floats<const N: usize>(v: &Value, key: &str, default: [f32; N]) -> [f32; N] {
+    match v[key].as_array() {
+        Some(a) => std::array::from_fn(|i| a.get(i).and_then(Value::as_f64).unwrap_or(0.0) as f32),
+        None => default,
+    }
+}
+
+/// Returns the index at `key` of `v`, if there is one.
+fn index(v: &Value, key: &str) -> Option<usize> {
+    v[key].as_u64().map(|i| i as usize)
+}
+
+/// Returns the nodes at the top of the scene to show, or every node that isn't a child if no scene is given.
+fn roots(doc: &Value) -> Vec<usize> {
+    let scene = index(doc, "scene").unwrap_or(0);
+    if let Some(s) = list(doc, "scenes").get(scene) {
+        return list(s, "nodes")
+            .iter()
+            .filter_map(Value::as_u64)
+            .map(|i| i as usize)
+            .collect();
+    }
+
+    let children: HashSet<u64> = list(doc, "nodes")
+        .iter()
+        .flat_map(|n| list(n, "children").iter().filter_map(Value::as_u64))
+        .collect();
+    (0..list(doc, "nodes").len())
+        .filter(|i| !children.contains(&(*i as u64)))
+        .collect()
+}
+
+/// Reads the data a uri points to, either embedded in a base64 `data:` uri or in a file relative to `dir`.
+fn read_uri(uri: &str, dir: &Path) -> Vec<u8> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, payload) = data
+            .split_once(";base64,")
+            .expect("only base64 data uris are supported");
+        return base64(payload);
+    }
+
+    let path = dir.join(percent_decode(uri));
+    std::fs::read(&path).unwrap_or_else(|e| panic!("couldn't read {}: {e}", path.display()))
+}
+
+/// Decodes base64 text, ignoring padding and anything else outside the base64 alphabet.
+pub fn base64(text: &str) -> Vec<u8> {
+    let digit = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for d in text.bytes().filter_map(digit) {
+        acc = (acc << 6) | d as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    out
+}
+
+/// Turns escapes like `%20` in a uri back into the characters they stand for.
+fn percent_decode(uri: &str) -> String {
+    let b = uri.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        let hex = b
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(c) if b[i] == b'%' => {
+                out.push(c);
+                i += 3;
+            }
+            _ => {
+                out.push(b[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Multiplies transforms `a` and `b`, giving the transform that applies `b` first.
+fn mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    std::array::from_fn(|r| std::array::from_fn(|c| (0..4).map(|k| a[r][k] * b[k][c]).sum()))
+}
+
+/// Returns the local transform of a node, given as a column-major matrix or as translation, rotation and scale.
+fn local_transform(node: &Value) -> Mat4 {
+    if node["matrix"].is_array() {
+        let m: [f32; 16] = floats(node, "matrix", [0.0; 16]);
+        return std::array::from_fn(|r| std::array::from_fn(|c| m[c * 4 + r]));
+    }
+
+    let [tx, ty, tz] = floats(node, "translation", [0.0; 3]);
+    let [x, y, z, w] = floats(node, "rotation", [0.0, 0.0, 0.0, 1.0]);
+    let [sx, sy, sz] = floats(node, "scale", [1.0; 3]);
+
+    let rot = [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ],
+        [
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ],
+        [
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ];
+    let t = [tx, ty, tz];
+    let s = [sx, sy, sz];
+    std::array::from_fn(|r| {
+        if r == 3 {
+            [0.0, 0.0, 0.0, 1.0]
+        } else {
+            [rot[r][0] * s[0], rot[r][1] * s[1], rot[r][2] * s[2], t[r]]
+        }
+    })
+}
+
+/// Applies transform `m` to `v`, with `w` = 1 for points and 0 for directions.
+fn apply(m: &Mat4, v: [f32; 3], w: f32) -> [f32; 3] {
+    std::array::from_fn(|r| m[r][0] * v[0] + m[r][1] * v[1] + m[r][2] * v[2] + m[r][3] * w)
+}
+
+/// Returns the matrix that transforms normals along with `m`, and whether `m` turns shapes inside out.
+/// This is the cofactor matrix of the upper 3x3 part, which only differs from its inverse transpose by scale.
+fn normal_transform(m: &Mat4) -> (Mat4, bool) {
+    let c = |r: usize, k: usize| {
+        let (r0, r1) = ((r + 1) % 3, (r + 2) % 3);
+        let (k0, k1) = ((k + 1) % 3, (k + 2) % 3);
+        m[r0][k0] * m[r1][k1] - m[r0][k1] * m[r1][k0]
+    };
+    let det = m[0][0] * c(0, 0) + m[0][1] * c(0, 1) + m[0][2] * c(0, 2);
+    let sign = if det < 0.0 { -1.0 } else { 1.0 };
+
+    let n = std::array::from_fn(|r| {
+        std::array::from_fn(|k| if r < 3 && k < 3 { sign * c(r, k) } else { 0.0 })
+    });
+    (n, det < 0.0)
+}
+
+/// Converts a point or direction from glTF's right-handed space to oxide's left-handed space by flipping z.
+fn to_oxide(v: [f32; 3]) -> Vector {
+    Vector::from_3(v[0], v[1], -v[2])
+}
+
+/// Keeps track of everything needed while walking through the nodes of a glTF file.
+struct Importer<'a> {
+    doc: &'a Value,
+    buffers: Vec<Vec<u8>>,
+    dir: &'a Path,
+    place: &'a Placement,
+    /// Textures already decoded, by image index.
+    textures: HashMap<usize, Arc<Texture>>,
+    scene: Scene,
+    /// Mesh names already taken.
+    names: HashSet<String>,
+    /// Index of the material used by primitives that don't name one, once it's needed.
+    default_material: Option<usize>,
+}
+
+impl Importer<'_> {
+    /// Moves a point from the file into place in the oxide scene.
+    fn place_point(&self, v: [f32; 3]) -> Vector {
+        to_oxide(v) * Vector::from_s(self.place.scale, 3) + self.place.offset
+    }
+
+    /// Returns the bytes of buffer view `idx`, along with the distance between elements if it sets one.
+    fn view(&self, idx: usize) -> (&[u8], Option<usize>) {
+        let v = &list(self.doc, "bufferViews")[idx];
+        let buf = &self.buffers[index(v, "buffer").expect("buffer view has no buffer")];
+        let start = index(v, "byteOffset").unwrap_or(0);
+        let len = index(v, "byteLength").expect("buffer view has no length");
+        let data = buf
+            .get(start..start + len)
+            .unwrap_or_else(|| panic!("buffer view {idx} reaches past the end of its buffer"));
+        (data, index(v, "byteStride"))
+    }
+
+    /// Reads accessor `idx`, returning its values in order and how many there are per element.
+    fn accessor(&self, idx: usize) -> (Vec<f64>, usize) {
+        let a = &list(self.doc, "accessors")[idx];
+        let count = index(a, "count").expect("accessor has no count");
+        let comps = match a["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            t => panic!("unknown accessor type {t:?}"),
+        };
+        if a.get("sparse").is_some() {
+            panic!("sparse accessors aren't supported");
+        }
+
+        let kind = index(a, "componentType").expect("accessor has no component type");
+        let size = match kind {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => panic!("unknown accessor component type {kind}"),
+        };
+
+        // accessors without a buffer view are all zeros
+        let Some(view) = index(a, "bufferView") else {
+            return (vec![0.0; count * comps], comps);
+        };
+        let (data, stride) = self.view(view);
+        let stride = stride.unwrap_or(size * comps);
+        let start = index(a, "byteOffset").unwrap_or(0);
+        let normalized = a["normalized"].as_bool().unwrap_or(false);
+
+        let mut out = Vec::with_capacity(count * comps);
+        for i in 0..count {
+            for c in 0..comps {
+                let at = start + i * stride + c * size;
+                let b = data.get(at..at + size).unwrap_or_else(|| {
+                    panic!("accessor {idx} reaches past the end of its buffer view")
+                });
+                let (v, max) = match kind {
+                    5120 => (b[0] as i8 as f64, 127.0),
+                    5121 => (b[0] as f64, 255.0),
+                    5122 => (i16::from_le_bytes([b[0], b[1]]) as f64, 32767.0),
+                    5123 => (u16::from_le_bytes([b[0], b[1]]) as f64, 65535.0),
+                    5125 => (u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64, 1.0),
+                    _ => (f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64, 1.0),
+                };
+                out.push(if normalized { (v / max).max(-1.0) } else { v });
+            }
+        }
+        (out, comps)
+    }
+
+    /// Returns the texture of image `idx`, decoding it the first time it's used.
+    fn texture(&mut self, idx: usize) -> Arc<Texture> {
+        if let Some(tex) = self.textures.get(&idx) {
+            return tex.clone();
+        }
+
+        let img = &list(self.doc, "images")[idx];
+        let (bytes, source) = match (img["uri"].as_str(), index(img, "bufferView")) {
+            (Some(uri), _) if uri.starts_with("data:") => (read_uri(uri, self.dir), None),
+            (Some(uri), _) => {
+                let path = self.dir.join(percent_decode(uri));
+                (
+                    read_uri(uri, self.dir),
+                    Some(path.to_string_lossy().into_owned()),
+                )
+            }
+            (None, Some(view)) => (self.view(view).0.to_vec(), None),
+            (None, None) => panic!("image {idx} has no data"),
+        };
+
+        let tex = Arc::new(Texture::decode(&bytes, source));
+        self.textures.insert(idx, tex.clone());
+        tex
+    }
+
+    /// Converts material `idx`, or the default glTF material if there's none, along with its base color texture
+    /// and the set of texture coordinates that texture uses.
+    fn material(&mut self, idx: Option<usize>) -> (Material, Option<Arc<Texture>>, usize) {
+        let id = match idx {
+            Some(i) => i,
+            None => *self.default_material.get_or_insert_with(|| {
+                self.scene.materials.push("default".to_string());
+                self.scene.materials.len() - 1
+            }),
+        };
+        let m = idx.map_or(&Value::Null, |i| &list(self.doc, "materials")[i]);
+        let pbr = &m["pbrMetallicRoughness"];
+
+        let [r, g, b, _] = floats(pbr, "baseColorFactor", [1.0; 4]);
+        let metallic = float(pbr, "metallicFactor", 1.0).clamp(0.0, 1.0);
+        let roughness = float(pbr, "roughnessFactor", 1.0).clamp(0.0, 1.0);
+        let strength = float(
+            &m["extensions"]["KHR_materials_emissive_strength"],
+            "emissiveStrength",
+            1.0,
+        );
+        let [er, eg, eb] = floats(m, "emissiveFactor", [0.0; 3]);
+
+        let tex_info = &pbr["baseColorTexture"];
+        let texture = index(tex_info, "index")
+            .and_then(|t| index(&list(self.doc, "textures")[t], "source"))
+            .map(|img| self.texture(img));
+
+        let mat = Material {
+            color: Vector::from_3(r, g, b),
+            bsdf: Model::Ggx(Ggx {
+                roughness,
+                metallic,
+            }),
+            // only smooth metals mirror their surroundings, rougher ones just get broad highlights
+            refl: metallic * (1.0 - roughness) * (1.0 - roughness),
+            roughness,
+            emission: Vector::from_3(er, eg, eb) * Vector::from_s(strength, 3),
+            transmission: Vector::zero(3),
+            id: self.place.first_material + id,
+        };
+        (mat, texture, index(tex_info, "texCoord").unwrap_or(0))
+    }
+
+    /// Returns `name`, or `name` with a number added if it's already taken.
+    fn unique(&mut self, name: String) -> String {
+        let mut out = name.clone();
+        let mut n = 1;
+        while !self.names.insert(out.clone()) {
+            n += 1;
+            out = format!("{name}_{n}");
+        }
+        out
+    }
+
+    /// Imports node `idx` and its children, where `parent` places the node's parent in the file.
+    fn node(&mut self, idx: usize, parent: Mat4) {
+        let node = &list(self.doc, "nodes")[idx];
+        let m = mul(&parent, &local_transform(node));
+
+        if let Some(mesh) = index(node, "mesh") {
+            let mesh = &list(self.doc, "meshes")[mesh];
+            let name = node["name"]
+                .as_str()
+                .or(mesh["name"].as_str())
+                .map_or(format!("node_{idx}"), str::to_string);
+            let prims = list(mesh, "primitives");
+
+            for (i, prim) in prims.iter().enumerate() {
+                let name = if prims.len() > 1 {
+                    format!("{name}.{i}")
+                } else {
+                    name.clone()
+                };
+                if let Some(mesh) = self.primitive(prim, &m) {
+                    let name = self.unique(name);
+                    self.scene.meshes.push((name, mesh));
+                }
+            }
+        }
+
+        if let Some(cam) = index(node, "camera") {
+            let cam = &list(self.doc, "cameras")[cam];
+            if self.scene.camera.is_none() && cam["type"] == "perspective" {
+                let pos = self.place_point(apply(&m, [0.0; 3], 1.0));
+                let fwd = to_oxide(apply(&m, [0.0, 0.0, -1.0], 0.0)).norm();
+                self.scene.camera = Some(Camera {
+                    pos,
+                    target: pos + fwd,
+                    yfov: float(&cam["perspective"], "yfov", 0.8),
+                });
+            }
+        }
+
+        if let Some(light) = index(&node["extensions"]["KHR_lights_punctual"], "light") {
+            let light = &list(&self.doc["extensions"]["KHR_lights_punctual"], "lights")[light];
+            let [r, g, b] = floats(light, "color", [1.0; 3]);
+            let color = Vector::from_3(r, g, b)
+                * Vector::from_s(float(light, "intensity", 1.0) * self.place.light_scale, 3);
+
+            // spot lights are treated as point lights, as oxide has no cones to narrow them with
+            let kind = match light["type"].as_str() {
+                Some("directional") => {
+                    LightType::Directional(to_oxide(apply(&m, [0.0, 0.0, 1.0], 0.0)).norm())
+                }
+                _ => LightType::Point(self.place_point(apply(&m, [0.0; 3], 1.0))),
+            };
+            self.scene.lights.push(Light { color, kind });
+        }
+
+        for child in list(node, "children").iter().filter_map(Value::as_u64) {
+            self.node(child as usize, m);
+        }
+    }
+
+    /// Converts primitive `prim` of a mesh placed by `m` into an oxide mesh.
+    /// Primitives made of points or lines have no surface and are skipped.
+    fn primitive(&mut self, prim: &Value, m: &Mat4) -> Option<Mesh> {
+        let attrs = &prim["attributes"];
+        let (pos, _) = self.accessor(index(attrs, "POSITION").expect("primitive has no positions"));
+        let count = pos.len() / 3;
+
+        let (mat, texture, set) = self.material(index(prim, "material"));
+        let normals = index(attrs, "NORMAL").map(|a| self.accessor(a).0);
+        let uvs = index(attrs, &format!("TEXCOORD_{set}")).map(|a| self.accessor(a).0);
+
+        let indices: Vec<usize> = match index(prim, "indices") {
+            Some(a) => self.accessor(a).0.iter().map(|&i| i as usize).collect(),
+            None => (0..count).collect(),
+        };
+        let corners: Vec<[usize; 3]> = match index(prim, "mode").unwrap_or(4) {
+            4 => indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            // strips flip every other triangle to keep them all facing the same way
+            5 => (2..indices.len())
+                .map(|i| match i % 2 {
+                    0 => [indices[i - 2], indices[i - 1], indices[i]],
+                    _ => [indices[i - 1], indices[i - 2], indices[i]],
+                })
+                .collect(),
+            6 => (2..indices.len())
+                .map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            _ => return None,
+        };
+
+        let (nm, mirrored) = normal_transform(m);
+        let vec3 = |data: &[f64], i: usize| {
+            [
+                data[i * 3] as f32,
+                data[i * 3 + 1] as f32,
+                data[i * 3 + 2] as f32,
+            ]
+        };
+
+        let tris: Vec<Triangle> = corners
+            .into_iter()
+            .filter(|t| t.iter().all(|&i| i < count))
+            .map(|t| {
+                // flipping z turns the triangle inside out, so its corners are swapped back into the right order,
+                // unless the node transform already turned it inside out
+                let t = if mirrored { t } else { [t[0], t[2], t[1]] };
+                Triangle {
+                    p: t.map(|i| self.place_point(apply(m, vec3(&pos, i), 1.0))),
+                    n: normals
+                        .as_ref()
+                        .map(|n| t.map(|i| to_oxide(apply(&nm, vec3(n, i), 0.0)).norm())),
+                    uv: uvs
+                        .as_ref()
+                        .map(|uv| t.map(|i| (uv[i * 2] as f32, uv[i * 2 + 1] as f32))),
+                    color: None,
+                }
+            })
+            .collect();
+
+        if tris.is_empty() {
+            return None;
+        }
+        Some(Mesh::new(tris, mat, texture, self.place.motion))
+    }
+}
@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use crate::gltf::*;
+    use crate::ray::*;
+    use crate::vec::*;
+
+    use std::path::Path;
+
+    fn placement() -> Placement {
+        Placement {
+            offset: Vector::from_3(0.0, 0.0, 10.0),
+            scale: 1.0,
+            light_scale: 1.0,
+            first_material: 3,
+            motion: Vector::zero(3),
+        }
+    }
+
+    /// One triangle in the xy plane followed by its indices, as little-endian bytes.
+    fn buffer() -> Vec<u8> {
+        let pos: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let idx: [u16; 3] = [0, 1, 2];
+        let mut b: Vec<u8> = pos.iter().flat_map(|f| f.to_le_bytes()).collect();
+        b.extend(idx.iter().flat_map(|i| i.to_le_bytes()));
+        b
+    }
+
+    fn document(buffer_uri: Option<String>) -> String {
+        let uri = buffer_uri.map_or(String::new(), |u| format!(", \"uri\": \"{u}\""));
+        format!(
+            r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0, 2] }}],
+            "nodes": [
+                {{ "name": "tri", "mesh": 0, "translation": [1, 2, 3], "children": [1] }},
+                {{ "name": "lamp", "translation": [0, 1, 0],
+                   "extensions": {{ "KHR_lights_punctual": {{ "light": 0 }} }} }},
+                {{ "camera": 0, "translation": [0, 0, 5] }}
+            ],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0 }}] }}],
+            "materials": [{{ "name": "gold", "pbrMetallicRoughness": {{
+                "baseColorFactor": [1, 0.5, 0, 1], "metallicFactor": 1, "roughnessFactor": 0.5 }} }}],
+            "cameras": [{{ "type": "perspective", "perspective": {{ "yfov": 0.5, "znear": 0.1 }} }}],
+            "extensions": {{ "KHR_lights_punctual": {{ "lights": [
+                {{ "type": "point", "color": [1, 0.5, 0], "intensity": 2 }}
+            ] }} }},
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                {{ "bufferView": 0, "byteOffset": 36, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ],
+            "bufferViews": [{{ "buffer": 0, "byteLength": 42 }}],
+            "buffers": [{{ "byteLength": 42{uri} }}]
+        }}"#
+        )
+    }
+
+    fn encode(bytes: &[u8]) -> String {
+        let abc = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::new();
+        for c in bytes.chunks(3) {
+            let n = (c[0] as u32) << 16
+                | (*c.get(1).unwrap_or(&0) as u32) << 8
+                | *c.get(2).unwrap_or(&0) as u32;
+            for i in 0..=c.len() {
+                out.push(abc[(n >> (18 - 6 * i) & 63) as usize] as char);
+            }
+        }
+        out
+    }
+
+    fn check(scene: &Scene) {
+        // z is flipped going from glTF to oxide, and the placement offset is added on
+        assert_eq!(scene.meshes.len(), 1);
+        let (name, mesh) = &scene.meshes[0];
+        assert_eq!(name, "tri");
+        let tri = mesh.triangles()[0];
+        assert!(tri.p.contains(&Vector::from_3(1.0, 2.0, 7.0)));
+        assert!(tri.p.contains(&Vector::from_3(2.0, 2.0, 7.0)));
+        assert!(tri.p.contains(&Vector::from_3(1.0, 3.0, 7.0)));
+        // the triangle faced +z in glTF, so it faces -z here
+        assert_eq!(tri.face_normal(), Vector::from_3(0.0, 0.0, -1.0));
+
+        assert_eq!(scene.materials, vec!["gold".to_string()]);
+        assert_eq!(mesh.mat.color, Vector::from_3(1.0, 0.5, 0.0));
+        assert_eq!(mesh.mat.id, 3);
+        assert_eq!(mesh.mat.roughness, 0.5);
+        assert_eq!(mesh.mat.refl, 0.25);
+
+        // the light is a child of the mesh node, so it's moved along with it
+        assert_eq!(scene.lights.len(), 1);
+        assert_eq!(scene.lights[0].color, Vector::from_3(2.0, 1.0, 0.0));
+        match scene.lights[0].kind {
+            LightType::Point(p) => assert_eq!(p, Vector::from_3(1.0, 3.0, 7.0)),
+            _ => panic!("expected a point light"),
+        }
+
+        // glTF cameras look down -z, which is +z here
+        let cam = scene.camera.as_ref().unwrap();
+        assert_eq!(cam.pos, Vector::from_3(0.0, 0.0, 5.0));
+        assert_eq!(cam.target, Vector::from_3(0.0, 0.0, 6.0));
+        assert_eq!(cam.yfov, 0.5);
+    }
+
+    #[test]
+    fn base64_test() {
+        assert_eq!(base64("aGVsbG8="), b"hello");
+        assert_eq!(base64("aGVsbG8gd29ybGQ"), b"hello world");
+        assert_eq!(base64(&encode(&buffer())), buffer());
+    }
+
+    #[test]
+    fn gltf_test() {
+        let uri = format!("data:application/octet-stream;base64,{}", encode(&buffer()));
+        let scene = parse(document(Some(uri)).as_bytes(), Path::new(""), &placement());
+        check(&scene);
+    }
+
+    #[test]
+    fn glb_test() {
+        // chunks are padded to four bytes, the JSON one with spaces
+        let mut json = document(None).into_bytes();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+        let mut bin = buffer();
+        bin.resize(bin.len().div_ceil(4) * 4, 0);
+
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = b"glTF".to_vec();
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((total as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(0x4E4F534Au32.to_le_bytes());
+        glb.extend(&json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(0x004E4942u32.to_le_bytes());
+        glb.extend(&bin);
+
+        let scene = parse(&glb, Path::new(""), &placement());
+        check(&scene);
+    }
+}
@@ -1,11 +1,73 @@
 pub mod vec;
 mod vec_test;
 
+pub mod rng;
+mod rng_test;
+
 pub mod mat;
 mod mat_test;
 
+pub mod sdf;
+mod sdf_test;
+
+pub mod env;
+mod env_test;
+
+pub mod denoise;
+mod denoise_test;
+
+pub mod progress;
+mod progress_test;
+
+pub mod stats;
+mod stats_test;
+
+pub mod film;
+mod film_test;
+
+pub mod adaptive;
+mod adaptive_test;
+
+pub mod sampler;
+mod sampler_test;
+
+pub mod fog;
+mod fog_test;
+
+pub mod medium;
+mod medium_test;
+
+pub mod bsdf;
+mod bsdf_test;
+
+pub mod export;
+mod export_test;
+
+pub mod mesh;
+mod mesh_test;
+
+pub mod gltf;
+mod gltf_test;
+
+pub mod ply;
+mod ply_test;
+
+pub mod stl;
+mod stl_test;
+
+pub mod cli;
+mod cli_test;
+
+pub mod scene;
+mod scene_test;
+
+pub mod config;
+mod config_test;
+
+pub mod render;
+mod render_test;
+
 pub mod draw;
 pub mod opts;
 pub mod output;
 pub mod ray;
-pub mod render;
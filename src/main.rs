@@ -1,168 +1,549 @@
-mod config;
-
-use std::{env, time};
+use std::io::Write;
+use std::{env, fs, io, process, time};
 
 // this call ensures that we're using the library version of the functions rather than including them in the binary and library
 // if this is failing, make sure to run "cargo clean" if you built everything as a binary
+use oxide::film::Film;
 use oxide::output::*;
-use oxide::{draw, mat, opts, render};
+use oxide::progress::Progress;
+use oxide::ray::{Light, RayInteraction};
+use oxide::stats::Stats;
+use oxide::vec::Vector;
+use oxide::{adaptive, cli, config, denoise, draw, export, mat, opts, render};
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
 
+type Objects = Vec<Box<dyn RayInteraction + Send + Sync>>;
+
+/// Holds one sample for every pixel of the whole image, each in rows from top to bottom.
+struct Samples {
+    /// Color of each sample. Colors of pixels that weren't sampled are meaningless.
+    color: Vec<Vector>,
+    /// Where in each pixel the sample was taken, in canvas coordinates relative to the pixel center.
+    offsets: Vec<(f32, f32)>,
+    /// Extra passes, only recorded for the first sample.
+    passes: Vec<Vec<Vector>>,
+    /// Counters for the work done.
+    stats: Stats,
+}
+
 fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("usage: oxide <config file>");
-        return Ok(());
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = match cli::parse(&args) {
+        Ok(a) if a.help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    let path = &args.input;
+
+    let (cfg, objs, lights) = match config::read_cfg(path, None, &args.overrides) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    // write the scene back out as it was read instead of rendering it, which also converts it to TOML
+    if let Some(out) = &args.export {
+        return export::write(out.as_ref(), &cfg, &objs, &lights);
     }
 
-    let path = &args[1];
+    let path_pre = args.prefix();
 
-    let (cfg, objs, lights) = config::read_cfg(path).expect("could not import config file");
+    let anim = match cfg.animation {
+        Some(a) => a,
+        None => {
+            render_frame(cfg, objs, lights, &path_pre);
+            return Ok(());
+        }
+    };
 
-    let w = cfg.output.width;
-    let h = cfg.output.height;
+    // animated scenes are read again for every frame, and written as a numbered image sequence
+    let mut first = Some((cfg, objs, lights));
+    for frame in anim.start..=anim.end {
+        eprintln!("\nframe {} of {}..{}", frame, anim.start, anim.end);
 
-    let out_parts: Vec<&str> = path.split('.').collect();
+        let (cfg, objs, lights) = match first.take() {
+            Some(scene) => scene,
+            // the overrides already went in for the first frame, so they fit every other one too
+            None => config::read_cfg(path, Some(frame), &args.overrides)
+                .expect("could not import config file"),
+        };
 
-    // split the render into vertical slices and divide amongst threads
-    // (horizontal slices are harder to collapse together)
+        render_frame(cfg, objs, lights, &format!("{}_{:04}", path_pre, frame));
+    }
 
-    let mut m_parts = vec![];
-    let mut handles = vec![];
+    Ok(())
+}
+
+/// Renders the scene described by `cfg`, `objs` and `lights`, writing the image and any extra outputs to files starting with `path_pre`.
+fn render_frame(cfg: opts::Config, objs: Objects, lights: Vec<Light>, path_pre: &str) {
+    let w = cfg.output.width;
+    let h = cfg.output.height;
 
     // wrap shared objects in Arc so the last thread to use em also deletes em
     let cfg = Arc::new(cfg);
     let lights = Arc::new(lights);
     let objs = Arc::new(objs);
 
-    // if the rendered image is small (or the machine is massive), clamp max # threads
-    let min_threads = std::cmp::min(cfg.render.threads, h);
-
-    // show correct number of threads used in total
-    let disp_threads = if h % min_threads == 0 {
-        min_threads
-    } else {
-        min_threads + 1
-    };
-
-    let dt = (h / min_threads as usize) as i32;
-    let start = -(h as i32) / 2 + dt / 2;
-
-    let t_x = w;
-    let t_y = dt;
+    let parts = slices(h, cfg.render.thread_count());
 
     // print to stderr so output isn't buffered until the end
     eprintln!(
-        "\nrender parameters: {} x {}, {} thread(s) ({} x {} pixels per thread)",
-        w, h, disp_threads, t_x, t_y
+        "\nrender parameters: {} x {}, {} thread(s) ({} x {} pixels per thread), up to {} sample(s) per pixel",
+        w,
+        h,
+        parts.len(),
+        w,
+        parts[0].1,
+        cfg.render.samples
     );
     eprintln!("rendering... ");
 
+    let total: u64 = parts
+        .iter()
+        .map(|&(_, dt)| render::traced_pixels((w, dt)))
+        .sum();
+    let progress = Arc::new(Progress::new(total * cfg.render.samples as u64));
+    let stop = Arc::new(AtomicBool::new(false));
+
     let clock = time::Instant::now();
+    let reporter = spawn_reporter(&cfg, &progress, &stop, clock);
+    let mut last_preview = time::Instant::now();
+
+    // running totals of every sample taken, plus the squared brightness of each sample to estimate noise
+    let mut sum = vec![Vector::zero(3); w * h];
+    let mut sum_sq = vec![0.0f32; w * h];
+    let mut counts = vec![0u32; w * h];
+    let mut film = Film::new(w, h, cfg.render.filter, cfg.render.filter_radius);
+    let mut pass_vecs = vec![];
+    let mut taken = 0;
+    let mut stats = Stats::default();
+
+    // pixels that still need more samples, which is all of them unless adaptive sampling is on
+    let adaptive = cfg.render.adaptive_threshold > 0.0;
+    let mut active = vec![true; w * h];
+
+    while taken < cfg.render.samples {
+        let mask = if adaptive { Some(&active[..]) } else { None };
+        let samples = render_pass(&cfg, &objs, &lights, &parts, taken, mask, &progress);
+        progress.add_pass();
+        stats.merge(&samples.stats);
+
+        if taken == 0 {
+            pass_vecs = samples.passes;
+        }
+
+        for (i, c) in samples.color.iter().enumerate() {
+            if !active[i] {
+                continue;
+            }
+
+            // canvas coordinates go up, but image rows go down
+            let off = samples.offsets[i];
+            film.add((i % w, i / w), (off.0, -off.1), *c);
+
+            sum[i] = sum[i] + *c;
+            let l = c.dot(Vector::from_s(1.0 / 3.0, 3));
+            sum_sq[i] += l * l;
+            counts[i] += 1;
+        }
+
+        taken += 1;
+
+        // stop sampling pixels whose error is already low enough
+        if adaptive && taken >= cfg.render.adaptive_min_samples {
+            adaptive::settle(
+                &mut active,
+                &sum,
+                &sum_sq,
+                &counts,
+                cfg.render.adaptive_threshold,
+            );
+        }
+
+        // the noise estimate is unreliable with only a few samples, so always take at least 4 before trusting it
+        let done = taken == cfg.render.samples
+            || !active.contains(&true)
+            || (cfg.render.time_limit > 0.0
+                && clock.elapsed().as_secs_f32() >= cfg.render.time_limit)
+            || (cfg.render.noise_threshold > 0.0
+                && taken >= 4
+                && adaptive::noise(&sum, &sum_sq, &counts) < cfg.render.noise_threshold);
+
+        if done {
+            break;
+        }
+
+        let preview = (cfg.output.preview_passes > 0
+            && taken.is_multiple_of(cfg.output.preview_passes))
+            || (cfg.output.preview_seconds > 0.0
+                && last_preview.elapsed().as_secs_f32() >= cfg.output.preview_seconds);
+
+        if preview {
+            write_beauty(&cfg, path_pre, &film.resolve(), &pass_vecs);
+            last_preview = time::Instant::now();
+        }
+    }
+
+    let time = clock.elapsed();
+
+    stop.store(true, Ordering::Relaxed);
+    reporter.join().expect("progress thread panicked");
+
+    eprintln!(
+        "done ({} sample(s), {}.{:03} sec)\n",
+        taken,
+        time.as_secs(),
+        time.subsec_millis()
+    );
+
+    let out_clock = time::Instant::now();
+    let denoise_time = write_beauty(&cfg, path_pre, &film.resolve(), &pass_vecs);
+    write_passes(&cfg, path_pre, &pass_vecs);
+    if cfg.output.sample_heatmap {
+        write_img(
+            &cfg,
+            path_pre.to_string() + "_samples",
+            &to_bytes(&heatmap(&counts)),
+        );
+    }
+
+    stats.stages.push(("render", time));
+    if cfg.post.denoise {
+        stats.stages.push(("denoise", denoise_time));
+    }
+    stats
+        .stages
+        .push(("write", out_clock.elapsed() - denoise_time));
+
+    eprint!("{}", stats.table());
+    if cfg.output.stats {
+        fs::write(path_pre.to_string() + "_stats.json", stats.json())
+            .expect("could not write stats");
+    }
+}
+
+/// Splits an image `h` pixels high into horizontal slices for up to `threads` threads to render.
+/// Returns the midpoint (in canvas coordinates) and height of each slice, from the top of the image down.
+fn slices(h: usize, threads: usize) -> Vec<(i32, usize)> {
+    // if the rendered image is small (or the machine is massive), clamp max # threads
+    let min_threads = std::cmp::min(threads, h);
+    let dt = h / min_threads;
+
+    // the top row of a slice `dt` rows high sits half its height (rounded up) above its midpoint, so line that up
+    // with the row `curr_h` rows below the top of the image, which sits half the image height above the center
+    let mid = |curr_h: usize, dt: usize| (curr_h + dt.div_ceil(2)) as i32 - h.div_ceil(2) as i32;
+
+    let mut parts = vec![];
+    let mut curr_h = 0;
+
+    for _ in 0..min_threads {
+        parts.push((mid(curr_h, dt), dt));
+        curr_h += dt;
+    }
+
+    if !h.is_multiple_of(min_threads) {
+        // need an extra slice here to handle remaining work since we can't split work into an even number of rows per thread
+        let final_dt = h - curr_h;
+        parts.push((mid(curr_h, final_dt), final_dt));
+        curr_h += final_dt;
+    }
+
+    assert_eq!(curr_h, h);
+
+    parts
+}
+
+/// Starts a thread that reports on `progress` a few times a second until `stop` is set, then reports one last time.
+/// Reports go to stderr as a single line that's rewritten in place, and to the configured progress file as JSON lines.
+fn spawn_reporter(
+    cfg: &opts::Config,
+    progress: &Arc<Progress>,
+    stop: &Arc<AtomicBool>,
+    clock: time::Instant,
+) -> thread::JoinHandle<()> {
+    let progress = Arc::clone(progress);
+    let stop = Arc::clone(stop);
+
+    let limit = match cfg.render.time_limit {
+        t if t > 0.0 => Some(time::Duration::from_secs_f32(t)),
+        _ => None,
+    };
+
+    let mut json: Option<Box<dyn Write + Send>> = match cfg.output.progress_file.as_deref() {
+        Some("-") => Some(Box::new(io::stdout())),
+        Some(f) => Some(Box::new(
+            fs::File::create(f).expect("could not create progress file"),
+        )),
+        None => None,
+    };
 
-    let mut curr_h: usize = 0;
+    let report_fn = move || loop {
+        // check before reporting, so the last report always comes after the render is done
+        let last = stop.load(Ordering::Relaxed);
+        let r = progress.report(clock.elapsed(), limit);
+
+        eprint!("\r{}", r.line());
+
+        if let Some(f) = json.as_mut() {
+            writeln!(f, "{}", r.json()).expect("could not write progress");
+            f.flush().expect("could not write progress");
+        }
+
+        if last {
+            eprintln!();
+            return;
+        }
+
+        // sleep in short bursts so the thread notices quickly when the render is done
+        let wake = time::Instant::now() + time::Duration::from_millis(500);
+        while time::Instant::now() < wake && !stop.load(Ordering::Relaxed) {
+            thread::sleep(time::Duration::from_millis(10));
+        }
+    };
 
-    for i in 0..min_threads {
+    thread::Builder::new()
+        .name("progress".to_string())
+        .spawn(report_fn)
+        .expect("could not spawn thread")
+}
+
+/// Takes sample number `sample` for every pixel, splitting the work across one thread per slice in `parts`.
+/// If `active` is set, only the pixels set in it are sampled.
+/// Returns the samples for the whole image, stitched together from every slice.
+fn render_pass(
+    cfg: &Arc<opts::Config>,
+    objs: &Arc<Objects>,
+    lights: &Arc<Vec<Light>>,
+    parts: &[(i32, usize)],
+    sample: u32,
+    active: Option<&[bool]>,
+    progress: &Arc<Progress>,
+) -> Samples {
+    let w = cfg.output.width;
+    let mut handles = vec![];
+    let mut row = 0;
+
+    for (i, &(mid, dt)) in parts.iter().enumerate() {
         // increase ref count of shared objects
-        let objs_c = Arc::clone(&objs);
-        let lights_c = Arc::clone(&lights);
-        let cfg_c = Arc::clone(&cfg);
-
-        let work_fn = move || -> mat::Matrix<draw::Color> {
-            render::render(
-                (0, start + dt * i as i32), // midpoints of subsection
-                (t_x, t_y as usize),        // width and height of subsection
-                &objs_c,
-                &lights_c,
-                &cfg_c,
-            )
+        let objs_c = Arc::clone(objs);
+        let lights_c = Arc::clone(lights);
+        let cfg_c = Arc::clone(cfg);
+        let progress_c = Arc::clone(progress);
+
+        // each thread gets the part of the mask covering its slice
+        let mask = active.map(|a| untrim(&a[row * w..(row + dt) * w], w, dt, false));
+        row += dt;
+
+        let work_fn = move || -> render::Frame {
+            let sec = render::Section {
+                start: (0, mid), // midpoints of subsection
+                dims: (w, dt),   // width and height of subsection
+                sample,
+                mask: mask.as_ref(),
+            };
+
+            render::render(&sec, &objs_c, &lights_c, &cfg_c, &progress_c)
         };
 
-        // launch thread  with useful name and store handle for later
+        // launch thread with useful name and store handle for later
         handles.push(
             thread::Builder::new()
                 .name(i.to_string())
                 .spawn(work_fn)
                 .expect("could not spawn thread"),
         );
-
-        curr_h += dt as usize;
     }
 
-    if h % min_threads != 0 {
-        // need an extra thread here to handle remaining work since we can't split work into an even number of rows per thread
+    let mut cvec = vec![];
+    let mut offs = vec![];
+    let mut pass_vecs = vec![];
+    let mut stats = Stats::default();
 
-        let objs_c = Arc::clone(&objs);
-        let lights_c = Arc::clone(&lights);
-        let cfg_c = Arc::clone(&cfg);
-
-        let final_dt = h - curr_h;
+    for h in handles {
+        let m = h.join().expect("child thread panicked");
+        stats.merge(&m.stats);
 
-        // launch thread and store handle for later
-        handles.push(thread::spawn(move || -> mat::Matrix<draw::Color> {
-            render::render(
-                (0, (curr_h + final_dt / 2) as i32), // midpoints of subsection
-                (w, final_dt),                       // width and height of subsection
-                &objs_c,
-                &lights_c,
-                &cfg_c,
-            )
-        }));
+        cvec.append(&mut trim(&m.color));
+        offs.append(&mut trim(&m.offsets));
 
-        curr_h += final_dt as usize;
+        pass_vecs.resize(m.passes.len(), vec![]);
+        for (pv, pm) in pass_vecs.iter_mut().zip(m.passes.iter()) {
+            pv.append(&mut trim(pm));
+        }
     }
 
-    assert_eq!(curr_h, h);
+    assert_eq!(cvec.len(), w * cfg.output.height);
 
-    for h in handles {
-        m_parts.push(h.join().expect("child thread panicked"));
+    Samples {
+        color: cvec,
+        offsets: offs,
+        passes: pass_vecs,
+        stats,
     }
+}
 
-    let time = clock.elapsed();
+/// Colors each pixel by how many samples it took, relative to the pixel that took the most.
+/// Goes from black through red and yellow to white.
+fn heatmap(counts: &[u32]) -> Vec<Vector> {
+    let max = counts.iter().copied().max().unwrap_or(1).max(1) as f32;
+
+    counts
+        .iter()
+        .map(|&n| {
+            let t = 3.0 * n as f32 / max;
+            Vector::from_3(t, t - 1.0, t - 2.0).clamp(0.0, 1.0)
+        })
+        .collect()
+}
 
-    eprintln!("done ({}.{:03} sec)\n", time.as_secs(), time.as_millis());
-    let get_bytes = |m: mat::Matrix<draw::Color>| -> Vec<u8> {
-        let size = (m.rlen - 1) * (m.clen - 1) * 3;
-        let mut buf: Vec<u8> = Vec::with_capacity(size);
-
-        for y in 0..m.clen - 1 {
-            // iterate through valid rows (stopping before we hit the last row)
-            for x in 0..m.rlen - 1 {
-                // iterate through all columns, skipping invalid (last) one
-                let idx = x + y * (m.rlen - 1) + y;
-
-                let c = m.mat[idx];
-                buf.push(c.r);
-                buf.push(c.g);
-                buf.push(c.b);
-            }
+/// Looks up pass `p` in the passes recorded by the renderer, whether it was requested for output or just needed internally.
+fn get_pass<'a>(cfg: &opts::Config, pass_vecs: &'a [Vec<Vector>], p: opts::Pass) -> &'a [Vector] {
+    let i = cfg
+        .passes()
+        .iter()
+        .position(|&q| q == p)
+        .expect("pass not rendered");
+    &pass_vecs[i]
+}
+
+/// Writes an 8 bit image named `name` (without the extension) in the configured output format.
+fn write_img(cfg: &opts::Config, name: String, buf: &[u8]) {
+    let (w, h) = (cfg.output.width, cfg.output.height);
+
+    match cfg.output.format {
+        opts::Format::Ppm => {
+            let mut out_img = PPM::new(name + ".ppm", w, h, cfg.output.bits);
+            out_img.write(buf);
+        }
+        opts::Format::Png => {
+            let mut out_img = PNG::new(name + ".png", w, h, cfg.output.bits);
+            out_img.write(buf);
         }
+    }
+}
 
-        buf
-    };
+/// Turns colors with channels in the range [0, 1] into 8 bit RGB bytes.
+fn to_bytes(cvec: &[Vector]) -> Vec<u8> {
+    cvec.iter()
+        .flat_map(|v| {
+            let c = draw::map_color(v.clamp(0.0, 1.0));
+            [c.r, c.g, c.b]
+        })
+        .collect()
+}
+
+/// Writes the final image, named `path_pre` plus the extension, running the denoiser over it first if requested.
+/// Returns the time spent denoising.
+fn write_beauty(
+    cfg: &opts::Config,
+    path_pre: &str,
+    cvec: &[Vector],
+    pass_vecs: &[Vec<Vector>],
+) -> time::Duration {
+    if cfg.post.denoise {
+        let guides = denoise::Guides {
+            depth: get_pass(cfg, pass_vecs, opts::Pass::Depth),
+            normal: get_pass(cfg, pass_vecs, opts::Pass::Normal),
+            albedo: get_pass(cfg, pass_vecs, opts::Pass::Albedo),
+        };
 
-    let mut bvec = vec![];
+        let clock = time::Instant::now();
+        let (w, h) = (cfg.output.width, cfg.output.height);
+        let cvec = denoise::denoise(cvec, &guides, w, h, &cfg.post);
+        let taken = clock.elapsed();
 
-    for m in m_parts {
-        bvec.append(&mut get_bytes(m));
+        write_img(cfg, path_pre.to_string(), &to_bytes(&cvec));
+        taken
+    } else {
+        write_img(cfg, path_pre.to_string(), &to_bytes(cvec));
+        time::Duration::ZERO
     }
+}
 
-    assert_eq!(bvec.len(), w * h * 3);
+/// Writes each requested extra pass to its own image, named `path_pre` plus the name of the pass.
+fn write_passes(cfg: &opts::Config, path_pre: &str, pass_vecs: &[Vec<Vector>]) {
+    let (w, h) = (cfg.output.width, cfg.output.height);
+
+    for pass in &cfg.output.passes {
+        let pv = get_pass(cfg, pass_vecs, *pass);
+        assert_eq!(pv.len(), w * h);
+        let name = format!("{}_{}", path_pre, pass.name());
+
+        match pass {
+            // ambient occlusion is a regular matte, so it's written in the same format as the final image
+            opts::Pass::Ao => write_img(cfg, name.clone(), &to_bytes(pv)),
+            // everything else needs the range and precision of floats
+            _ => {
+                let channels = pass.channels();
+                let buf: Vec<u8> = pv
+                    .iter()
+                    .flat_map(|v| v.get()[..channels].to_vec())
+                    .flat_map(|f| f.to_le_bytes())
+                    .collect();
+
+                let mut out_img = PFM::new(name.clone() + ".pfm", w, h, channels);
+                out_img.write(&buf);
+            }
+        }
 
-    let path_pre = String::from(out_parts[0]);
+        // ID passes come with a list of which name belongs to which index
+        let names = match pass {
+            opts::Pass::Object => Some(&cfg.world.object_names),
+            opts::Pass::Material => Some(&cfg.world.material_names),
+            _ => None,
+        };
 
-    match cfg.output.format {
-        opts::Format::Ppm => {
-            let mut out_img = PPM::new(path_pre + ".ppm", w, h, cfg.output.bits);
-            out_img.write(&bvec);
+        if let Some(names) = names {
+            let legend: String = names
+                .iter()
+                .enumerate()
+                .map(|(i, n)| format!("{} {}\n", i, n))
+                .collect();
+            fs::write(name + ".txt", legend).expect("could not write pass legend");
         }
-        opts::Format::Png => {
-            let mut out_img = PNG::new(path_pre + ".png", w, h, cfg.output.bits);
-            out_img.write(&bvec);
+    }
+}
+
+/// Collects the pixels of a matrix rendered by a thread, dropping the extra row and column used to keep the canvas symmetrical.
+fn trim<T: Copy>(m: &mat::Matrix<T>) -> Vec<T> {
+    let mut buf = Vec::with_capacity((m.rlen - 1) * (m.clen - 1));
+
+    for y in 0..m.clen - 1 {
+        // iterate through valid rows (stopping before we hit the last row)
+        for x in 0..m.rlen - 1 {
+            // iterate through all columns, skipping invalid (last) one
+            let idx = x + y * (m.rlen - 1) + y;
+            buf.push(m.mat[idx]);
         }
     }
 
-    Ok(())
+    buf
+}
+
+/// Lays out `w` by `h` pixels like a matrix rendered by a thread, the opposite of `trim`.
+/// The extra row and column are filled with `fill`.
+fn untrim<T: Copy>(buf: &[T], w: usize, h: usize, fill: T) -> mat::Matrix<T> {
+    let mut m = mat::Matrix {
+        mat: vec![fill; (w + 1) * (h + 1)],
+        rlen: w + 1,
+        clen: h + 1,
+    };
+
+    for y in 0..h {
+        m.mat[y * (w + 1)..y * (w + 1) + w].copy_from_slice(&buf[y * w..(y + 1) * w]);
+    }
+
+    m
 }
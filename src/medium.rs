@@ -0,0 +1,294 @@
+//! Defines participating media: volumes of smoke, cloud or colored haze that light scatters through.
+//!
+//! Volumes are bounded by a signed distance field shape and marched through in fixed steps. At every step,
+//! light arriving from each light source is scattered towards the viewer according to the Henyey-Greenstein
+//! phase function, and everything further along the ray is dimmed by what the step absorbs and scatters away.
+
+use crate::rng::Rng;
+use crate::sdf::Shape;
+use crate::vec::*;
+
+use std::f32::consts::PI;
+
+/// Distance past which marching gives up, for rays that never hit anything.
+const MAX_DIST: f32 = 1000.0;
+
+/// Maximum number of steps taken along a single ray.
+const MAX_STEPS: u32 = 4096;
+
+/// Returns the Henyey-Greenstein phase function with anisotropy `g` for light turned by an angle with cosine `cos`.
+/// Positive `g` scatters light mostly forwards, negative `g` mostly backwards and 0 evenly in all directions.
+/// Integrates to 1 over the sphere of directions.
+pub fn phase(g: f32, cos: f32) -> f32 {
+    let denom = 1.0 + g * g - 2.0 * g * cos;
+    (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+}
+
+/// Returns a random value in [0, 1) for the lattice point `(x, y, z)`.
+fn lattice(x: i32, y: i32, z: i32, seed: u32) -> f32 {
+    let h = (x as u32 as u64).wrapping_mul(0x8da6b343)
+        ^ (y as u32 as u64).wrapping_mul(0xd8163841)
+        ^ (z as u32 as u64).wrapping_mul(0xcb1ab31f)
+        ^ (seed as u64) << 32;
+    Rng::new(h).next_f32()
+}
+
+/// Returns smoothly interpolated value noise at `p`, in [0, 1).
+fn value_noise(p: Vector, seed: u32) -> f32 {
+    let (fx, fy, fz) = (p.x().floor(), p.y().floor(), p.z().floor());
+    let (x, y, z) = (fx as i32, fy as i32, fz as i32);
+
+    // smoothstep the position within the cell so the noise has no visible grid lines
+    let s = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (sx, sy, sz) = (s(p.x() - fx), s(p.y() - fy), s(p.z() - fz));
+    let mix = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let mut layer = [0.0; 2];
+    for (dz, l) in layer.iter_mut().enumerate() {
+        let c = |dx: i32, dy: i32| lattice(x + dx, y + dy, z + dz as i32, seed);
+        *l = mix(mix(c(0, 0), c(1, 0), sx), mix(c(0, 1), c(1, 1), sx), sy);
+    }
+
+    mix(layer[0], layer[1], sz)
+}
+
+/// Describes how the density of a medium varies throughout its volume.
+pub enum Density {
+    /// The same density everywhere.
+    Constant,
+    /// Fractal value noise, for smoke and clouds. Features are roughly `1 / scale` units across,
+    /// with `octaves` layers of finer detail. Noise below `threshold` is empty, which breaks the medium up into wisps.
+    Noise {
+        scale: f32,
+        octaves: u32,
+        threshold: f32,
+        seed: u32,
+    },
+    /// A grid of `res.0` by `res.1` by `res.2` density values stretched over the box from `min` to `max`,
+    /// stored with x changing fastest and z slowest. Values are interpolated between grid points, and are 0 outside the box.
+    Grid {
+        res: (usize, usize, usize),
+        values: Vec<f32>,
+        min: Vector,
+        max: Vector,
+    },
+}
+
+impl Density {
+    /// Returns the relative density at point `p`, usually between 0 and 1.
+    pub fn at(&self, p: Vector) -> f32 {
+        match self {
+            Density::Constant => 1.0,
+            Density::Noise {
+                scale,
+                octaves,
+                threshold,
+                seed,
+            } => {
+                let mut n = 0.0;
+                let mut amp = 0.5;
+                let mut total = 0.0;
+                let mut q = p * Vector::from_s(*scale, 3);
+
+                for o in 0..*octaves {
+                    n += amp * value_noise(q, seed.wrapping_add(o));
+                    total += amp;
+                    amp *= 0.5;
+                    q = q * Vector::from_s(2.0, 3);
+                }
+
+                let n = n / total.max(1e-6);
+                ((n - threshold) / (1.0 - threshold).max(1e-6)).max(0.0)
+            }
+            Density::Grid {
+                res,
+                values,
+                min,
+                max,
+            } => {
+                // position in grid cells along each axis
+                let rel = (p - *min).get();
+                let size = (*max - *min).get();
+                let dims = [res.0, res.1, res.2];
+                let mut cell = [0usize; 3];
+                let mut frac = [0.0f32; 3];
+
+                for i in 0..3 {
+                    let g = rel[i] / size[i] * (dims[i] - 1) as f32;
+                    if !(0.0..=(dims[i] - 1) as f32).contains(&g) {
+                        return 0.0;
+                    }
+                    cell[i] = (g.floor() as usize).min(dims[i].saturating_sub(2));
+                    frac[i] = g - cell[i] as f32;
+                }
+
+                let at = |dx: usize, dy: usize, dz: usize| {
+                    let (x, y, z) = (
+                        (cell[0] + dx).min(dims[0] - 1),
+                        (cell[1] + dy).min(dims[1] - 1),
+                        (cell[2] + dz).min(dims[2] - 1),
+                    );
+                    values[(z * dims[1] + y) * dims[0] + x]
+                };
+                let mix = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+                let mut layer = [0.0; 2];
+                for (dz, l) in layer.iter_mut().enumerate() {
+                    *l = mix(
+                        mix(at(0, 0, dz), at(1, 0, dz), frac[0]),
+                        mix(at(0, 1, dz), at(1, 1, dz), frac[0]),
+                        frac[1],
+                    );
+                }
+
+                mix(layer[0], layer[1], frac[2])
+            }
+        }
+    }
+}
+
+/// The material filling a volume.
+pub struct Medium {
+    /// Fraction of light absorbed per unit of distance at a density of 1, for each color channel.
+    pub absorption: Vector,
+    /// Fraction of light scattered per unit of distance at a density of 1, for each color channel.
+    pub scattering: Vector,
+    /// Anisotropy of the phase function, from -1 (scatters backwards) to 1 (scatters forwards).
+    pub g: f32,
+    /// Multiplier applied to `field`.
+    pub density: f32,
+    /// How the density varies throughout the volume.
+    pub field: Density,
+}
+
+/// A region of space filled with a participating medium.
+pub struct Volume {
+    /// Boundary of the volume. The medium only fills the inside of the shape.
+    pub shape: Shape,
+    /// What fills the volume.
+    pub medium: Medium,
+    /// Distance between samples when marching through the volume. Smaller steps pick up finer detail but take longer.
+    pub step: f32,
+}
+
+/// Returns e raised to each element of `v`.
+fn exp(v: Vector) -> Vector {
+    let a = v.get();
+    Vector::from_3(a[0].exp(), a[1].exp(), a[2].exp())
+}
+
+/// Returns the absorption plus scattering (the extinction) of all volumes at `p`, plus the scattering of each volume.
+fn coefficients(vols: &[Volume], p: Vector) -> (Vector, Vec<Vector>) {
+    let mut ext = Vector::zero(3);
+    let mut scat = Vec::with_capacity(vols.len());
+
+    for v in vols {
+        let s = if v.shape.dist(p) < 0.0 {
+            let d = v.medium.density * v.medium.field.at(p);
+            ext = ext + (v.medium.absorption + v.medium.scattering) * Vector::from_s(d, 3);
+            v.medium.scattering * Vector::from_s(d, 3)
+        } else {
+            Vector::zero(3)
+        };
+        scat.push(s);
+    }
+
+    (ext, scat)
+}
+
+/// Marches through `vols` along a ray from `o` going `dist` units in unit direction `d`, sampling each step
+/// `jitter` (in [0, 1)) of the way along it. `dist` can be infinite for rays that never hit anything.
+///
+/// When `light` is given, it's called with each point sampled and returns the light arriving there as pairs
+/// of the direction towards the light (`None` for light arriving evenly from everywhere) and its color.
+///
+/// Returns the light scattered towards `o` along the ray, and the fraction of light from beyond the ray that makes it through.
+pub fn march<F>(
+    vols: &[Volume],
+    o: Vector,
+    d: Vector,
+    dist: f32,
+    jitter: f32,
+    light: Option<F>,
+) -> (Vector, Vector)
+where
+    F: Fn(Vector) -> Vec<(Option<Vector>, Vector)>,
+{
+    let mut tr = Vector::from_s(1.0, 3);
+    let mut scattered = Vector::zero(3);
+    if vols.is_empty() {
+        return (scattered, tr);
+    }
+
+    let step = vols.iter().fold(f32::INFINITY, |s, v| s.min(v.step));
+    let end = dist.min(MAX_DIST);
+    let mut t = 0.0;
+
+    for _ in 0..MAX_STEPS {
+        if t >= end {
+            break;
+        }
+
+        // skip empty space quickly by stepping up to the closest boundary,
+        // moving on at least a bit each time so rays grazing a volume don't crawl along it
+        let p = o + d * Vector::from_s(t, 3);
+        let gap = vols
+            .iter()
+            .fold(f32::INFINITY, |g, v| g.min(v.shape.dist(p)));
+        if gap > 0.0 {
+            t += gap.max(step * 0.1);
+            continue;
+        }
+
+        let dt = step.min(end - t);
+        let p = o + d * Vector::from_s(t + dt * jitter, 3);
+        let (ext, scat) = coefficients(vols, p);
+
+        if let Some(light) = &light {
+            // light scattered towards the viewer at this point
+            let mut s = Vector::zero(3);
+            if scat.iter().any(|s| s.dot(Vector::from_s(1.0, 3)) > 0.0) {
+                for (ld, lc) in light(p) {
+                    for (v, sc) in vols.iter().zip(scat.iter()) {
+                        let f = match ld {
+                            Some(ld) => phase(v.medium.g, ld.dot(-d)),
+                            None => 1.0,
+                        };
+                        s = s + *sc * lc * Vector::from_s(f, 3);
+                    }
+                }
+            }
+
+            // integrate the scattered light exactly over the step, assuming it stays constant,
+            // so thick media don't get brighter than they should with big steps
+            let e = ext.get();
+            let a = s.get();
+            let mut step_light = [0.0; 3];
+            for i in 0..3 {
+                step_light[i] = if e[i] > 1e-6 {
+                    a[i] * (1.0 - (-e[i] * dt).exp()) / e[i]
+                } else {
+                    a[i] * dt
+                };
+            }
+            scattered =
+                scattered + tr * Vector::from_3(step_light[0], step_light[1], step_light[2]);
+        }
+
+        tr = tr * exp(-ext * Vector::from_s(dt, 3));
+        if tr.dot(Vector::from_s(1.0, 3)) < 1e-4 {
+            tr = Vector::zero(3);
+            break;
+        }
+
+        t += dt;
+    }
+
+    (scattered, tr)
+}
+
+/// Returns the fraction of light that makes it through `vols` along a ray from `o` going `dist` units
+/// in unit direction `d`, for each color channel.
+pub fn transmittance(vols: &[Volume], o: Vector, d: Vector, dist: f32) -> Vector {
+    march::<fn(Vector) -> Vec<(Option<Vector>, Vector)>>(vols, o, d, dist, 0.5, None).1
+}
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use crate::medium::*;
+    use crate::sdf::Shape;
+    use crate::vec::*;
+
+    use std::f32::consts::PI;
+
+    // marching in steps is only accurate to within about a step
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-2
+    }
+
+    fn slab(field: Density) -> Volume {
+        Volume {
+            shape: Shape::Cuboid {
+                b: Vector::from_3(10.0, 10.0, 1.0),
+            },
+            medium: Medium {
+                absorption: Vector::from_3(0.5, 0.0, 0.0),
+                scattering: Vector::from_3(0.0, 0.0, 0.5),
+                g: 0.0,
+                density: 1.0,
+                field,
+            },
+            step: 0.01,
+        }
+    }
+
+    #[test]
+    fn phase_test() {
+        // isotropic scattering is the same in every direction
+        assert!(approx(phase(0.0, 0.3), 1.0 / (4.0 * PI)));
+        assert!(phase(0.5, 1.0) > phase(0.5, -1.0));
+        assert!(phase(-0.5, 1.0) < phase(-0.5, -1.0));
+
+        // integrates to 1 over the sphere
+        let n = 1000;
+        let sum: f32 = (0..n)
+            .map(|i| {
+                phase(0.7, -1.0 + 2.0 * (i as f32 + 0.5) / n as f32) * 2.0 * PI * 2.0 / n as f32
+            })
+            .sum();
+        assert!((sum - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn transmittance_test() {
+        let vols = vec![slab(Density::Constant)];
+        let o = Vector::from_3(0.0, 0.0, -5.0);
+        let d = Vector::from_3(0.0, 0.0, 1.0);
+
+        // 2 units through the slab with an extinction of 0.5
+        let tr = transmittance(&vols, o, d, f32::INFINITY);
+        assert!(approx(tr.x(), (-1.0f32).exp()));
+        assert!(approx(tr.z(), (-1.0f32).exp()));
+
+        // stopping halfway through only goes through half as much
+        let tr = transmittance(&vols, o, d, 5.0);
+        assert!(approx(tr.x(), (-0.5f32).exp()));
+
+        // missing the slab leaves light untouched
+        let tr = transmittance(&vols, o, Vector::from_3(0.0, 1.0, 0.0), f32::INFINITY);
+        assert!(approx(tr.x(), 1.0));
+    }
+
+    #[test]
+    fn scatter_test() {
+        let vols = vec![slab(Density::Constant)];
+        let light = |_: Vector| vec![(None, Vector::from_s(1.0, 3))];
+
+        let (s, _) = march(
+            &vols,
+            Vector::from_3(0.0, 0.0, -5.0),
+            Vector::from_3(0.0, 0.0, 1.0),
+            f32::INFINITY,
+            0.5,
+            Some(light),
+        );
+
+        // only the scattering channel picks up light, and at most as much as a fully opaque medium would
+        assert!(approx(s.x(), 0.0));
+        assert!(s.z() > 0.0 && s.z() < 1.0);
+        assert!(approx(s.z(), 0.5 * (1.0 - (-1.0f32).exp()) / 0.5));
+    }
+
+    #[test]
+    fn density_test() {
+        let noise = Density::Noise {
+            scale: 2.0,
+            octaves: 4,
+            threshold: 0.0,
+            seed: 1,
+        };
+        for i in 0..100 {
+            let d = noise.at(Vector::from_3(i as f32 * 0.37, 1.3, -0.7));
+            assert!((0.0..=1.0).contains(&d));
+        }
+
+        let grid = Density::Grid {
+            res: (2, 2, 2),
+            values: vec![0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0],
+            min: Vector::zero(3),
+            max: Vector::from_s(1.0, 3),
+        };
+        assert!(approx(grid.at(Vector::from_3(0.25, 0.5, 0.5)), 0.25));
+        assert_eq!(grid.at(Vector::from_3(2.0, 0.5, 0.5)), 0.0);
+    }
+}
@@ -0,0 +1,463 @@
+//! Defines triangle meshes, kept fast to trace by a bounding volume hierarchy over their triangles.
+
+use crate::mat::Matrix;
+use crate::ray::*;
+use crate::vec::*;
+use std::sync::Arc;
+
+/// Most triangles kept together in a leaf of the hierarchy.
+const LEAF_SIZE: usize = 4;
+
+/// An image wrapped over a mesh using the texture coordinates of its vertices.
+pub struct Texture {
+    /// Colors of the image, with rows from top to bottom.
+    pub img: Matrix<Vector>,
+    /// File the image was read from, if it came from its own file.
+    pub source: Option<String>,
+}
+
+impl Texture {
+    /// Reads a PNG or JPEG image from the file at `path`.
+    pub fn load(path: &str) -> Texture {
+        let bytes = std::fs::read(path).unwrap_or_else(|e| panic!("couldn't read {path}: {e}"));
+        Texture::decode(&bytes, Some(path.to_string()))
+    }
+
+    /// Decodes a PNG or JPEG image from `bytes`, which came from the file `source` if it's set.
+    /// Colors are read as they're stored, from 0 to 1, the same way scene colors are.
+    pub fn decode(bytes: &[u8], source: Option<String>) -> Texture {
+        let (w, h, channels, data) = if bytes.starts_with(b"\x89PNG") {
+            let mut decoder = png::Decoder::new(bytes);
+            decoder.set_transformations(png::Transformations::normalize_to_color8());
+            let mut reader = decoder.read_info().expect("couldn't read PNG texture");
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let info = reader
+                .next_frame(&mut buf)
+                .expect("couldn't decode PNG texture");
+            buf.truncate(info.buffer_size());
+            (
+                info.width as usize,
+                info.height as usize,
+                info.color_type.samples(),
+                buf,
+            )
+        } else if bytes.starts_with(&[0xFF, 0xD8]) {
+            let mut decoder = zune_jpeg::JpegDecoder::new(std::io::Cursor::new(bytes));
+            let buf = decoder.decode().expect("couldn't decode JPEG texture");
+            let info = decoder.info().expect("couldn't read JPEG texture");
+            let (w, h) = (info.width as usize, info.height as usize);
+            (w, h, buf.len() / (w * h).max(1), buf)
+        } else {
+            panic!("unsupported texture format, only PNG and JPEG are supported");
+        };
+
+        // grayscale images have one or two channels, color images three or four, and alpha is dropped
+        let mat = data
+            .chunks_exact(channels)
+            .map(|px| {
+                let c = |i: usize| px[if channels < 3 { 0 } else { i }] as f32 / 255.0;
+                Vector::from_3(c(0), c(1), c(2))
+            })
+            .collect();
+
+        Texture {
+            img: Matrix {
+                mat,
+                rlen: w,
+                clen: h,
+            },
+            source,
+        }
+    }
+
+    /// Finds the color at texture coordinates `uv`, blending the four nearest pixels.
+    /// Coordinates run from 0 to 1 across the image, with v = 0 at the top, and repeat outside that range.
+    pub fn lookup(&self, uv: (f32, f32)) -> Vector {
+        let (w, h) = (self.img.rlen, self.img.clen);
+
+        let fx = uv.0 * w as f32 - 0.5;
+        let fy = uv.1 * h as f32 - 0.5;
+
+        let x0 = fx.floor();
+        let y0 = fy.floor();
+        let (tx, ty) = (fx - x0, fy - y0);
+
+        let x0 = (x0 as i64).rem_euclid(w as i64) as usize;
+        let y0 = (y0 as i64).rem_euclid(h as i64) as usize;
+        let x1 = (x0 + 1) % w;
+        let y1 = (y0 + 1) % h;
+
+        let px = |x, y| self.img.mat[y * w + x];
+        let top = Vector::lerp(px(x1, y0), px(x0, y0), tx);
+        let bot = Vector::lerp(px(x1, y1), px(x0, y1), tx);
+
+        Vector::lerp(bot, top, ty)
+    }
+}
+
+/// A triangle of a mesh.
+#[derive(Clone, Copy)]
+pub struct Triangle {
+    /// Corners of the triangle.
+    pub p: [Vector; 3],
+    /// Normals at each corner, blended across the triangle to make it look smooth.
+    pub n: Option<[Vector; 3]>,
+    /// Texture coordinates at each corner.
+    pub uv: Option<[(f32, f32); 3]>,
+    /// Colors at each corner, blended across the triangle and multiplying the material color.
+    pub color: Option<[Vector; 3]>,
+}
+
+impl Triangle {
+    /// Returns the normal of the flat triangle, following the right hand rule through its corners in order.
+    pub fn face_normal(&self) -> Vector {
+        (self.p[1] - self.p[0]).cross(self.p[2] - self.p[0]).norm()
+    }
+
+    /// Returns the area of the triangle.
+    pub fn area(&self) -> f32 {
+        mag((self.p[1] - self.p[0]).cross(self.p[2] - self.p[0])) / 2.0
+    }
+
+    /// Checks for a hit from ray `r` over time range `t` with the Möller–Trumbore algorithm.
+    /// Both sides of the triangle can be hit.
+    pub fn hit(&self, r: &Ray, t: (f32, f32)) -> Option<f32> {
+        let e1 = self.p[1] - self.p[0];
+        let e2 = self.p[2] - self.p[0];
+
+        let pv = r.d.cross(e2);
+        let det = e1.dot(pv);
+        if det.abs() < 1e-12 {
+            return None; // parallel to the triangle
+        }
+
+        let inv = 1.0 / det;
+        let tv = r.o - self.p[0];
+        let u = tv.dot(pv) * inv;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let qv = tv.cross(e1);
+        let v = r.d.dot(qv) * inv;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let ct = e2.dot(qv) * inv;
+        (t.0 < ct && ct < t.1).then_some(ct)
+    }
+
+    /// Returns the weights of each corner for the point on the triangle's plane nearest to `p`,
+    /// along with how far outside the triangle that point is (0 for points inside).
+    fn barycentric(&self, p: Vector) -> ([f32; 3], f32) {
+        let e1 = self.p[1] - self.p[0];
+        let e2 = self.p[2] - self.p[0];
+        let v = p - self.p[0];
+
+        let (d00, d01, d11) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
+        let (d20, d21) = (v.dot(e1), v.dot(e2));
+        let denom = d00 * d11 - d01 * d01;
+        if denom <= 0.0 {
+            return ([1.0, 0.0, 0.0], f32::INFINITY); // degenerate triangle
+        }
+
+        let b1 = (d11 * d20 - d01 * d21) / denom;
+        let b2 = (d00 * d21 - d01 * d20) / denom;
+        let w = [1.0 - b1 - b2, b1, b2];
+
+        let outside: f32 = w.iter().map(|b| (-b).max(0.0)).sum();
+        let plane = e1.cross(e2).norm().dot(v).abs();
+
+        (w, plane + outside * (d00.sqrt() + d11.sqrt()))
+    }
+
+    /// Blends the corner normals with weights `w`, or returns the flat normal if the triangle has none.
+    fn normal_at(&self, w: [f32; 3]) -> Vector {
+        match self.n {
+            Some(n) => blend(n, w).norm(),
+            None => self.face_normal(),
+        }
+    }
+}
+
+/// A node of the bounding volume hierarchy.
+/// Leaves hold `count` triangles from `start`, other nodes have their first child right after them
+/// and their second child at `start`.
+struct Node {
+    min: Vector,
+    max: Vector,
+    start: usize,
+    count: usize,
+}
+
+/// Blends the values `v` at the corners of a triangle with weights `w`.
+fn blend(v: [Vector; 3], w: [f32; 3]) -> Vector {
+    v[0] * Vector::from_s(w[0], 3) + v[1] * Vector::from_s(w[1], 3) + v[2] * Vector::from_s(w[2], 3)
+}
+
+/// Returns the length of `v`.
+fn mag(v: Vector) -> f32 {
+    v.dot(v).sqrt()
+}
+
+/// Checks whether ray `r`, with the reciprocal of its direction `inv`, passes through the box from `min` to `max`
+/// over time range `t`.
+fn hits_box(min: Vector, max: Vector, r: &Ray, inv: Vector, t: (f32, f32)) -> bool {
+    let a = (min - r.o) * inv;
+    let b = (max - r.o) * inv;
+    let (near, far) = (a.min(b).get(), a.max(b).get());
+
+    let enter = near[0].max(near[1]).max(near[2]).max(t.0);
+    let exit = far[0].min(far[1]).min(far[2]).min(t.1);
+    enter <= exit
+}
+
+/// A mesh of triangles.
+pub struct Mesh {
+    tris: Vec<Triangle>,
+    nodes: Vec<Node>,
+    /// Running total of triangle areas, used to pick points on the surface.
+    areas: Vec<f32>,
+    /// Whether any triangle has corner colors.
+    colored: bool,
+    /// The material of the mesh.
+    pub mat: Material,
+    /// Image multiplying the material color, wrapped over triangles with texture coordinates.
+    pub texture: Option<Arc<Texture>>,
+    /// How far the mesh moves while the shutter is open.
+    pub motion: Vector,
+}
+
+impl Mesh {
+    /// Creates a mesh from `tris`, building the hierarchy used to trace it.
+    pub fn new(
+        tris: Vec<Triangle>,
+        mat: Material,
+        texture: Option<Arc<Texture>>,
+        motion: Vector,
+    ) -> Mesh {
+        assert!(!tris.is_empty(), "mesh has no triangles");
+
+        let mut order: Vec<usize> = (0..tris.len()).collect();
+        let mut nodes = Vec::new();
+        build(&tris, &mut order, 0, &mut nodes);
+
+        let tris: Vec<Triangle> = order.iter().map(|&i| tris[i]).collect();
+        let areas = tris
+            .iter()
+            .scan(0.0, |sum, tri| {
+                *sum += tri.area();
+                Some(*sum)
+            })
+            .collect();
+
+        Mesh {
+            colored: tris.iter().any(|tri| tri.color.is_some()),
+            tris,
+            nodes,
+            areas,
+            mat,
+            texture,
+            motion,
+        }
+    }
+
+    /// Returns the triangles of the mesh.
+    pub fn triangles(&self) -> &[Triangle] {
+        &self.tris
+    }
+
+    /// Returns the corners of a box around the mesh where it starts out.
+    pub fn bounds(&self) -> (Vector, Vector) {
+        (self.nodes[0].min, self.nodes[0].max)
+    }
+
+    /// Finds the triangle nearest to point `p` on the surface of the mesh where it starts out,
+    /// along with the weights of its corners at that point.
+    fn locate(&self, p: Vector) -> (usize, [f32; 3]) {
+        let mut best = (0, [1.0, 0.0, 0.0], f32::INFINITY);
+        let tol = Vector::from_s(1e-3, 3);
+
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            let outside = (node.min - tol - p).max(p - node.max - tol).get();
+            if outside[0] > 0.0 || outside[1] > 0.0 || outside[2] > 0.0 {
+                continue;
+            }
+
+            if node.count > 0 {
+                for j in node.start..node.start + node.count {
+                    let (w, err) = self.tris[j].barycentric(p);
+                    if err < best.2 {
+                        best = (j, w, err);
+                    }
+                }
+            } else {
+                stack.push(node.start);
+                stack.push(i + 1);
+            }
+        }
+
+        // points slightly off the surface can miss every box, so fall back to checking everything
+        if best.2 == f32::INFINITY {
+            for (j, tri) in self.tris.iter().enumerate() {
+                let (w, err) = tri.barycentric(p);
+                if err < best.2 {
+                    best = (j, w, err);
+                }
+            }
+        }
+
+        (best.0, best.1)
+    }
+}
+
+/// Builds the hierarchy over the triangles listed in `order`, whose first one is triangle `start` of the finished mesh.
+/// Splits the triangles in half along the axis their centers are most spread out on until few enough are left.
+fn build(tris: &[Triangle], order: &mut [usize], start: usize, nodes: &mut Vec<Node>) {
+    let corners = order.iter().flat_map(|&i| tris[i].p);
+    let min = corners
+        .clone()
+        .fold(Vector::from_s(f32::INFINITY, 3), Vector::min);
+    let max = corners.fold(Vector::from_s(f32::NEG_INFINITY, 3), Vector::max);
+
+    let idx = nodes.len();
+    nodes.push(Node {
+        min,
+        max,
+        start,
+        count: order.len(),
+    });
+    if order.len() <= LEAF_SIZE {
+        return;
+    }
+
+    let center = |i: usize| tris[i].p[0] + tris[i].p[1] + tris[i].p[2];
+    let lo = order
+        .iter()
+        .map(|&i| center(i))
+        .fold(Vector::from_s(f32::INFINITY, 3), Vector::min);
+    let hi = order
+        .iter()
+        .map(|&i| center(i))
+        .fold(Vector::from_s(f32::NEG_INFINITY, 3), Vector::max);
+    let spread = (hi - lo).get();
+    let axis = (0..3).fold(0, |a, b| if spread[b] > spread[a] { b } else { a });
+
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by(mid, |&a, &b| {
+        center(a).get()[axis].total_cmp(&center(b).get()[axis])
+    });
+
+    let (left, right) = order.split_at_mut(mid);
+    build(tris, left, start, nodes);
+    nodes[idx].start = nodes.len();
+    nodes[idx].count = 0;
+    build(tris, right, start + mid, nodes);
+}
+
+impl RayInteraction for Mesh {
+    fn hit(&self, r: &Ray, t: (f32, f32)) -> HitType {
+        // trace the mesh where it sits at the time of the ray by moving the ray the other way instead
+        let r = &Ray {
+            o: r.o - self.motion * Vector::from_s(r.time, 3),
+            d: r.d,
+            time: r.time,
+        };
+        let inv = Vector::from_s(1.0, 3) / r.d;
+
+        let mut near = t.1;
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !hits_box(node.min, node.max, r, inv, (t.0, near)) {
+                continue;
+            }
+
+            if node.count > 0 {
+                for tri in &self.tris[node.start..node.start + node.count] {
+                    if let Some(ct) = tri.hit(r, (t.0, near)) {
+                        near = ct;
+                    }
+                }
+            } else {
+                stack.push(node.start);
+                stack.push(i + 1);
+            }
+        }
+
+        if near < t.1 {
+            HitType::Hit(near)
+        } else {
+            HitType::Miss()
+        }
+    }
+
+    fn normal(&self, p: &Vector, time: f32) -> Vector {
+        let (i, w) = self.locate(*p - self.motion * Vector::from_s(time, 3));
+        self.tris[i].normal_at(w)
+    }
+
+    fn material(&self, p: &Vector, time: f32) -> Material {
+        // plain meshes don't need to search for the triangle that was hit
+        if self.texture.is_none() && !self.colored {
+            return self.mat;
+        }
+
+        let (i, w) = self.locate(*p - self.motion * Vector::from_s(time, 3));
+        let tri = &self.tris[i];
+
+        let mut color = self.mat.color;
+        if let (Some(tex), Some(uv)) = (&self.texture, tri.uv) {
+            let u = (0..3).map(|j| uv[j].0 * w[j]).sum();
+            let v = (0..3).map(|j| uv[j].1 * w[j]).sum();
+            color = color * tex.lookup((u, v));
+        }
+        if let Some(c) = tri.color {
+            color = color * blend(c, w);
+        }
+
+        Material { color, ..self.mat }
+    }
+
+    fn base_material(&self) -> Material {
+        self.mat
+    }
+
+    fn sample(&self, u: (f32, f32), time: f32) -> Option<(Vector, Vector, f32)> {
+        let area = *self.areas.last().unwrap();
+        if area <= 0.0 {
+            return None;
+        }
+
+        // pick a triangle by its share of the area, and reuse what's left of u.0 to pick a point on it
+        let target = u.0 * area;
+        let i = self
+            .areas
+            .partition_point(|&a| a <= target)
+            .min(self.tris.len() - 1);
+        let below = if i > 0 { self.areas[i - 1] } else { 0.0 };
+        let s = ((target - below) / (self.areas[i] - below).max(f32::MIN_POSITIVE)).clamp(0.0, 1.0);
+
+        let su = s.sqrt();
+        let w = [1.0 - su, u.1 * su, (1.0 - u.1) * su];
+        let tri = &self.tris[i];
+        let p = blend(tri.p, w);
+
+        Some((
+            p + self.motion * Vector::from_s(time, 3),
+            tri.normal_at(w),
+            area,
+        ))
+    }
+
+    fn name(&self) -> &'static str {
+        "mesh"
+    }
+
+    fn describe(&self) -> Object<'_> {
+        Object::Mesh(self)
+    }
+}
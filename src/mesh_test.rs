@@ -0,0 +1,204 @@
+#[cfg(test)]
+mod tests {
+    use crate::bsdf::*;
+    use crate::mat::Matrix;
+    use crate::mesh::*;
+    use crate::ray::*;
+    use crate::rng::Rng;
+    use crate::vec::*;
+
+    use std::sync::Arc;
+
+    fn material() -> Material {
+        Material {
+            color: Vector::from_3(1.0, 1.0, 1.0),
+            bsdf: Model::Lambert(Lambert {}),
+            refl: 0.0,
+            roughness: 0.0,
+            emission: Vector::zero(3),
+            transmission: Vector::zero(3),
+            id: 0,
+        }
+    }
+
+    fn tri(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> Triangle {
+        Triangle {
+            p: [
+                Vector::from_3(a[0], a[1], a[2]),
+                Vector::from_3(b[0], b[1], b[2]),
+                Vector::from_3(c[0], c[1], c[2]),
+            ],
+            n: None,
+            uv: None,
+            color: None,
+        }
+    }
+
+    fn ray(o: Vector, d: Vector) -> Ray {
+        Ray { o, d, time: 0.0 }
+    }
+
+    #[test]
+    fn triangle_test() {
+        let t = tri([0.0, 0.0, 2.0], [1.0, 0.0, 2.0], [0.0, 1.0, 2.0]);
+        assert_eq!(t.area(), 0.5);
+
+        // hits from both sides, measured in multiples of the direction
+        let r = ray(Vector::from_3(0.2, 0.2, 0.0), Vector::from_3(0.0, 0.0, 2.0));
+        assert_eq!(t.hit(&r, (0.0, f32::INFINITY)), Some(1.0));
+        let r = ray(
+            Vector::from_3(0.2, 0.2, 3.0),
+            Vector::from_3(0.0, 0.0, -1.0),
+        );
+        assert_eq!(t.hit(&r, (0.0, f32::INFINITY)), Some(1.0));
+
+        // outside the triangle, or beyond the range
+        let r = ray(Vector::from_3(0.6, 0.6, 0.0), Vector::from_3(0.0, 0.0, 1.0));
+        assert_eq!(t.hit(&r, (0.0, f32::INFINITY)), None);
+        let r = ray(Vector::from_3(0.2, 0.2, 0.0), Vector::from_3(0.0, 0.0, 1.0));
+        assert_eq!(t.hit(&r, (0.0, 1.5)), None);
+    }
+
+    #[test]
+    fn hierarchy_test() {
+        // a cloud of small random triangles, traced against checking every triangle
+        let mut rng = Rng::new(7);
+        let mut f = || rng.next_f32() * 4.0 - 2.0;
+        let tris: Vec<Triangle> = (0..300)
+            .map(|_| {
+                let c = [f(), f(), f() + 5.0];
+                let corner =
+                    |d: [f32; 3]| [c[0] + d[0] * 0.3, c[1] + d[1] * 0.3, c[2] + d[2] * 0.3];
+                tri(c, corner([1.0, 0.2, 0.1]), corner([0.1, 1.0, -0.3]))
+            })
+            .collect();
+        let mesh = Mesh::new(tris.clone(), material(), None, Vector::zero(3));
+
+        let mut hits = 0;
+        for i in 0..400 {
+            let (x, y) = ((i % 20) as f32 / 5.0 - 2.0, (i / 20) as f32 / 5.0 - 2.0);
+            let r = ray(Vector::zero(3), Vector::from_3(x, y, 5.0));
+
+            let nearest = tris
+                .iter()
+                .filter_map(|t| t.hit(&r, (0.0, f32::INFINITY)))
+                .fold(f32::INFINITY, f32::min);
+            match mesh.hit(&r, (0.0, f32::INFINITY)) {
+                HitType::Hit(t) => {
+                    assert_eq!(t, nearest);
+                    hits += 1;
+                }
+                HitType::Miss() => assert_eq!(nearest, f32::INFINITY),
+            }
+        }
+        assert!(hits > 50);
+    }
+
+    #[test]
+    fn surface_test() {
+        // a unit square facing -z, with bent normals and texture coordinates
+        let mut a = tri([0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]);
+        let mut b = tri([0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 0.0]);
+        let up = Vector::from_3(0.0, 1.0, -1.0).norm();
+        let down = Vector::from_3(0.0, -1.0, -1.0).norm();
+        a.n = Some([down, up, up]);
+        b.n = Some([down, up, down]);
+        a.uv = Some([(0.0, 1.0), (0.0, 0.0), (1.0, 0.0)]);
+        b.uv = Some([(0.0, 1.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        // a 2x1 texture, red on the left and blue on the right
+        let img = Matrix {
+            mat: vec![Vector::from_3(1.0, 0.0, 0.0), Vector::from_3(0.0, 0.0, 1.0)],
+            rlen: 2,
+            clen: 1,
+        };
+        let tex = Arc::new(Texture { img, source: None });
+        let mesh = Mesh::new(vec![a, b], material(), Some(tex), Vector::zero(3));
+
+        assert_eq!(a.face_normal(), Vector::from_3(0.0, 0.0, -1.0));
+        let n = mesh.normal(&Vector::from_3(0.5, 0.5, 0.0), 0.0);
+        assert!(
+            (n - Vector::from_3(0.0, 0.0, -1.0)).dot(n - Vector::from_3(0.0, 0.0, -1.0)) < 1e-10
+        );
+
+        // a quarter of the way across lands on the center of the left pixel
+        let left = mesh.material(&Vector::from_3(0.25, 0.5, 0.0), 0.0).color;
+        let right = mesh.material(&Vector::from_3(0.75, 0.5, 0.0), 0.0).color;
+        assert!(
+            (left - Vector::from_3(1.0, 0.0, 0.0)).dot(left - Vector::from_3(1.0, 0.0, 0.0))
+                < 1e-10
+        );
+        assert!(
+            (right - Vector::from_3(0.0, 0.0, 1.0)).dot(right - Vector::from_3(0.0, 0.0, 1.0))
+                < 1e-10
+        );
+
+        // halfway between the pixel centers, wrapping around at the edges
+        let tex = mesh.texture.as_ref().unwrap();
+        assert_eq!(tex.lookup((0.5, 0.5)), Vector::from_3(0.5, 0.0, 0.5));
+        assert_eq!(tex.lookup((0.0, 0.5)), Vector::from_3(0.5, 0.0, 0.5));
+        assert_eq!(tex.lookup((1.25, 3.5)), Vector::from_3(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn moving_texture_test() {
+        // a triangle moving along x, with u following x across a texture red on the left and blue on the right
+        let mut t = tri([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        t.uv = Some([(0.0, 0.5), (1.0, 0.5), (0.0, 0.5)]);
+        let img = Matrix {
+            mat: vec![Vector::from_3(1.0, 0.0, 0.0), Vector::from_3(0.0, 0.0, 1.0)],
+            rlen: 2,
+            clen: 1,
+        };
+        let tex = Arc::new(Texture { img, source: None });
+        let mesh = Mesh::new(
+            vec![t],
+            material(),
+            Some(tex),
+            Vector::from_3(0.5, 0.0, 0.0),
+        );
+
+        // the texture moves along with the triangle
+        let red = Vector::from_3(1.0, 0.0, 0.0);
+        let blue = Vector::from_3(0.0, 0.0, 1.0);
+        assert_eq!(
+            mesh.material(&Vector::from_3(0.25, 0.25, 0.0), 0.0).color,
+            red
+        );
+        assert_eq!(
+            mesh.material(&Vector::from_3(0.75, 0.25, 0.0), 1.0).color,
+            red
+        );
+        assert_eq!(
+            mesh.material(&Vector::from_3(1.0, 0.1, 0.0), 0.5).color,
+            blue
+        );
+    }
+
+    #[test]
+    fn sample_test() {
+        let mesh = Mesh::new(
+            vec![
+                tri([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+                tri([5.0, 0.0, 0.0], [8.0, 0.0, 0.0], [5.0, 1.0, 0.0]),
+            ],
+            material(),
+            None,
+            Vector::from_3(0.0, 0.0, 1.0),
+        );
+
+        // the second triangle is three times the size, so it gets three quarters of the points
+        let mut rng = Rng::new(3);
+        let mut far = 0;
+        for _ in 0..4000 {
+            let (p, n, area) = mesh.sample((rng.next_f32(), rng.next_f32()), 0.5).unwrap();
+            assert_eq!(area, 2.0);
+            assert_eq!(p.z(), 0.5);
+            assert_eq!(n.z().abs(), 1.0);
+            if p.x() >= 5.0 {
+                far += 1;
+            }
+        }
+        assert!((2850..3150).contains(&far));
+    }
+}
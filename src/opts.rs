@@ -1,16 +1,116 @@
 //! Defines all configuration options for the renderer.
 
+use crate::env::EnvMap;
+use crate::medium::Volume;
 use crate::vec::Vector;
 
 /// List of possible formats for render output. PNG is recommended.
+#[derive(Default)]
 pub enum Format {
     Ppm,
+    #[default]
     Png,
 }
 
-impl Default for Format {
-    fn default() -> Format {
-        Format::Png
+/// List of extra per-pixel passes that can be written alongside the final image.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pass {
+    /// Distance from the camera to the first hit.
+    Depth,
+    /// World space surface normal at the first hit.
+    Normal,
+    /// Base color of the material at the first hit.
+    Albedo,
+    /// Index of the object at the first hit.
+    Object,
+    /// Index of the material at the first hit.
+    Material,
+    /// Fraction of ambient light reaching the first hit.
+    Ao,
+}
+
+impl Pass {
+    /// Returns the name of the pass, used in config files and output file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::Depth => "depth",
+            Pass::Normal => "normal",
+            Pass::Albedo => "albedo",
+            Pass::Object => "object",
+            Pass::Material => "material",
+            Pass::Ao => "ao",
+        }
+    }
+
+    /// Returns the number of meaningful channels in the pass.
+    pub fn channels(&self) -> usize {
+        match self {
+            Pass::Normal | Pass::Albedo => 3,
+            _ => 1,
+        }
+    }
+}
+
+/// List of filters used to reconstruct pixels from the samples around them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Filter {
+    /// Averages the samples inside each pixel.
+    #[default]
+    Box,
+    /// Weights samples linearly by distance.
+    Tent,
+    /// Weights samples with a bell curve, giving a soft result.
+    Gaussian,
+    /// Mitchell-Netravali filter, which balances sharpness and ringing.
+    Mitchell,
+    /// Windowed sinc filter, which is sharp but can ring around edges.
+    Lanczos,
+}
+
+impl Filter {
+    /// Returns the name of the filter, used in config files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Box => "box",
+            Filter::Tent => "tent",
+            Filter::Gaussian => "gaussian",
+            Filter::Mitchell => "mitchell",
+            Filter::Lanczos => "lanczos",
+        }
+    }
+
+    /// Returns the radius the filter uses if none is set, in pixels.
+    pub fn default_radius(&self) -> f32 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell | Filter::Lanczos => 2.0,
+        }
+    }
+}
+
+/// List of patterns random decisions can be drawn from. See the `sampler` module for details on each.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SamplerType {
+    #[default]
+    Random,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+impl SamplerType {
+    /// Returns the name of the sampler, used in config files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SamplerType::Random => "random",
+            SamplerType::Stratified => "stratified",
+            SamplerType::Halton => "halton",
+            SamplerType::Sobol => "sobol",
+            SamplerType::BlueNoise => "blue_noise",
+        }
     }
 }
 
@@ -25,6 +125,18 @@ pub struct Output {
     pub height: usize,
     /// Number of bits per channel in the resulting image.
     pub bits: usize,
+    /// Extra passes to write alongside the final image.
+    pub passes: Vec<Pass>,
+    /// Rewrite the image with the samples taken so far every time this many samples per pixel finish. 0 disables this.
+    pub preview_passes: u32,
+    /// Rewrite the image with the samples taken so far once this many seconds have passed since the last write. 0 disables this.
+    pub preview_seconds: f32,
+    /// File to write a JSON line describing render progress to every time progress is reported. `-` means stdout.
+    pub progress_file: Option<String>,
+    /// Write an image showing how many samples each pixel took, as "<name>_samples".
+    pub sample_heatmap: bool,
+    /// Write render statistics as JSON next to the image, as "<name>_stats.json".
+    pub stats: bool,
 }
 
 /// Contains parameters for how to render the scene.
@@ -33,8 +145,83 @@ pub struct Render {
     /// Maximum number of reflections possible for ray.
     pub max_reflections: u32,
 
-    /// Number of threads to use to render everything.
-    pub threads: usize,
+    /// Number of threads to use to render everything, or `None` to use one per core. See `thread_count`.
+    pub threads: Option<usize>,
+
+    /// Maximum number of samples to average for each pixel. Samples are taken in passes over the whole image.
+    pub samples: u32,
+
+    /// Stop taking samples after this many seconds. 0 means there's no time limit.
+    pub time_limit: f32,
+
+    /// Stop taking samples once the estimated noise (the average standard error of each pixel's brightness)
+    /// drops below this value. 0 means there's no noise threshold.
+    pub noise_threshold: f32,
+
+    /// Stop sampling a pixel once the standard error of its brightness drops below this value.
+    /// 0 means every pixel takes the same number of samples.
+    pub adaptive_threshold: f32,
+
+    /// Number of samples every pixel takes before adaptive sampling starts skipping pixels.
+    pub adaptive_min_samples: u32,
+
+    /// Pattern every random decision is drawn from.
+    pub sampler: SamplerType,
+
+    /// Filter used to turn the samples around each pixel into its final color.
+    pub filter: Filter,
+
+    /// Radius of the filter, in pixels.
+    pub filter_radius: f32,
+
+    /// When the shutter opens (`shutter.0`) and closes (`shutter.1`), in frames after the start of the frame.
+    /// Moving objects are blurred over this interval. Motion blur is disabled if both are the same.
+    pub shutter: (f32, f32),
+
+    /// Number of shadow rays to cast towards each emissive object when lighting a point.
+    pub light_samples: u32,
+
+    /// Number of shadow rays to cast towards the environment map when lighting a point.
+    pub env_samples: u32,
+
+    /// Number of reflection rays averaged over for rough reflections.
+    /// Only reflections seen directly are sampled this many times, deeper ones take a single ray.
+    pub gloss_samples: u32,
+
+    /// Number of rays to cast when estimating how occluded a point is from ambient light.
+    /// Ambient occlusion is disabled if this is 0.
+    pub ao_samples: u32,
+
+    /// Maximum distance at which other objects occlude ambient light.
+    pub ao_distance: f32,
+}
+
+impl Render {
+    /// Returns the number of threads to render with, finding out how many cores there are if `threads` isn't set.
+    pub fn thread_count(&self) -> usize {
+        self.threads.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .expect("could not discover number of threads")
+                .get()
+        })
+    }
+}
+
+/// Describes fog that thickens with distance, and optionally thins out with height.
+#[derive(Clone, Copy, Default)]
+pub struct Fog {
+    /// Amount of fog per unit of distance at `height`. Fog is disabled if this is 0.
+    pub density: f32,
+
+    /// Color things fade into as the fog thickens.
+    pub color: Vector,
+
+    /// Height at which the fog has density `density`.
+    pub height: f32,
+
+    /// How quickly the fog thins out going up, and thickens going down, from `height`.
+    /// 0 gives the same density everywhere.
+    pub falloff: f32,
 }
 
 /// Contains information on scene information.
@@ -43,11 +230,64 @@ pub struct World {
     /// Position of the camera in the scene.
     pub cam_pos: Vector,
 
+    /// Point the camera looks at.
+    pub camera_target: Vector,
+
+    /// Horizontal field of view of the camera, in degrees.
+    pub fov: f32,
+
     /// Background color.
     pub background: Vector,
 
-    /// Where to start fog blending (`fog.0`) and where to end it (`fog.1`)
-    pub fog: (f32, f32),
+    /// Environment map, which replaces the background color and lights the scene if set.
+    pub environment: Option<EnvMap>,
+
+    /// Fog seen along every ray, including reflections and the background.
+    pub fog: Fog,
+
+    /// Volumes of smoke, cloud or haze, which light scatters through.
+    pub volumes: Vec<Volume>,
+
+    /// Names of all objects, in the order they're rendered in.
+    pub object_names: Vec<String>,
+
+    /// Names of all materials, indexed by `Material::id`.
+    pub material_names: Vec<String>,
+}
+
+/// Contains parameters for processing the image after rendering.
+#[derive(Default)]
+pub struct Post {
+    /// Whether to run the denoiser over the final image.
+    pub denoise: bool,
+
+    /// Number of filtering passes the denoiser makes, up to `denoise::MAX_ITERATIONS`. Each pass doubles the distance it blurs over.
+    pub denoise_iterations: u32,
+
+    /// How different two colors can be before the denoiser stops blurring them together.
+    pub denoise_color: f32,
+
+    /// How different two normals can be before the denoiser stops blurring them together.
+    pub denoise_normal: f32,
+
+    /// How different two relative depths can be before the denoiser stops blurring them together.
+    pub denoise_depth: f32,
+
+    /// How different two surface base colors can be before the denoiser stops blurring them together.
+    pub denoise_albedo: f32,
+}
+
+/// Contains the frame range of an animated scene.
+#[derive(Clone, Copy, Debug)]
+pub struct Animation {
+    /// First frame to render.
+    pub start: u32,
+
+    /// Last frame to render, inclusive.
+    pub end: u32,
+
+    /// Frames per second, used to turn keyframe times in seconds into frame numbers.
+    pub fps: f32,
 }
 
 /// Overall struct holding all configuration parameters.
@@ -59,4 +299,26 @@ pub struct Config {
     pub render: Render,
     /// Controls global scene parameters in the render.
     pub world: World,
+    /// Controls processing of the final image.
+    pub post: Post,
+    /// Frame range to render, if the scene is animated.
+    pub animation: Option<Animation>,
+}
+
+impl Config {
+    /// Returns all passes the renderer needs to record: the ones requested for output,
+    /// plus the depth, normal and albedo passes if the denoiser needs them as guides.
+    pub fn passes(&self) -> Vec<Pass> {
+        let mut passes = self.output.passes.clone();
+
+        if self.post.denoise {
+            for p in [Pass::Depth, Pass::Normal, Pass::Albedo] {
+                if !passes.contains(&p) {
+                    passes.push(p);
+                }
+            }
+        }
+
+        passes
+    }
 }
@@ -74,3 +74,43 @@ impl Output for PNG {
             .expect("could not write .png image content");
     }
 }
+
+/// Struct for .pfm output.
+/// Stores a 32 bit float per channel, so it can hold values that don't fit in a regular image (such as depth or normals).
+pub struct PFM {
+    f: BufWriter<std::fs::File>,
+    w: usize,
+    channels: usize,
+}
+
+impl PFM {
+    /// Creates a .pfm image with `channels` channels, which must be either 1 (grayscale) or 3 (RGB).
+    pub fn new(name: String, w: usize, h: usize, channels: usize) -> PFM {
+        let magic = match channels {
+            1 => "Pf",
+            3 => "PF",
+            _ => panic!("cannot represent channel count in .pfm output"),
+        };
+
+        let mut f = BufWriter::new(File::create(name).expect("could not create .pfm image"));
+
+        // a negative scale marks the data as little endian
+        let header = format!("{}\n{} {}\n-1.0\n", magic, w, h);
+        f.write_all(header.as_bytes())
+            .expect("could not write .pfm image header");
+
+        PFM { f, w, channels }
+    }
+}
+
+impl Output for PFM {
+    /// Writes the whole image, given as little endian floats in rows from top to bottom.
+    fn write(&mut self, buf: &[u8]) {
+        // .pfm images store rows from bottom to top
+        for row in buf.chunks(self.w * self.channels * 4).rev() {
+            self.f
+                .write_all(row)
+                .expect("could not write .pfm image content");
+        }
+    }
+}
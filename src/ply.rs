@@ -0,0 +1,289 @@
+//! Reads triangle meshes from PLY files, in ASCII or binary (little or big endian) form.
+//! Vertex positions, normals, colors and texture coordinates are read, and polygons are split into triangles.
+
+use crate::mesh::Triangle;
+use crate::vec::Vector;
+
+/// How the body of the file is written.
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+/// The type of a property value.
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    /// Reads a type name from a PLY header, in either its old ("uchar") or new ("uint8") spelling.
+    fn parse(name: &str) -> Scalar {
+        match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => panic!("unknown PLY property type \"{name}\""),
+        }
+    }
+
+    /// Returns the size of a value in bytes.
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// Returns the largest value of integer types, which colors stored as integers are divided by.
+    fn max(self) -> f64 {
+        match self {
+            Scalar::I8 => 127.0,
+            Scalar::U8 => 255.0,
+            Scalar::I16 => 32767.0,
+            Scalar::U16 => 65535.0,
+            Scalar::I32 => 2147483647.0,
+            Scalar::U32 => 4294967295.0,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+/// A property of an element, which is a list if it has a type for the count of items.
+struct Property {
+    name: String,
+    kind: Scalar,
+    count: Option<Scalar>,
+}
+
+/// A group of items in the file, like the vertices or the faces.
+struct Element {
+    name: String,
+    count: usize,
+    props: Vec<Property>,
+}
+
+/// Reads values one at a time from the body of the file.
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Body<'_> {
+    /// Reads the next value, of type `kind`.
+    fn next(&mut self, kind: Scalar) -> f64 {
+        if self.format == Format::Ascii {
+            while self.bytes.get(self.at).is_some_and(u8::is_ascii_whitespace) {
+                self.at += 1;
+            }
+            let start = self.at;
+            while self
+                .bytes
+                .get(self.at)
+                .is_some_and(|b| !b.is_ascii_whitespace())
+            {
+                self.at += 1;
+            }
+            return std::str::from_utf8(&self.bytes[start..self.at])
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .expect("truncated or invalid PLY file");
+        }
+
+        let n = kind.size();
+        let mut b = [0; 8];
+        b[..n].copy_from_slice(
+            self.bytes
+                .get(self.at..self.at + n)
+                .expect("truncated PLY file"),
+        );
+        if self.format == Format::BigEndian {
+            b[..n].reverse();
+        }
+        self.at += n;
+
+        match kind {
+            Scalar::I8 => b[0] as i8 as f64,
+            Scalar::U8 => b[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(b),
+        }
+    }
+}
+
+/// Reads the PLY file at `path`.
+pub fn load(path: &str) -> Vec<Triangle> {
+    let bytes = std::fs::read(path).unwrap_or_else(|e| panic!("couldn't read {path}: {e}"));
+    parse(&bytes)
+}
+
+/// Reads a PLY file from `bytes`.
+/// Vertices can have normals (`nx`, `ny`, `nz`), colors (`red`, `green`, `blue`)
+/// and texture coordinates (`u` and `v`, or `s` and `t`).
+pub fn parse(bytes: &[u8]) -> Vec<Triangle> {
+    let (format, elements, body) = header(bytes);
+    let mut body = Body {
+        format,
+        bytes: &bytes[body..],
+        at: 0,
+    };
+
+    let mut verts: Vec<Vec<f64>> = vec![];
+    let mut vert_props: &[Property] = &[];
+    let mut faces: Vec<Vec<usize>> = vec![];
+
+    for el in &elements {
+        for _ in 0..el.count {
+            let mut values = vec![];
+            for p in &el.props {
+                match p.count {
+                    Some(count) => {
+                        let n = body.next(count) as usize;
+                        let items: Vec<usize> =
+                            (0..n).map(|_| body.next(p.kind) as usize).collect();
+                        if el.name == "face"
+                            && (p.name == "vertex_indices" || p.name == "vertex_index")
+                        {
+                            faces.push(items);
+                        }
+                    }
+                    None => values.push(body.next(p.kind)),
+                }
+            }
+            if el.name == "vertex" {
+                verts.push(values);
+            }
+        }
+        if el.name == "vertex" {
+            vert_props = &el.props;
+        }
+    }
+
+    // find where each vertex property ended up, skipping lists since they're not kept
+    let scalars: Vec<&Property> = vert_props.iter().filter(|p| p.count.is_none()).collect();
+    let find = |names: &[&str]| -> Option<(usize, Scalar)> {
+        names.iter().find_map(|n| {
+            let i = scalars.iter().position(|p| p.name == *n)?;
+            Some((i, scalars[i].kind))
+        })
+    };
+    let get3 = |names: [&[&str]; 3]| -> Option<[(usize, Scalar); 3]> {
+        Some([find(names[0])?, find(names[1])?, find(names[2])?])
+    };
+
+    let pos = get3([&["x"], &["y"], &["z"]]).expect("PLY vertices have no positions");
+    let normal = get3([&["nx"], &["ny"], &["nz"]]);
+    let color = get3([
+        &["red", "r", "diffuse_red"],
+        &["green", "g", "diffuse_green"],
+        &["blue", "b", "diffuse_blue"],
+    ]);
+    let uv = find(&["u", "s", "texture_u"]).zip(find(&["v", "t", "texture_v"]));
+
+    let vec3 = |v: &[f64], idx: [(usize, Scalar); 3], scale: bool| {
+        let c = |(i, kind): (usize, Scalar)| (v[i] / if scale { kind.max() } else { 1.0 }) as f32;
+        Vector::from_3(c(idx[0]), c(idx[1]), c(idx[2]))
+    };
+
+    // polygons are split into fans of triangles around their first corner
+    let mut tris = vec![];
+    for f in &faces {
+        if f.iter().any(|&i| i >= verts.len()) {
+            panic!("PLY face uses a vertex that doesn't exist");
+        }
+        for k in 2..f.len() {
+            let c = [f[0], f[k - 1], f[k]];
+            let v = c.map(|i| verts[i].as_slice());
+            tris.push(Triangle {
+                p: v.map(|v| vec3(v, pos, false)),
+                n: normal.map(|n| v.map(|v| vec3(v, n, false).norm())),
+                // v runs up in PLY files but down in textures
+                uv: uv.map(|((u, _), (t, _))| v.map(|v| (v[u] as f32, 1.0 - v[t] as f32))),
+                color: color.map(|col| v.map(|v| vec3(v, col, true))),
+            });
+        }
+    }
+    tris
+}
+
+/// Reads the header of a PLY file, returning how the body is written,
+/// the elements it holds and where the body starts.
+fn header(bytes: &[u8]) -> (Format, Vec<Element>, usize) {
+    let end = bytes
+        .windows(10)
+        .position(|w| w == b"end_header")
+        .expect("not a PLY file, no end_header found");
+    let body = bytes[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |i| end + i + 1);
+    let text = String::from_utf8_lossy(&bytes[..end]);
+
+    let mut lines = text.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        panic!("not a PLY file, it doesn't start with \"ply\"");
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", f, ..] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => panic!("unknown PLY format \"{f}\""),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().expect("invalid PLY element count"),
+                props: vec![],
+            }),
+            ["property", "list", count, kind, name] => elements
+                .last_mut()
+                .expect("PLY property outside an element")
+                .props
+                .push(Property {
+                    name: name.to_string(),
+                    kind: Scalar::parse(kind),
+                    count: Some(Scalar::parse(count)),
+                }),
+            ["property", kind, name] => elements
+                .last_mut()
+                .expect("PLY property outside an element")
+                .props
+                .push(Property {
+                    name: name.to_string(),
+                    kind: Scalar::parse(kind),
+                    count: None,
+                }),
+            _ => {} // comments, obj_info and blank lines
+        }
+    }
+
+    (format.expect("PLY file has no format line"), elements, body)
+}
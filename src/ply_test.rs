@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use crate::ply::*;
+    use crate::vec::*;
+
+    const HEADER: &str = "ply
+format {} 1.0
+comment a square with a red and a blue side
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+property float u
+property float v
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    fn check(tris: &[crate::mesh::Triangle]) {
+        // the square is split into two triangles around its first corner
+        assert_eq!(tris.len(), 2);
+        assert_eq!(tris[0].p[0], Vector::from_3(0.0, 0.0, 0.0));
+        assert_eq!(tris[0].p[2], Vector::from_3(1.0, 1.0, 0.0));
+        assert_eq!(tris[1].p[1], Vector::from_3(1.0, 1.0, 0.0));
+        assert_eq!(tris[1].p[2], Vector::from_3(0.0, 1.0, 0.0));
+        assert!(tris[0].n.is_none());
+
+        let colors = tris[0].color.unwrap();
+        assert_eq!(colors[0], Vector::from_3(1.0, 0.0, 0.0));
+        assert_eq!(colors[2], Vector::from_3(0.0, 0.0, 1.0));
+
+        // v is flipped, since it runs down in textures
+        let uv = tris[1].uv.unwrap();
+        assert_eq!(uv[2], (0.0, 0.0));
+        assert_eq!(uv[0], (0.0, 1.0));
+    }
+
+    #[test]
+    fn ascii_test() {
+        let body = "0 0 0 255 0 0 0 0
+1 0 0 255 0 0 1 0
+1 1 0 0 0 255 1 1
+0 1 0 0 0 255 0 1
+4 0 1 2 3
+";
+        let text = HEADER.replace("{}", "ascii") + body;
+        check(&parse(text.as_bytes()));
+    }
+
+    #[test]
+    fn binary_test() {
+        let verts: [([f32; 3], [u8; 3], [f32; 2]); 4] = [
+            ([0.0, 0.0, 0.0], [255, 0, 0], [0.0, 0.0]),
+            ([1.0, 0.0, 0.0], [255, 0, 0], [1.0, 0.0]),
+            ([1.0, 1.0, 0.0], [0, 0, 255], [1.0, 1.0]),
+            ([0.0, 1.0, 0.0], [0, 0, 255], [0.0, 1.0]),
+        ];
+
+        for (format, big) in [("binary_little_endian", false), ("binary_big_endian", true)] {
+            let f = |x: f32| {
+                if big {
+                    x.to_be_bytes()
+                } else {
+                    x.to_le_bytes()
+                }
+            };
+            let i = |x: i32| {
+                if big {
+                    x.to_be_bytes()
+                } else {
+                    x.to_le_bytes()
+                }
+            };
+
+            let mut bytes = HEADER.replace("{}", format).into_bytes();
+            for (p, c, uv) in verts {
+                p.iter().for_each(|x| bytes.extend(f(*x)));
+                bytes.extend(c);
+                uv.iter().for_each(|x| bytes.extend(f(*x)));
+            }
+            bytes.push(4);
+            (0..4).for_each(|x| bytes.extend(i(x)));
+
+            check(&parse(&bytes));
+        }
+    }
+}
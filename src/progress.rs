@@ -0,0 +1,110 @@
+//! Tracks how far along a render is, so it can be reported while the render runs.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counters shared between the render threads and whatever reports on them.
+/// Updates are atomic, so any number of threads can add to them at once.
+pub struct Progress {
+    /// Number of pixels finished so far, over all passes, including pixels that were skipped.
+    pixels: AtomicU64,
+    /// Number of rays cast so far, of every kind counted in `stats`.
+    rays: AtomicU64,
+    /// Number of passes (samples per pixel) finished so far.
+    passes: AtomicU32,
+    /// Number of pixels finished in a full render.
+    total: u64,
+}
+
+/// A snapshot of a render's progress.
+pub struct Report {
+    /// Fraction of the render that's done, from 0 to 1.
+    pub done: f32,
+    /// Number of passes (samples per pixel) finished.
+    pub passes: u32,
+    /// Time since the render started.
+    pub elapsed: Duration,
+    /// Rays cast per second: camera, shadow, reflection, glossy and occlusion rays, and shadow rays from inside volumes.
+    pub rays_per_sec: f64,
+    /// Estimated time until the render finishes.
+    pub eta: Duration,
+}
+
+impl Progress {
+    /// Returns a new tracker for a render that finishes `total` pixels in all.
+    pub fn new(total: u64) -> Progress {
+        Progress {
+            pixels: AtomicU64::new(0),
+            rays: AtomicU64::new(0),
+            passes: AtomicU32::new(0),
+            total,
+        }
+    }
+
+    /// Records that `n` more pixels are finished, casting `rays` rays between them.
+    pub fn add_pixels(&self, n: u64, rays: u64) {
+        self.pixels.fetch_add(n, Ordering::Relaxed);
+        self.rays.fetch_add(rays, Ordering::Relaxed);
+    }
+
+    /// Records that another pass over the whole image has finished.
+    pub fn add_pass(&self) {
+        self.passes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Summarizes progress, given that the render has been running for `elapsed`.
+    /// The estimated time left never goes past `limit` if one is given.
+    pub fn report(&self, elapsed: Duration, limit: Option<Duration>) -> Report {
+        let pixels = self.pixels.load(Ordering::Relaxed);
+        let done = (pixels as f64 / self.total.max(1) as f64).min(1.0);
+
+        let secs = elapsed.as_secs_f64();
+        let rays = self.rays.load(Ordering::Relaxed);
+        let rays_per_sec = if secs > 0.0 { rays as f64 / secs } else { 0.0 };
+
+        // assume the rest of the render goes as fast as the part that's done
+        let mut eta = if done > 0.0 {
+            Duration::from_secs_f64(secs * (1.0 - done) / done)
+        } else {
+            Duration::ZERO
+        };
+
+        if let Some(limit) = limit {
+            eta = eta.min(limit.saturating_sub(elapsed));
+        }
+
+        Report {
+            done: done as f32,
+            passes: self.passes.load(Ordering::Relaxed),
+            elapsed,
+            rays_per_sec,
+            eta,
+        }
+    }
+}
+
+impl Report {
+    /// Formats the report as a single human readable line.
+    pub fn line(&self) -> String {
+        format!(
+            "{:5.1}% | {} pass(es) | {:.2} Mrays/s | {:.1}s elapsed | ETA {:.1}s",
+            self.done * 100.0,
+            self.passes,
+            self.rays_per_sec / 1e6,
+            self.elapsed.as_secs_f32(),
+            self.eta.as_secs_f32()
+        )
+    }
+
+    /// Formats the report as a single line JSON object.
+    pub fn json(&self) -> String {
+        format!(
+            "{{\"percent\": {:.2}, \"passes\": {}, \"rays_per_sec\": {:.0}, \"elapsed\": {:.3}, \"eta\": {:.3}}}",
+            self.done * 100.0,
+            self.passes,
+            self.rays_per_sec,
+            self.elapsed.as_secs_f32(),
+            self.eta.as_secs_f32()
+        )
+    }
+}
@@ -0,0 +1,22 @@
+#[cfg(test)]
+mod tests {
+    use crate::progress::*;
+    use std::time::Duration;
+
+    #[test]
+    fn report_test() {
+        let p = Progress::new(400);
+        p.add_pixels(100, 60);
+        p.add_pass();
+
+        let r = p.report(Duration::from_secs(2), None);
+        assert_eq!(r.done, 0.25);
+        assert_eq!(r.passes, 1);
+        assert_eq!(r.rays_per_sec, 30.0);
+        assert_eq!(r.eta, Duration::from_secs(6));
+
+        // a time limit caps the estimate
+        let r = p.report(Duration::from_secs(2), Some(Duration::from_secs(5)));
+        assert_eq!(r.eta, Duration::from_secs(3));
+    }
+}
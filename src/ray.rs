@@ -1,5 +1,8 @@
 //! Controls how rays interact with shapes.
 
+use crate::bsdf::Model;
+use crate::mesh::Mesh;
+use crate::sdf::Sdf;
 use crate::vec::*;
 
 /// Defines how a light can behave.
@@ -25,6 +28,9 @@ pub struct Ray {
     pub o: Vector,
     /// The direction.
     pub d: Vector,
+    /// When the ray was cast, as a fraction of the way through the shutter interval from 0 to 1.
+    /// Moving objects are intersected at wherever they are at this time.
+    pub time: f32,
 }
 
 /// Defines how a ray can intersect with things.
@@ -40,8 +46,12 @@ pub enum HitType {
 #[derive(Copy, Clone)]
 pub struct Material {
     pub color: Vector,
-    pub spec: f32, // specular exponent, set to -1 for no specular highlights
-    pub refl: f32, // reflectivity from 0 to 1
+    pub bsdf: Model,          // how the surface reflects light
+    pub refl: f32,            // reflectivity from 0 to 1
+    pub roughness: f32,       // how blurry reflections are, from 0 (mirror) to 1
+    pub emission: Vector,     // light given off by the surface, zero for surfaces that don't glow
+    pub transmission: Vector, // fraction of light let through the surface for each channel, zero for opaque surfaces
+    pub id: usize, // index of the material in the scene, used to tell materials apart in output passes
 }
 
 /// Defines behavior needed to interact with traced rays.
@@ -49,12 +59,40 @@ pub trait RayInteraction {
     /// Checks for a hit from ray `r` over time range `t`.
     fn hit(&self, r: &Ray, t: (f32, f32)) -> HitType;
 
-    /// Calculates a normal at point `p` on the object surface.
-    fn normal(&self, p: &Vector) -> Vector;
+    /// Calculates a normal at point `p` on the object surface, with the object placed where it is at `time`.
+    fn normal(&self, p: &Vector, time: f32) -> Vector;
 
-    /// Calculates the color at point `p` on surface.
+    /// Calculates the color at point `p` on surface, with the object placed where it is at `time`.
     /// Constant unless the material is defined procedurally.
-    fn material(&self, p: &Vector) -> Material;
+    fn material(&self, p: &Vector, time: f32) -> Material;
+
+    /// Returns the material of the object as it's defined, before any textures are applied to it.
+    fn base_material(&self) -> Material {
+        self.material(&Vector::zero(3), 0.0)
+    }
+
+    /// Picks a point on the object surface from `u`, a pair of uniformly distributed numbers in [0, 1),
+    /// with the object placed where it is at `time`.
+    /// Returns the point, the normal at that point and the total surface area of the object.
+    /// Objects that can't be sampled (such as infinite planes) return `None` and can't act as light sources.
+    fn sample(&self, _u: (f32, f32), _time: f32) -> Option<(Vector, Vector, f32)> {
+        None
+    }
+
+    /// Returns the name of the type of object, used to group render statistics.
+    fn name(&self) -> &'static str;
+
+    /// Returns which kind of object this is, holding the object itself, for code that needs to know exactly what it is,
+    /// like the scene writer.
+    fn describe(&self) -> Object<'_>;
+}
+
+/// The kinds of object a scene can hold, each holding an object of that kind.
+pub enum Object<'a> {
+    Plane(&'a Plane),
+    Sphere(&'a Sphere),
+    Sdf(&'a Sdf),
+    Mesh(&'a Mesh),
 }
 
 /// Defines an infinite plane with a given normal.
@@ -65,12 +103,15 @@ pub struct Plane {
     pub n: Vector,
     /// The material of the plane.
     pub mat: Material,
+    /// How far the plane moves while the shutter is open.
+    pub motion: Vector,
 }
 
 impl RayInteraction for Plane {
     fn hit(&self, r: &Ray, _t: (f32, f32)) -> HitType {
         if self.n.dot(-r.d) >= 0.0 {
-            let t = self.n.dot(r.o - self.p) / self.n.dot(-r.d);
+            let p = self.p + self.motion * Vector::from_s(r.time, 3);
+            let t = self.n.dot(r.o - p) / self.n.dot(-r.d);
 
             if _t.0 < t && t < _t.1 {
                 return HitType::Hit(t);
@@ -81,13 +122,21 @@ impl RayInteraction for Plane {
         HitType::Miss()
     }
 
-    fn normal(&self, _p: &Vector) -> Vector {
+    fn normal(&self, _p: &Vector, _time: f32) -> Vector {
         self.n
     }
 
-    fn material(&self, _p: &Vector) -> Material {
+    fn material(&self, _p: &Vector, _time: f32) -> Material {
         self.mat
     }
+
+    fn name(&self) -> &'static str {
+        "plane"
+    }
+
+    fn describe(&self) -> Object<'_> {
+        Object::Plane(self)
+    }
 }
 
 /// Defines a sphere.
@@ -98,13 +147,22 @@ pub struct Sphere {
     pub r: f32,
     /// The material of the sphere.
     pub mat: Material,
+    /// How far the sphere moves while the shutter is open.
+    pub motion: Vector,
+}
+
+impl Sphere {
+    /// Returns the center of the sphere at `time`.
+    fn center(&self, time: f32) -> Vector {
+        self.c + self.motion * Vector::from_s(time, 3)
+    }
 }
 
 impl RayInteraction for Sphere {
     /// Checks if ray `r` hits sphere `s` at time `t`.
     fn hit(&self, r: &Ray, t: (f32, f32)) -> HitType {
         let a = r.d.dot(r.d);
-        let oc = r.o - self.c;
+        let oc = r.o - self.center(r.time);
         let b = 2.0 * oc.dot(r.d);
 
         let c = oc.dot(oc) - self.r * self.r;
@@ -140,11 +198,32 @@ impl RayInteraction for Sphere {
         }
     }
 
-    fn normal(&self, p: &Vector) -> Vector {
-        (*p - self.c).norm()
+    fn normal(&self, p: &Vector, time: f32) -> Vector {
+        (*p - self.center(time)).norm()
     }
 
-    fn material(&self, _p: &Vector) -> Material {
+    fn material(&self, _p: &Vector, _time: f32) -> Material {
         self.mat
     }
+
+    fn sample(&self, u: (f32, f32), time: f32) -> Option<(Vector, Vector, f32)> {
+        // uniformly distributed point on a unit sphere
+        let z = 1.0 - 2.0 * u.0;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let (sin, cos) = (2.0 * std::f32::consts::PI * u.1).sin_cos();
+        let n = Vector::from_3(r * cos, r * sin, z);
+
+        let p = self.center(time) + Vector::from_s(self.r, 3) * n;
+        let area = 4.0 * std::f32::consts::PI * self.r * self.r;
+
+        Some((p, n, area))
+    }
+
+    fn name(&self) -> &'static str {
+        "sphere"
+    }
+
+    fn describe(&self) -> Object<'_> {
+        Object::Sphere(self)
+    }
 }
@@ -1,11 +1,23 @@
 //! Contains overall render logic.
 
+use crate::bsdf::Bsdf;
 use crate::draw::*;
+use crate::fog;
 use crate::mat::*;
+use crate::medium;
 use crate::opts::*;
+use crate::progress::*;
 use crate::ray::*;
+use crate::rng::*;
+use crate::sampler::*;
+use crate::stats::{self, Stats};
 use crate::vec::*;
 
+use std::f32::consts::PI;
+
+/// Maximum number of times a shadow ray is followed through the surface of a single transparent object.
+const MAX_CROSSINGS: u32 = 64;
+
 /// Iterates through all objects in objs and return the index of the _closest_ object and the hit point.
 /// Returns `None` if nothing hits.
 pub fn closest_hit(
@@ -17,6 +29,7 @@ pub fn closest_hit(
     let mut best = None;
 
     for (i, obj) in objs.iter().enumerate() {
+        stats::record(|s| s.add_test(obj.name()));
         if let HitType::Hit(t) = obj.hit(r, lim) {
             let p = r.o + Vector::from_s(t, 3) * r.d;
             if t < best_t {
@@ -37,6 +50,7 @@ pub fn any_hit(
     lim: (f32, f32),
 ) -> Option<(usize, Vector)> {
     for (i, obj) in objs.iter().enumerate() {
+        stats::record(|s| s.add_test(obj.name()));
         if let HitType::Hit(t) = obj.hit(r, lim) {
             let p = r.o + Vector::from_s(t, 3) * r.d;
             return Some((i, p));
@@ -46,22 +60,101 @@ pub fn any_hit(
     None
 }
 
-/// Runs lighting calculations at point `p` for the object at index `i`.
-/// `num_refl` determines the maximum recursion depth reflections.
+/// Tracks how many more reflections lighting calculations can follow, and how rough reflections are sampled.
+pub struct Bounce<'a, 's> {
+    /// Number of reflections left to follow.
+    pub left: u32,
+    /// Number of reflections already followed.
+    pub depth: usize,
+    /// Number of rays averaged over for rough reflections at this depth.
+    pub samples: u32,
+    /// Draws the directions of rough reflections.
+    pub sampler: &'a mut PixelSampler<'s>,
+}
+
+/// Returns the direction light arriving from `lv` is reflected in by a surface with normal `n` and the given roughness, picked with `u`.
+/// Smooth surfaces reflect like a mirror, and rougher ones spread reflections over a wider lobe around the mirror direction.
+fn glossy(lv: Vector, n: Vector, roughness: f32, u: (f32, f32)) -> Vector {
+    let mirror = Vector::refl(lv, n);
+    if roughness <= 0.0 {
+        return mirror;
+    }
+
+    // roughly match the width of the highlights of a ggx surface with the same roughness
+    let a = roughness * roughness;
+    let d = lobe(mirror.norm(), (2.0 / (a * a) - 2.0).max(0.0), u);
+
+    // flip directions that end up going into the surface back out of it
+    let c = d.dot(n);
+    match c < 0.0 {
+        true => d - n * Vector::from_s(2.0 * c, 3),
+        false => d,
+    }
+}
+
+/// Returns the fraction of light that makes it along ray `r` through the objects in `objs` over `lim`, for each color channel.
+/// Opaque objects block light outright, while transparent ones dim and tint it every time the ray crosses their surface.
+pub fn shadow(r: &Ray, objs: &[Box<dyn RayInteraction + Send + Sync>], lim: (f32, f32)) -> Vector {
+    // most scenes are entirely opaque, so settle the common cases with a single test
+    match any_hit(r, objs, lim) {
+        None => return Vector::from_s(1.0, 3),
+        Some((i, p)) if objs[i].material(&p, r.time).transmission == Vector::zero(3) => {
+            return Vector::zero(3)
+        }
+        _ => {}
+    }
+
+    // the order light passes through surfaces doesn't matter, so go through every crossing of each object in turn
+    let mut tr = Vector::from_s(1.0, 3);
+    let eps = 1e-3 / r.d.dot(r.d).sqrt();
+
+    for obj in objs {
+        let mut start = lim.0;
+
+        for _ in 0..MAX_CROSSINGS {
+            stats::record(|s| s.add_test(obj.name()));
+            let t = match obj.hit(r, (start, lim.1)) {
+                HitType::Hit(t) => t,
+                HitType::Miss() => break,
+            };
+
+            let p = r.o + Vector::from_s(t, 3) * r.d;
+            let m = obj.material(&p, r.time);
+            if m.transmission == Vector::zero(3) {
+                return Vector::zero(3);
+            }
+
+            tr = tr * m.transmission;
+            start = t + eps;
+        }
+    }
+
+    tr
+}
+
+/// Runs lighting calculations at point `p` for the object at index `i`, which was seen by ray `r`.
+/// Every object is placed where it is at the time of `r`, and light is reflected back along `r` by the object's bsdf.
+/// `bounce` determines how many reflections are followed and how rough ones are sampled. Light and reflections pass through the fog and volumes in `world`.
 pub fn light(
     idx: usize,
     set: &[Box<dyn RayInteraction + Send + Sync>],
+    r: &Ray,
     p: &Vector,
     l: &Light,
-    num_refl: u32,
+    world: &World,
+    bounce: &mut Bounce,
 ) -> Vector {
-    let lc = l.color;
+    let time = r.time;
+    let mut lc = l.color;
+
+    let obj = &set[idx];
+    let m = obj.material(p, time);
 
     // calculate vector going _to_ the light source
     let lv = match &l.kind {
         LightType::Point(lp) => *lp - *p,
         LightType::Directional(ldir) => *ldir,
-        LightType::Ambient => return lc,
+        LightType::Ambient => return m.color * lc, // ambient light is only reflected diffusely
     };
 
     let max = match l.kind {
@@ -69,96 +162,447 @@ pub fn light(
         _ => 0.99, // don't test for shadows beyond the light origin for point lights
     };
 
-    let obj = &set[idx];
-
-    let m = obj.material(p);
-    let mut color = m.color;
-
-    // if the ray going to the light hits another object, point p is in shadow
+    // if the ray going to the light hits another object, point p is in shadow,
+    // unless the object lets some light through
     // avoid edge case where object hits itself by using a small offset from 0 for t
-    if let Some((_, _)) = any_hit(&Ray { o: *p, d: lv }, set, (0.01, max)) {
+    stats::record(|s| s.shadow_rays += 1);
+    let tr = shadow(&Ray { o: *p, d: lv, time }, set, (0.01, max));
+    if tr == Vector::zero(3) {
         return Vector::zero(3); // no light contribution if in shadow
     }
+    lc = lc * tr;
+
+    // volumes in the way dim and tint the light instead of blocking it outright
+    if !world.volumes.is_empty() {
+        let dist = match l.kind {
+            LightType::Directional(_) => f32::INFINITY,
+            _ => lv.dot(lv).sqrt(),
+        };
+        lc = lc * medium::transmittance(&world.volumes, *p, lv.norm(), dist);
+    }
 
-    let i = lv.norm();
-    let n = obj.normal(p);
-
-    let diff = n.dot(i).max(0.0);
-    let diff_v = Vector::from_s(diff, 3);
-    color = diff_v * color * l.color;
+    let n = obj.normal(p, time);
+
+    // the bsdf decides how much of the light is reflected back towards whatever is looking at p
+    let mut color = m.bsdf.eval(n, lv.norm(), -r.d.norm(), m.color) * lc;
+
+    if bounce.left > 0 && m.refl > 0.01 {
+        // mirrors only need one ray, rough surfaces average over several spread around the mirror direction
+        let dirs: Vec<Vector> = match m.roughness > 0.0 {
+            true => bounce
+                .sampler
+                .next_2d_array(bounce.samples)
+                .into_iter()
+                .map(|u| glossy(lv, n, m.roughness, u))
+                .collect(),
+            false => vec![glossy(lv, n, 0.0, (0.0, 0.0))],
+        };
+
+        let mut ref_sum = Vector::zero(3);
+        let mut hits = 0;
+
+        for d in &dirs {
+            let ref_ray = Ray { o: *p, d: *d, time };
+
+            // if object is reflective and we can recurse more, calculate lighting on reflection
+            stats::record(|s| s.add_reflection(bounce.depth));
+            if let Some((i2, p2)) = closest_hit(&ref_ray, set, (0.01, f32::INFINITY)) {
+                let ref_l = Light {
+                    color,
+                    kind: LightType::Point(*p),
+                };
+
+                let ref_color = light(
+                    i2,
+                    set,
+                    &ref_ray,
+                    &p2,
+                    &ref_l,
+                    world,
+                    &mut Bounce {
+                        left: bounce.left - 1,
+                        depth: bounce.depth + 1,
+                        samples: 1,
+                        sampler: bounce.sampler,
+                    },
+                );
+                let dist = (p2 - *p).dot(p2 - *p).sqrt();
+                let ref_color =
+                    ref_color * medium::transmittance(&world.volumes, *p, d.norm(), dist);
+                ref_sum = ref_sum + fog::apply(&world.fog, ref_color, *p, d.norm(), dist);
+                hits += 1;
+            }
+        }
 
-    let r = Vector::refl(lv, n); // calculate reflected vector off normal
-    let np = -*p; // negative p, or a vector to the camera
-    let spec_dot = r.norm().dot(np.norm());
-    if m.spec > 0.0 && spec_dot > 0.0 {
-        // diff * color + spec
-        let spec_v = Vector::from_s(spec_dot.powf(m.spec), 3);
-        let specc = spec_v * lc; // specular color depends on light source
-        color = color + specc;
+        // if we reflect off the object and hit something, compute the light for that:
+        // color = color * (1 - refl) + ref_color * surface color * refl
+        // where rough reflections only mix in as much as the share of rays that hit something
+        if hits > 0 {
+            let n = dirs.len() as f32;
+            color = ref_sum * m.color * Vector::from_s(m.refl / n, 3)
+                + color * Vector::from_s(1.0 - m.refl * hits as f32 / n, 3);
+        }
     }
 
-    color.clamp(0.0, 1.0); // clamp color to proper range
+    color
+}
 
-    if num_refl > 0 && m.refl > 0.01 {
-        let r = Vector::refl(lv, obj.normal(p));
-        let ref_ray = Ray { o: *p, d: r };
+/// Runs lighting calculations at point `p` for the object at index `idx`, seen by ray `r`, using the emissive objects at indices `emitters` as light sources.
+/// Each emitter is sampled `cfg.render.light_samples` times, so partially hidden emitters cast soft shadows.
+pub fn emitted_light(
+    idx: usize,
+    set: &[Box<dyn RayInteraction + Send + Sync>],
+    r: &Ray,
+    p: &Vector,
+    emitters: &[usize],
+    cfg: &Config,
+    sampler: &mut PixelSampler,
+) -> Vector {
+    let samples = cfg.render.light_samples;
+    let mut color = Vector::zero(3);
+
+    for &e in emitters {
+        // draw points even for skipped emitters, so every emitter always gets the same dimension
+        let points = sampler.next_2d_array(samples);
+
+        if e == idx {
+            continue; // surfaces don't light themselves
+        }
 
-        // if object is reflective and we can recurse more, calculate lighting on reflection
-        if let Some((i2, p2)) = closest_hit(&ref_ray, set, (0.01, f32::INFINITY)) {
-            let ref_l = Light {
-                color,
-                kind: LightType::Point(*p),
+        for u in points {
+            let (q, nq, area) = match set[e].sample(u, r.time) {
+                Some(s) => s,
+                None => continue,
             };
 
-            let ref_color = light(i2, set, &p2, &ref_l, num_refl - 1);
+            // skip points on the far side of the emitter, which face away from p
+            let d = *p - q;
+            let cos_e = nq.dot(d.norm());
+            if cos_e <= 0.0 {
+                continue;
+            }
+
+            // treat the sampled patch of surface as a point light with the patch's share of the emitted power
+            let g = cos_e * area / (PI * d.dot(d) * samples as f32);
+            let l = Light {
+                color: set[e].material(&q, r.time).emission * Vector::from_s(g, 3),
+                kind: LightType::Point(q),
+            };
+
+            color = color
+                + light(
+                    idx,
+                    set,
+                    r,
+                    p,
+                    &l,
+                    &cfg.world,
+                    &mut Bounce {
+                        left: 0,
+                        depth: 0,
+                        samples: 1,
+                        sampler,
+                    },
+                );
+        }
+    }
+
+    color
+}
+
+/// Runs lighting calculations at point `p` for the object at index `idx`, seen by ray `r`, using the environment map of `cfg` as a light source.
+/// Directions are picked `cfg.render.env_samples` times, favoring the brightest parts of the map.
+pub fn env_light(
+    idx: usize,
+    set: &[Box<dyn RayInteraction + Send + Sync>],
+    r: &Ray,
+    p: &Vector,
+    cfg: &Config,
+    sampler: &mut PixelSampler,
+) -> Vector {
+    let samples = cfg.render.env_samples;
+    let mut color = Vector::zero(3);
+    let env = match &cfg.world.environment {
+        Some(env) => env,
+        None => return color,
+    };
+
+    for u in sampler.next_2d_array(samples) {
+        if let Some((d, c, pdf)) = env.sample(u) {
+            // treat each sampled direction as a directional light carrying its share of the incoming light
+            let l = Light {
+                color: c * Vector::from_s(1.0 / (PI * pdf * samples as f32), 3),
+                kind: LightType::Directional(d),
+            };
 
-            // if we reflect off the object and hit something, compute the light for that:
-            // color = color * (1 - refl) + ref_color * refl
-            color = ref_color * Vector::from_s(m.refl, 3) + color * Vector::from_s(1.0 - m.refl, 3);
+            color = color
+                + light(
+                    idx,
+                    set,
+                    r,
+                    p,
+                    &l,
+                    &cfg.world,
+                    &mut Bounce {
+                        left: 0,
+                        depth: 0,
+                        samples: 1,
+                        sampler,
+                    },
+                );
         }
     }
 
     color
 }
 
-/// Renders a scene containing objects in `objs`, lights in `lights`, and configuration information in `cfg`.
-/// Returns a Matrix of colors representing RGB values of the final image.
+/// Estimates how much ambient light reaches point `p` on the object at index `idx` by casting `samples` rays
+/// over the hemisphere around its normal. Objects further away than `dist` don't block ambient light.
+/// Returns the fraction of rays that escape, from 0 (fully occluded) to 1 (fully visible).
+pub fn occlusion(
+    idx: usize,
+    set: &[Box<dyn RayInteraction + Send + Sync>],
+    p: &Vector,
+    time: f32,
+    samples: u32,
+    dist: f32,
+    sampler: &mut PixelSampler,
+) -> f32 {
+    let n = set[idx].normal(p, time);
+    let mut open = 0;
+
+    for u in sampler.next_2d_array(samples) {
+        let d = hemisphere(n, u);
+
+        // directions are unit length, so t is measured in world units
+        stats::record(|s| s.occlusion_rays += 1);
+        if any_hit(&Ray { o: *p, d, time }, set, (0.01, dist)).is_none() {
+            open += 1;
+        }
+    }
+
+    open as f32 / samples as f32
+}
+
+/// Returns the light from each of `lights` arriving at point `p` inside a volume, as pairs of the direction towards the light
+/// (`None` for ambient light, which arrives from everywhere) and its color after passing through the volumes in `world`.
+pub fn volume_light(
+    set: &[Box<dyn RayInteraction + Send + Sync>],
+    lights: &[Light],
+    world: &World,
+    p: Vector,
+    time: f32,
+) -> Vec<(Option<Vector>, Vector)> {
+    lights
+        .iter()
+        .filter_map(|l| {
+            let (lv, max, dist) = match &l.kind {
+                LightType::Ambient => return Some((None, l.color)),
+                LightType::Point(lp) => (*lp - p, 0.99, (*lp - p).dot(*lp - p).sqrt()),
+                LightType::Directional(ldir) => (*ldir, f32::INFINITY, f32::INFINITY),
+            };
+
+            stats::record(|s| s.shadow_rays += 1);
+            let tr = shadow(&Ray { o: p, d: lv, time }, set, (0.0, max));
+            if tr == Vector::zero(3) {
+                return None;
+            }
+
+            // surfaces are lit without dividing by π, so scale up to match the phase function, which integrates to 1
+            let tr = tr * medium::transmittance(&world.volumes, p, lv.norm(), dist);
+            Some((Some(lv.norm()), l.color * tr * Vector::from_s(PI, 3)))
+        })
+        .collect()
+}
+
+/// Returns the color seen by a ray going in direction `d` that doesn't hit anything.
+pub fn background(d: Vector, cfg: &Config) -> Vector {
+    match &cfg.world.environment {
+        Some(env) => env.lookup(d),
+        None => cfg.world.background,
+    }
+}
+
+/// Returns the right, up and forward directions of a camera at `pos` looking at `target`.
+/// The camera is kept level, so its right direction always lies flat.
+pub fn camera_basis(pos: Vector, target: Vector) -> (Vector, Vector, Vector) {
+    let fwd = (target - pos).norm();
+
+    // looking straight up or down leaves no way to tell which way is level, so fall back to the z axis as up
+    let mut up = Vector::from_3(0.0, 1.0, 0.0);
+    if up.cross(fwd).dot(up.cross(fwd)) < 1e-8 {
+        up = Vector::from_3(0.0, 0.0, 1.0);
+    }
+
+    let right = up.cross(fwd).norm();
+    (right, fwd.cross(right), fwd)
+}
+
+/// Returns the width and height of the viewport a distance `dist` in front of the camera,
+/// for a horizontal field of view of `fov` degrees and an image `dims` pixels in size.
+/// The viewport has the same shape as the image, so pixels stay square at any field of view.
+pub fn viewport(fov: f32, dist: f32, dims: (usize, usize)) -> (f32, f32) {
+    let width = (fov as f64 / 2.0).to_radians().tan() as f32 * 2.0 * dist;
+    (width, width * dims.1 as f32 / dims.0 as f32)
+}
+
+/// Holds the images produced by rendering part of the scene.
+pub struct Frame {
+    /// The final image, with each channel in the range [0, 1].
+    pub color: Matrix<Vector>,
+    /// Extra passes, in the same order as `cfg.passes()`.
+    pub passes: Vec<Matrix<Vector>>,
+    /// Where within each pixel the sample was taken, in canvas coordinates relative to the pixel center.
+    pub offsets: Matrix<(f32, f32)>,
+    /// Counters for the work done rendering this part.
+    pub stats: Stats,
+}
+
+/// Describes the first surface seen through a pixel, which is what output passes record.
+pub struct Hit {
+    /// Index of the object hit.
+    pub idx: usize,
+    /// Distance from the camera to the hit.
+    pub dist: f32,
+    /// Surface normal at the hit.
+    pub normal: Vector,
+    /// Material at the hit.
+    pub mat: Material,
+    /// Fraction of ambient light reaching the hit.
+    pub ao: f32,
+}
+
+/// Returns the value `pass` records for a pixel whose first hit is `hit`, or for empty space if `hit` is `None`.
+/// Single channel passes repeat their value across all channels.
+pub fn pass_value(pass: Pass, hit: Option<&Hit>) -> Vector {
+    match (pass, hit) {
+        (Pass::Depth, Some(h)) => Vector::from_s(h.dist, 3),
+        (Pass::Depth, None) => Vector::from_s(f32::INFINITY, 3),
+        (Pass::Normal, Some(h)) => h.normal,
+        (Pass::Albedo, Some(h)) => h.mat.color,
+        (Pass::Normal | Pass::Albedo, None) => Vector::zero(3),
+        (Pass::Object, Some(h)) => Vector::from_s(h.idx as f32, 3),
+        (Pass::Material, Some(h)) => Vector::from_s(h.mat.id as f32, 3),
+        (Pass::Object | Pass::Material, None) => Vector::from_s(-1.0, 3),
+        (Pass::Ao, Some(h)) => Vector::from_s(h.ao, 3),
+        (Pass::Ao, None) => Vector::from_s(1.0, 3),
+    }
+}
+
+/// Returns the number of pixels `render` covers for a section `dims` pixels in size, counting the extra row and column and any masked pixels.
+pub fn traced_pixels(dims: (usize, usize)) -> u64 {
+    let di = (dims.0 as i64, dims.1 as i64);
+    ((di.0 / 2 * 2 + 1) * (di.1 / 2 * 2 + 2)) as u64
+}
+
+/// Describes the part of the image a call to `render` covers and the sample it takes.
+pub struct Section<'a> {
+    /// Midpoint of the section, in canvas coordinates.
+    pub start: (i32, i32),
+    /// Width and height of the section.
+    pub dims: (usize, usize),
+    /// Index of the sample being taken for each pixel: sample 0 goes through the pixel center,
+    /// and later samples are jittered randomly within the pixel so they can be averaged together for antialiasing.
+    pub sample: u32,
+    /// Which pixels to take the sample for, laid out like the rendered matrices. Every pixel is sampled if `None`.
+    pub mask: Option<&'a Matrix<bool>>,
+}
+
+/// Renders section `sec` of a scene containing objects in `objs`, lights in `lights`, and configuration information in `cfg`.
+/// Returns a Frame holding Matrices of colors representing RGB values of the final image and any extra passes.
+/// Extra passes are only recorded for sample 0. Every finished row is added to `progress`.
 pub fn render(
-    start: (i32, i32),
-    dims: (usize, usize),
+    sec: &Section,
     set: &[Box<dyn RayInteraction + Send + Sync>],
     lights: &[Light],
     cfg: &Config,
-) -> Matrix<Color> {
+    progress: &Progress,
+) -> Frame {
+    let (start, dims, sample) = (sec.start, sec.dims, sec.sample);
+
     let view_dist = 0.5; // distance from camera to viewport
-    let view_width = 1.0; // width of viewport
-    let view_height = 1.0 * dims.1 as f32 / dims.0 as f32; // height of viewport, transformed to make the viewport square regardless of the output dimensions
+    let (view_width, view_height) = viewport(cfg.world.fov, view_dist, dims);
 
     let di = (dims.0 as i32, dims.1 as i32);
+    let sampler = new_sampler(cfg.render.sampler);
+    let (right, up, fwd) = camera_basis(cfg.world.cam_pos, cfg.world.camera_target);
+
+    // any object with a glowing material that we can pick points on acts as a light source
+    let emitters: Vec<usize> = (0..set.len())
+        .filter(|&i| match set[i].sample((0.5, 0.5), 0.0) {
+            Some((q, _, _)) => {
+                set[i]
+                    .material(&q, 0.0)
+                    .emission
+                    .dot(Vector::from_s(1.0, 3))
+                    > 0.0
+            }
+            None => false,
+        })
+        .collect();
 
     // adding an extra row and column to make canvas bounds symmetrical
     let pixels = dims.0 * dims.1 + dims.0 + dims.1 + 1;
-    let bg = cfg.world.background.get();
-
     let mut buf = Matrix {
-        mat: vec![
-            Color {
-                r: (bg[0] * 255.0) as u8,
-                g: (bg[1] * 255.0) as u8,
-                b: (bg[2] * 255.0) as u8
-            };
-            pixels
-        ],
-        rlen: dims.0 as usize + 1,
-        clen: dims.1 as usize + 1,
+        mat: vec![cfg.world.background; pixels],
+        rlen: dims.0 + 1,
+        clen: dims.1 + 1,
     };
 
+    let mut offsets = Matrix {
+        mat: vec![(0.0, 0.0); pixels],
+        rlen: dims.0 + 1,
+        clen: dims.1 + 1,
+    };
+
+    // pixels that don't hit anything keep the values each pass uses for empty space
+    let pass_list = if sample == 0 { cfg.passes() } else { vec![] };
+    let mut passes: Vec<Matrix<Vector>> = pass_list
+        .iter()
+        .map(|pass| Matrix {
+            mat: vec![pass_value(*pass, None); pixels],
+            rlen: dims.0 + 1,
+            clen: dims.1 + 1,
+        })
+        .collect();
+
     // rendering 1 extra column here to avoid issues where di.1 / 2 is fractional
     for y in -di.1 / 2..di.1 / 2 + 2 {
         // need to write to _full_ symmetric matrix here - the last row and column are cut off in main() when we collect the results of all threads
+        let rays = stats::rays();
+
         for x in -di.0 / 2..di.0 / 2 + 1 {
-            let xf = x as f32;
-            let yf = y as f32;
+            if let Some(mask) = sec.mask {
+                if read_pixel(mask, (x, y)) != Some(true) {
+                    continue;
+                }
+            }
+
+            // use the position in the full image so results don't depend on how work is split between threads
+            let mut ps = PixelSampler::new(
+                &*sampler,
+                (x + start.0, start.1 - y),
+                sample,
+                cfg.render.samples,
+            );
+
+            let j = ps.next_2d();
+            let (jx, jy) = match sample {
+                0 => (0.0, 0.0),
+                _ => (j.0 - 0.5, j.1 - 0.5),
+            };
+
+            draw_pixel(&mut offsets, (x, y), (jx, jy));
+
+            // the first sample is taken halfway through the shutter interval, like it's taken at the pixel center
+            // scenes without motion blur skip this, so they draw the same numbers as they would without it
+            let time = match (sample, cfg.render.shutter.1 > cfg.render.shutter.0) {
+                (_, false) | (0, true) => 0.5,
+                _ => ps.next_2d().0,
+            };
+
+            let xf = x as f32 + jx;
+            let yf = y as f32 + jy;
 
             // transform canvas coordinates to viewport coordinates
             // note that the viewport axis and scale is the same of the canvas, so the transform is just a scaling op
@@ -169,34 +613,152 @@ pub fn render(
             );
 
             let cv = cfg.world.cam_pos;
-            // create ray coming off viewport
+            // create ray coming off viewport, turned to face the camera target
             let v_ray = Ray {
                 o: cv,
-                d: view_coord, // can adjust rotation by multiplying by rotation matrix here
+                d: right * Vector::from_s(view_coord.x(), 3)
+                    + up * Vector::from_s(view_coord.y(), 3)
+                    + fwd * Vector::from_s(view_coord.z(), 3),
+                time,
+            };
+
+            // where within each step of a volume to sample, drawn only when there are volumes for the same reason as time
+            let jitter = match (sample, cfg.world.volumes.is_empty()) {
+                (_, true) | (0, false) => 0.5,
+                _ => ps.next_2d().0,
             };
 
-            if let Some((i, p)) = closest_hit(&v_ray, set, (view_dist, f32::INFINITY)) {
-                let mut color_v = Vector::zero(3);
+            stats::record(|s| s.primary_rays += 1);
+            let seen = if let Some((i, p)) = closest_hit(&v_ray, set, (view_dist, f32::INFINITY)) {
+                let mut color_v = emitted_light(i, set, &v_ray, &p, &emitters, cfg, &mut ps);
+
+                let ao = match cfg.render.ao_samples {
+                    0 => 1.0,
+                    n => occlusion(i, set, &p, time, n, cfg.render.ao_distance, &mut ps),
+                };
 
                 for l in lights {
-                    color_v = color_v + light(i, set, &p, l, cfg.render.max_reflections);
+                    let lc = light(
+                        i,
+                        set,
+                        &v_ray,
+                        &p,
+                        l,
+                        &cfg.world,
+                        &mut Bounce {
+                            left: cfg.render.max_reflections,
+                            depth: 0,
+                            samples: cfg.render.gloss_samples,
+                            sampler: &mut ps,
+                        },
+                    );
+
+                    // occlusion only dims ambient light, since other lights already cast their own shadows
+                    color_v = color_v
+                        + match l.kind {
+                            LightType::Ambient => lc * Vector::from_s(ao, 3),
+                            _ => lc,
+                        };
                 }
 
-                // clamp sum of light colors to correct output range and multiply by surface color
-                let mut color_v = (set[i].material(&p).color * color_v).clamp(0.0, 1.0);
+                let m = set[i].material(&p, time);
+
+                let hit = Hit {
+                    idx: i,
+                    dist: (p - cv).dot(p - cv).sqrt(),
+                    normal: set[i].normal(&p, time),
+                    mat: m,
+                    ao,
+                };
+                for (pass, pbuf) in pass_list.iter().zip(passes.iter_mut()) {
+                    draw_pixel(pbuf, (x, y), pass_value(*pass, Some(&hit)));
+                }
 
-                let z = p.z() - cv.z();
-                let fog = cfg.world.fog;
-                if z > fog.0 {
-                    color_v =
-                        Vector::lerp(cfg.world.background, color_v, (z - fog.0) / (fog.1 - fog.0));
+                if let Some(env) = &cfg.world.environment {
+                    color_v = color_v + env_light(i, set, &v_ray, &p, cfg, &mut ps);
+
+                    // reflective surfaces show the environment wherever their reflections escape the scene
+                    // rough surfaces blur it by averaging over several reflection rays
+                    let n = set[i].normal(&p, time);
+                    if m.refl > 0.01 && cfg.render.max_reflections > 0 {
+                        let dirs: Vec<Vector> = match m.roughness > 0.0 {
+                            true => ps
+                                .next_2d_array(cfg.render.gloss_samples)
+                                .into_iter()
+                                .map(|u| glossy(-v_ray.d, n, m.roughness, u))
+                                .collect(),
+                            false => vec![glossy(-v_ray.d, n, 0.0, (0.0, 0.0))],
+                        };
+
+                        let mut env_sum = Vector::zero(3);
+                        let mut misses = 0;
+                        for r in &dirs {
+                            stats::record(|s| s.add_reflection(0));
+                            if closest_hit(&Ray { o: p, d: *r, time }, set, (0.01, f32::INFINITY))
+                                .is_none()
+                            {
+                                env_sum = env_sum
+                                    + fog::apply(
+                                        &cfg.world.fog,
+                                        env.lookup(*r),
+                                        p,
+                                        r.norm(),
+                                        f32::INFINITY,
+                                    );
+                                misses += 1;
+                            }
+                        }
+
+                        if misses > 0 {
+                            let n = dirs.len() as f32;
+                            color_v = env_sum * m.color * Vector::from_s(m.refl / n, 3)
+                                + color_v * Vector::from_s(1.0 - m.refl * misses as f32 / n, 3);
+                        }
+                    }
                 }
 
+                // clamp sum of reflected light to correct output range, with glowing surfaces adding their own light on top
+                let color_v = (color_v + m.emission).clamp(0.0, 1.0);
+
+                Some((color_v, hit.dist))
+            } else if cfg.world.environment.is_some()
+                || cfg.world.fog.density > 0.0
+                || !cfg.world.volumes.is_empty()
+            {
+                Some((background(v_ray.d, cfg).clamp(0.0, 1.0), f32::INFINITY))
+            } else {
+                None
+            };
+
+            if let Some((color_v, dist)) = seen {
+                let d = v_ray.d.norm();
+
+                // volumes in front of whatever the ray saw dim it and add the light they scatter towards the camera
+                let (scattered, tr) = medium::march(
+                    &cfg.world.volumes,
+                    cv,
+                    d,
+                    dist,
+                    jitter,
+                    Some(|q| volume_light(set, lights, &cfg.world, q, time)),
+                );
+                let color_v = color_v * tr + scattered;
+
+                // fade into the fog over the distance the ray travelled, whichever way it went
+                let color_v = fog::apply(&cfg.world.fog, color_v, cv, d, dist);
+
                 // use full dimensions to make buffer collection work properly
-                draw_pixel(&mut buf, (x, y), map_color(color_v));
+                draw_pixel(&mut buf, (x, y), color_v);
             }
         }
+
+        progress.add_pixels((di.0 / 2 * 2 + 1) as u64, stats::rays() - rays);
     }
 
-    buf
+    Frame {
+        color: buf,
+        passes,
+        offsets,
+        stats: stats::take(),
+    }
 }
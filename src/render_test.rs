@@ -0,0 +1,204 @@
+#[cfg(test)]
+mod tests {
+    use crate::bsdf::*;
+    use crate::mat::Matrix;
+    use crate::opts::*;
+    use crate::progress::Progress;
+    use crate::ray::*;
+    use crate::render::*;
+    use crate::sampler::*;
+    use crate::sdf::*;
+    use crate::vec::*;
+
+    type Objects = Vec<Box<dyn RayInteraction + Send + Sync>>;
+
+    fn material() -> Material {
+        Material {
+            color: Vector::from_s(1.0, 3),
+            bsdf: Model::Lambert(Lambert),
+            refl: 0.0,
+            roughness: 0.0,
+            emission: Vector::zero(3),
+            transmission: Vector::zero(3),
+            id: 0,
+        }
+    }
+
+    fn plane(y: f32, ny: f32) -> Box<dyn RayInteraction + Send + Sync> {
+        Box::new(Plane {
+            p: Vector::from_3(0.0, y, 0.0),
+            n: Vector::from_3(0.0, ny, 0.0),
+            mat: material(),
+            motion: Vector::zero(3),
+        })
+    }
+
+    fn glass() -> Material {
+        Material {
+            transmission: Vector::from_3(0.5, 0.8, 1.0),
+            ..material()
+        }
+    }
+
+    fn approx(a: Vector, b: Vector) -> bool {
+        let d = a - b;
+        d.dot(d) < 1e-6
+    }
+
+    /// Returns an opaque wall across the z axis at `z`, facing the origin.
+    fn plane_z(z: f32) -> Box<dyn RayInteraction + Send + Sync> {
+        Box::new(Plane {
+            p: Vector::from_3(0.0, 0.0, z),
+            n: Vector::from_3(0.0, 0.0, -1.0),
+            mat: material(),
+            motion: Vector::zero(3),
+        })
+    }
+
+    #[test]
+    fn shadow_test() {
+        let r = Ray {
+            o: Vector::zero(3),
+            d: Vector::from_3(0.0, 0.0, 1.0),
+            time: 0.0,
+        };
+        let lim = (0.01, f32::INFINITY);
+        let tr2 = Vector::from_3(0.25, 0.64, 1.0);
+
+        assert_eq!(shadow(&r, &[], lim), Vector::from_s(1.0, 3));
+
+        // light going through a transparent sphere crosses its surface twice
+        let sphere = || -> Box<dyn RayInteraction + Send + Sync> {
+            Box::new(Sphere {
+                c: Vector::from_3(0.0, 0.0, 5.0),
+                r: 1.0,
+                mat: glass(),
+                motion: Vector::zero(3),
+            })
+        };
+        assert!(approx(shadow(&r, &[sphere()], lim), tr2));
+
+        // the same goes for a signed distance field, which starts marching inside the shape after the first crossing
+        let sdf: Objects = vec![Box::new(Sdf {
+            shape: Shape::Translate {
+                off: Vector::from_3(0.0, 0.0, 5.0),
+                s: Box::new(Shape::Sphere { r: 1.0 }),
+            },
+            mat: glass(),
+            step: 1.0,
+            max_steps: 256,
+            max_dist: 100.0,
+            motion: Vector::zero(3),
+        })];
+        assert!(approx(shadow(&r, &sdf, lim), tr2));
+
+        // anything opaque in the way blocks the light, whatever else it goes through
+        let wall: Objects = vec![sphere(), plane_z(8.0)];
+        assert_eq!(shadow(&r, &wall, lim), Vector::zero(3));
+
+        // and nothing beyond the end of the ray counts
+        assert!(approx(shadow(&r, &wall, (0.01, 7.0)), tr2));
+    }
+
+    #[test]
+    fn viewport_test() {
+        // a 90 degree field of view spans as far to the sides as the viewport is in front of the camera
+        let (w, h) = viewport(90.0, 0.5, (400, 300));
+        assert!((w - 1.0).abs() < 1e-5);
+        assert!((h - 0.75).abs() < 1e-5);
+
+        // narrowing or widening the view keeps the image's aspect ratio, so nothing gets stretched
+        for fov in [10.0, 45.0, 60.0, 120.0, 170.0] {
+            for dims in [(400, 300), (300, 400), (256, 256), (1920, 1080)] {
+                let (w, h) = viewport(fov, 0.5, dims);
+                let aspect = dims.0 as f32 / dims.1 as f32;
+                assert!(
+                    (w / h - aspect).abs() < 1e-4 * aspect,
+                    "{} at {} degrees",
+                    w / h,
+                    fov
+                );
+            }
+        }
+        assert!(viewport(45.0, 0.5, (400, 300)).0 < viewport(60.0, 0.5, (400, 300)).0);
+    }
+
+    #[test]
+    fn occlusion_test() {
+        let sampler = new_sampler(SamplerType::Random);
+        let mut s = PixelSampler::new(&*sampler, (0, 0), 0, 1);
+        let p = Vector::zero(3);
+
+        // a floor on its own sees the whole sky
+        let objs: Objects = vec![plane(0.0, 1.0)];
+        assert_eq!(occlusion(0, &objs, &p, 0.0, 64, 10.0, &mut s), 1.0);
+
+        // a ceiling blocks every ray, unless it's further away than the occlusion distance
+        let objs: Objects = vec![plane(0.0, 1.0), plane(1.0, -1.0)];
+        assert_eq!(occlusion(0, &objs, &p, 0.0, 64, 10.0, &mut s), 0.0);
+        assert_eq!(occlusion(0, &objs, &p, 0.0, 64, 0.5, &mut s), 1.0);
+    }
+
+    #[test]
+    fn mask_test() {
+        // only pixels set in the mask are traced, which shows up as one camera ray each
+        let mut cfg = Config::default();
+        cfg.render.samples = 2;
+        cfg.world.fov = 90.0;
+        cfg.world.cam_pos = Vector::zero(3);
+        cfg.world.camera_target = Vector::from_3(0.0, 0.0, 1.0);
+        cfg.world.background = Vector::zero(3);
+
+        let (w, h) = (6, 4);
+        let mask = Matrix {
+            mat: (0..(w + 1) * (h + 1)).map(|i| i % 3 == 0).collect(),
+            rlen: w + 1,
+            clen: h + 1,
+        };
+        let sec = Section {
+            start: (0, 0),
+            dims: (w, h),
+            sample: 1,
+            mask: Some(&mask),
+        };
+
+        let progress = Progress::new(1);
+        let frame = render(&sec, &[], &[], &cfg, &progress);
+        let set = mask.mat.iter().filter(|m| **m).count() as u64;
+        assert_eq!(frame.stats.primary_rays, set);
+
+        // pixels that weren't traced keep the offset of the pixel center
+        for (m, o) in mask.mat.iter().zip(frame.offsets.mat.iter()) {
+            if !m {
+                assert_eq!(*o, (0.0, 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn progress_test() {
+        // progress counts every ray cast, not just the ones from the camera
+        let mut cfg = Config::default();
+        cfg.world.fov = 90.0;
+        cfg.world.cam_pos = Vector::from_3(0.0, 1.0, 0.0);
+        cfg.world.camera_target = Vector::from_3(0.0, 0.0, 1.0);
+
+        let objs: Objects = vec![plane(0.0, 1.0)];
+        let lights = [Light {
+            color: Vector::from_s(1.0, 3),
+            kind: LightType::Point(Vector::from_3(0.0, 4.0, 2.0)),
+        }];
+        let sec = Section {
+            start: (0, 0),
+            dims: (6, 4),
+            sample: 0,
+            mask: None,
+        };
+
+        let progress = Progress::new(1);
+        let frame = render(&sec, &objs, &lights, &cfg, &progress);
+        let r = progress.report(std::time::Duration::from_secs(1), None);
+        assert!(frame.stats.shadow_rays > 0);
+        assert_eq!(r.rays_per_sec, frame.stats.total_rays() as f64);
+    }
+}
@@ -0,0 +1,81 @@
+//! Defines a small pseudorandom number generator for stochastic render decisions.
+
+use crate::vec::Vector;
+
+use std::f32::consts::PI;
+
+/// A permuted congruential generator (PCG32).
+/// Much faster than anything cryptographically secure, and seedable so renders are repeatable.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Returns a generator seeded with `seed`. Nearby seeds give unrelated sequences.
+    pub fn new(seed: u64) -> Rng {
+        // run the seed through a round of splitmix64 so neighbouring pixels don't get correlated streams
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+        Rng {
+            state: z ^ (z >> 31),
+        }
+    }
+
+    /// Returns a uniformly distributed 32 bit integer.
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    /// Returns a uniformly distributed float in the range [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        // use the top 24 bits so every value is exactly representable
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Returns a direction in the hemisphere around the unit normal `n`, picked with `u`, a pair of uniformly distributed numbers in [0, 1).
+/// Directions are cosine weighted, so they bunch up around the normal where they matter most for lighting.
+pub fn hemisphere(n: Vector, u: (f32, f32)) -> Vector {
+    let (t, b) = tangents(n);
+
+    // pick a point on a unit disk and project it up onto the hemisphere
+    let r = u.0.sqrt();
+    let (sin, cos) = (2.0 * PI * u.1).sin_cos();
+    let z = (1.0 - u.0).max(0.0).sqrt();
+
+    t * Vector::from_s(r * cos, 3) + b * Vector::from_s(r * sin, 3) + n * Vector::from_s(z, 3)
+}
+
+/// Returns a direction around the unit vector `axis`, picked with `u`, a pair of uniformly distributed numbers in [0, 1).
+/// Directions follow a Phong lobe: they bunch up more tightly around `axis` as `exponent` goes up,
+/// and spread evenly over the hemisphere around it when `exponent` is 0.
+pub fn lobe(axis: Vector, exponent: f32, u: (f32, f32)) -> Vector {
+    let (t, b) = tangents(axis);
+
+    let z = (1.0 - u.0).powf(1.0 / (exponent + 1.0));
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let (sin, cos) = (2.0 * PI * u.1).sin_cos();
+
+    t * Vector::from_s(r * cos, 3) + b * Vector::from_s(r * sin, 3) + axis * Vector::from_s(z, 3)
+}
+
+/// Returns two unit vectors perpendicular to the unit vector `n` and to each other.
+fn tangents(n: Vector) -> (Vector, Vector) {
+    // use whichever axis is least parallel to n
+    let a = if n.x().abs() > 0.9 {
+        Vector::from_3(0.0, 1.0, 0.0)
+    } else {
+        Vector::from_3(1.0, 0.0, 0.0)
+    };
+    let t = n.cross(a).norm();
+    (t, n.cross(t))
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use crate::rng::*;
+    use crate::vec::*;
+
+    #[test]
+    fn rng_test() {
+        // the same seed always gives the same sequence
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+
+        // values are spread evenly over [0, 1), with every tenth of the range getting close to its share
+        let mut r = Rng::new(7);
+        let mut buckets = [0; 10];
+        for _ in 0..100000 {
+            let x = r.next_f32();
+            assert!((0.0..1.0).contains(&x));
+            buckets[(x * 10.0) as usize] += 1;
+        }
+        for n in buckets {
+            assert!(
+                (9500..10500).contains(&n),
+                "uneven bucket with {} values",
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn hemisphere_test() {
+        let n = Vector::from_3(1.0, 2.0, -0.5).norm();
+        let mut r = Rng::new(3);
+        let mut cos = 0.0;
+
+        for _ in 0..20000 {
+            let d = hemisphere(n, (r.next_f32(), r.next_f32()));
+            assert!((d.dot(d) - 1.0).abs() < 1e-4);
+            assert!(d.dot(n) >= 0.0);
+            cos += d.dot(n);
+        }
+
+        // cosine weighting puts the mean cosine at 2/3, where uniform directions would give 1/2
+        assert!((cos / 20000.0 - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn lobe_test() {
+        let axis = Vector::from_3(0.0, -1.0, 1.0).norm();
+        let mut r = Rng::new(5);
+
+        // directions spread with a density of cos^n around the axis have a mean cosine of (n + 1) / (n + 2),
+        // so exponent 0 is uniform over the hemisphere and higher ones keep them closer to the axis
+        for exponent in [0.0, 1.0, 20.0, 500.0] {
+            let mut cos = 0.0;
+            for _ in 0..20000 {
+                let d = lobe(axis, exponent, (r.next_f32(), r.next_f32()));
+                assert!((d.dot(d) - 1.0).abs() < 1e-4);
+                assert!(d.dot(axis) >= 0.0);
+                cos += d.dot(axis);
+            }
+
+            let expected = (exponent + 1.0) / (exponent + 2.0);
+            assert!(
+                (cos / 20000.0 - expected).abs() < 0.01,
+                "mean cosine {} for exponent {}",
+                cos / 20000.0,
+                exponent
+            );
+        }
+    }
+
+    #[test]
+    fn seed_test() {
+        // neighbouring seeds, like those of neighbouring pixels, give unrelated streams
+        let mut same = 0;
+        let mut product = 0.0;
+        for seed in 0..10000 {
+            let (x, y) = (Rng::new(seed).next_f32(), Rng::new(seed + 1).next_f32());
+            if x == y {
+                same += 1;
+            }
+            product += x * y;
+        }
+
+        assert_eq!(same, 0);
+        // the mean of the product of two independent uniform numbers is 1/4
+        assert!((product / 10000.0 - 0.25).abs() < 0.01);
+    }
+}
@@ -0,0 +1,316 @@
+//! Defines the patterns of numbers used for every random decision made while rendering.
+//!
+//! Every stochastic choice (where in a pixel to sample, which point on a light to pick, which way to cast an occlusion ray)
+//! draws from its own dimension of a pattern. Patterns only depend on the pixel, dimension and sample index,
+//! so renders come out exactly the same no matter how the work is split between threads.
+
+use crate::opts::SamplerType;
+use crate::rng::Rng;
+
+use std::sync::OnceLock;
+
+/// Defines a pattern of points in the unit square, used to make random decisions.
+pub trait Sampler {
+    /// Returns point number `index` out of `count` points drawn from dimension `dim` for the pixel at `pixel`.
+    /// Both numbers of the point are in [0, 1). Different dimensions and pixels give unrelated points.
+    fn point(&self, pixel: (i32, i32), dim: u32, index: u32, count: u32) -> (f32, f32);
+}
+
+/// Returns a sampler generating the pattern `t`.
+pub fn new_sampler(t: SamplerType) -> Box<dyn Sampler + Send + Sync> {
+    match t {
+        SamplerType::Random => Box::new(Random),
+        SamplerType::Stratified => Box::new(Stratified),
+        SamplerType::Halton => Box::new(Halton),
+        SamplerType::Sobol => Box::new(Sobol),
+        SamplerType::BlueNoise => Box::new(BlueNoise {
+            tile: blue_noise_tile(),
+        }),
+    }
+}
+
+/// Returns a generator seeded on a pixel, dimension and point index.
+fn hash(pixel: (i32, i32), dim: u32, index: u32) -> Rng {
+    let seed = (pixel.0 as u32 as u64) << 32 | pixel.1 as u32 as u64;
+    Rng::new(
+        seed ^ (dim as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15)
+            ^ (index as u64).wrapping_mul(0xd1b54a32d192ed03),
+    )
+}
+
+/// Turns the top 24 bits of `i` into a float in [0, 1).
+fn to_f32(i: u32) -> f32 {
+    (i >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Independent uniformly distributed random points. Converges the slowest, but has no patterns at all.
+pub struct Random;
+
+impl Sampler for Random {
+    fn point(&self, pixel: (i32, i32), dim: u32, index: u32, _count: u32) -> (f32, f32) {
+        let mut rng = hash(pixel, dim, index);
+        (rng.next_f32(), rng.next_f32())
+    }
+}
+
+/// Shuffles `i` within [0, `l`), giving a different permutation for every `p`.
+/// Uses Kensler's hash based permutation, so shuffling doesn't need any memory.
+fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // cycle walk until the shuffled index lands in range
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < l {
+            break;
+        }
+    }
+
+    (i.wrapping_add(p)) % l
+}
+
+/// Splits the unit square into a grid with a cell for each point, and puts each point somewhere random in its own cell.
+/// Cells are visited in a shuffled order, so any run of points is spread out as well.
+pub struct Stratified;
+
+impl Sampler for Stratified {
+    fn point(&self, pixel: (i32, i32), dim: u32, index: u32, count: u32) -> (f32, f32) {
+        let count = count.max(1);
+        let nx = ((count as f32).sqrt() as u32).max(1);
+        let ny = count.div_ceil(nx);
+
+        let mut rng = hash(pixel, dim, index);
+        let cell = permute(
+            index % (nx * ny),
+            nx * ny,
+            hash(pixel, dim, u32::MAX).next_u32(),
+        );
+
+        (
+            ((cell % nx) as f32 + rng.next_f32()) / nx as f32,
+            ((cell / nx) as f32 + rng.next_f32()) / ny as f32,
+        )
+    }
+}
+
+/// Returns `i` with its digits in base `b` mirrored around the decimal point.
+fn radical_inverse(b: u32, mut i: u32) -> f32 {
+    let inv = 1.0 / b as f64;
+    let mut f = inv;
+    let mut r = 0.0;
+
+    while i > 0 {
+        r += (i % b) as f64 * f;
+        i /= b;
+        f *= inv;
+    }
+
+    r as f32
+}
+
+/// Wraps `x` into [0, 1).
+fn wrap(x: f32) -> f32 {
+    let x = x - x.floor();
+    if x >= 1.0 {
+        0.0
+    } else {
+        x
+    }
+}
+
+/// The Halton sequence in bases 2 and 3, shifted randomly for every pixel and dimension.
+pub struct Halton;
+
+impl Sampler for Halton {
+    fn point(&self, pixel: (i32, i32), dim: u32, index: u32, _count: u32) -> (f32, f32) {
+        let mut rng = hash(pixel, dim, u32::MAX);
+        (
+            wrap(radical_inverse(2, index) + rng.next_f32()),
+            wrap(radical_inverse(3, index) + rng.next_f32()),
+        )
+    }
+}
+
+/// Returns the second dimension of the Sobol sequence for index `i`, as a fraction of 2^32.
+fn sobol2(mut i: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut r = 0;
+
+    while i != 0 {
+        if i & 1 != 0 {
+            r ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+
+    r
+}
+
+/// The first two dimensions of the Sobol sequence, scrambled randomly for every pixel and dimension.
+/// Every power of 2 points is perfectly stratified, so this is usually the fastest to converge.
+pub struct Sobol;
+
+impl Sampler for Sobol {
+    fn point(&self, pixel: (i32, i32), dim: u32, index: u32, _count: u32) -> (f32, f32) {
+        // flipping the same bits of every point keeps the stratification intact
+        let mut rng = hash(pixel, dim, u32::MAX);
+        (
+            to_f32(index.reverse_bits() ^ rng.next_u32()),
+            to_f32(sobol2(index) ^ rng.next_u32()),
+        )
+    }
+}
+
+/// Width and height of the blue noise tile.
+const TILE: usize = 64;
+
+/// Returns a `TILE` by `TILE` tile of blue noise, with values evenly spread over [0, 1).
+/// Neighboring pixels get very different values, so leftover noise looks like fine grain instead of clumps.
+/// The tile is built the first time it's needed and shared after that.
+pub fn blue_noise_tile() -> &'static [f32] {
+    static NOISE: OnceLock<Vec<f32>> = OnceLock::new();
+
+    NOISE.get_or_init(|| {
+        let n = TILE * TILE;
+        let sigma = 1.5f32;
+        let reach = 6i32;
+
+        // ranks pixels by repeatedly picking the one furthest from those already ranked, found as the pixel
+        // with the least energy when every ranked pixel spreads a gaussian over its neighbors
+        let mut rng = Rng::new(0x5eed);
+        let mut energy: Vec<f32> = (0..n).map(|_| rng.next_f32() * 1e-3).collect();
+        let mut rank = vec![u32::MAX; n];
+
+        for r in 0..n as u32 {
+            let mut best = 0;
+            for i in 0..n {
+                if rank[i] == u32::MAX && (rank[best] != u32::MAX || energy[i] < energy[best]) {
+                    best = i;
+                }
+            }
+            rank[best] = r;
+
+            let (bx, by) = ((best % TILE) as i32, (best / TILE) as i32);
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    // the tile wraps around, so it can be repeated without seams
+                    let x = (bx + dx).rem_euclid(TILE as i32) as usize;
+                    let y = (by + dy).rem_euclid(TILE as i32) as usize;
+                    energy[y * TILE + x] +=
+                        (-((dx * dx + dy * dy) as f32) / (2.0 * sigma * sigma)).exp();
+                }
+            }
+        }
+
+        rank.iter().map(|&r| (r as f32 + 0.5) / n as f32).collect()
+    })
+}
+
+/// Starts each pixel at a value from a blue noise tile, then steps through the R2 sequence for later points.
+/// Noise is spread evenly across the image instead of clumping, which looks cleaner at low sample counts.
+pub struct BlueNoise {
+    /// The tile the starting values come from.
+    pub tile: &'static [f32],
+}
+
+impl Sampler for BlueNoise {
+    fn point(&self, pixel: (i32, i32), dim: u32, index: u32, _count: u32) -> (f32, f32) {
+        // each dimension reads a different part of the tile, so dimensions aren't correlated
+        let lookup = |ox: u32, oy: u32| {
+            let x = (pixel.0 + (ox as i32).wrapping_mul(dim as i32 + 1)).rem_euclid(TILE as i32);
+            let y = (pixel.1 + (oy as i32).wrapping_mul(dim as i32 + 1)).rem_euclid(TILE as i32);
+            self.tile[y as usize * TILE + x as usize]
+        };
+
+        // steps of the R2 sequence, based on the plastic number, which keep successive points evenly spread
+        let (a1, a2) = (0.754_877_7f64, 0.569_840_3f64);
+
+        (
+            wrap((lookup(17, 29) as f64 + index as f64 * a1).fract() as f32),
+            wrap((lookup(37, 11) as f64 + index as f64 * a2).fract() as f32),
+        )
+    }
+}
+
+/// Draws points for one sample of one pixel, moving on to a new dimension for every random decision.
+pub struct PixelSampler<'a> {
+    /// Pattern the points come from.
+    sampler: &'a dyn Sampler,
+    /// Pixel being sampled, in image coordinates.
+    pixel: (i32, i32),
+    /// Index of the sample being taken.
+    sample: u32,
+    /// Number of samples that will be taken for the pixel.
+    samples: u32,
+    /// Next dimension to draw from.
+    dim: u32,
+}
+
+impl<'a> PixelSampler<'a> {
+    /// Returns a sampler for sample `sample` out of `samples` of the pixel at `pixel`, drawing points from `sampler`.
+    pub fn new(
+        sampler: &'a dyn Sampler,
+        pixel: (i32, i32),
+        sample: u32,
+        samples: u32,
+    ) -> PixelSampler<'a> {
+        PixelSampler {
+            sampler,
+            pixel,
+            sample,
+            samples,
+            dim: 0,
+        }
+    }
+
+    /// Returns a point for a single random decision.
+    pub fn next_2d(&mut self) -> (f32, f32) {
+        let p = self
+            .sampler
+            .point(self.pixel, self.dim, self.sample, self.samples);
+        self.dim += 1;
+        p
+    }
+
+    /// Returns `n` points for a decision made `n` times per sample, such as picking points on a light.
+    /// The points for every sample of the pixel come from one pattern, so they're spread out across samples too.
+    pub fn next_2d_array(&mut self, n: u32) -> Vec<(f32, f32)> {
+        let count = self.samples.saturating_mul(n);
+        let points = (0..n)
+            .map(|j| {
+                self.sampler.point(
+                    self.pixel,
+                    self.dim,
+                    self.sample.wrapping_mul(n).wrapping_add(j),
+                    count,
+                )
+            })
+            .collect();
+        self.dim += 1;
+        points
+    }
+}
@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use crate::opts::SamplerType;
+    use crate::sampler::*;
+
+    const ALL: [SamplerType; 5] = [
+        SamplerType::Random,
+        SamplerType::Stratified,
+        SamplerType::Halton,
+        SamplerType::Sobol,
+        SamplerType::BlueNoise,
+    ];
+
+    #[test]
+    fn range_test() {
+        for t in ALL {
+            let s = new_sampler(t);
+            for i in 0..64 {
+                let p = s.point((i as i32 - 7, 3), i % 5, i, 64);
+                assert!((0.0..1.0).contains(&p.0) && (0.0..1.0).contains(&p.1));
+
+                // the same pixel, dimension and index always give the same point
+                assert_eq!(p, s.point((i as i32 - 7, 3), i % 5, i, 64));
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_test() {
+        // 16 points from stratified samplers put exactly one point in each cell of a 4 by 4 grid
+        for t in [SamplerType::Stratified, SamplerType::Sobol] {
+            let s = new_sampler(t);
+            let mut cells = [0; 16];
+
+            for i in 0..16 {
+                let p = s.point((5, -2), 1, i, 16);
+                cells[(p.1 * 4.0) as usize * 4 + (p.0 * 4.0) as usize] += 1;
+            }
+
+            assert_eq!(cells, [1; 16]);
+        }
+    }
+
+    #[test]
+    fn blue_noise_test() {
+        // every value shows up exactly once in the tile
+        let mut tile = blue_noise_tile().to_vec();
+        tile.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        for (i, v) in tile.iter().enumerate() {
+            assert_eq!(*v, (i as f32 + 0.5) / tile.len() as f32);
+        }
+    }
+}
@@ -0,0 +1,1005 @@
+//! Defines the scene description every scene file is read into, whether it's written in TOML, JSON or YAML,
+//! and how scene files are loaded, combined and checked before anything is read from them.
+
+use crate::vec::Vector;
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Index;
+use std::path::{Path, PathBuf};
+
+/// A table of scene values, sorted by name.
+pub type Table = BTreeMap<String, Value>;
+
+/// A value in a scene description. Every scene file format is read into the same tree of values,
+/// so scenes are read the same way whatever they're written in.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+impl Value {
+    /// Returns the value at `key` if this is a table that has one.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_table().and_then(|t| t.get(key))
+    }
+
+    /// Returns the value at `key` if this is a table that has one, so it can be changed.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.as_table_mut().and_then(|t| t.get_mut(key))
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns the number if this is one written with a decimal point. Use `to_f32` to read any number.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Value::Table(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn as_table_mut(&mut self) -> Option<&mut Table> {
+        match self {
+            Value::Table(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    /// Returns the table this holds, if it's a table.
+    pub fn into_table(self) -> Option<Table> {
+        match self {
+            Value::Table(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.as_integer().is_some()
+    }
+
+    pub fn is_float(&self) -> bool {
+        self.as_float().is_some()
+    }
+
+    pub fn is_str(&self) -> bool {
+        self.as_str().is_some()
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.as_array().is_some()
+    }
+
+    pub fn is_table(&self) -> bool {
+        self.as_table().is_some()
+    }
+}
+
+/// Looks up `key` in a table, panicking if it isn't there. Scenes are validated before they're read,
+/// so required keys can be looked up this way.
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        self.get(key)
+            .unwrap_or_else(|| panic!("could not find \"{}\"", key))
+    }
+}
+
+/// Looks up element `i` of an array, panicking if there's no such element.
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, i: usize) -> &Value {
+        self.as_array()
+            .and_then(|a| a.get(i))
+            .unwrap_or_else(|| panic!("could not find element {}", i))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Boolean(b)
+    }
+}
+
+impl From<toml::Value> for Value {
+    fn from(v: toml::Value) -> Value {
+        match v {
+            toml::Value::Boolean(b) => Value::Boolean(b),
+            toml::Value::Integer(i) => Value::Integer(i),
+            toml::Value::Float(f) => Value::Float(f),
+            toml::Value::String(s) => Value::String(s),
+            // nothing in a scene is a date, but they're kept as they're written rather than lost
+            toml::Value::Datetime(d) => Value::String(d.to_string()),
+            toml::Value::Array(a) => Value::Array(a.into_iter().map(Value::from).collect()),
+            toml::Value::Table(t) => {
+                Value::Table(t.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
+
+/// Turns a scene value into a TOML value, to write it out.
+impl From<Value> for toml::Value {
+    fn from(v: Value) -> toml::Value {
+        match v {
+            Value::Boolean(b) => toml::Value::Boolean(b),
+            Value::Integer(i) => toml::Value::Integer(i),
+            Value::Float(f) => toml::Value::Float(f),
+            Value::String(s) => toml::Value::String(s),
+            Value::Array(a) => toml::Value::Array(a.into_iter().map(toml::Value::from).collect()),
+            Value::Table(t) => {
+                toml::Value::Table(t.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
+
+/// Turns a JSON value into a scene value. JSON has nulls, which scenes have no use for.
+fn from_json(v: serde_json::Value) -> Result<Value, String> {
+    use serde_json::Value as J;
+
+    Ok(match v {
+        J::Null => return Err("null values aren't allowed".to_string()),
+        J::Bool(b) => Value::Boolean(b),
+        J::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Float(n.as_f64().ok_or("number out of range")?),
+        },
+        J::String(s) => Value::String(s),
+        J::Array(a) => Value::Array(a.into_iter().map(from_json).collect::<Result<_, _>>()?),
+        J::Object(o) => Value::Table(
+            o.into_iter()
+                .map(|(k, v)| Ok((k, from_json(v)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+    })
+}
+
+/// Turns a YAML value into a scene value. YAML tables can be keyed by anything, but scene tables are keyed by name.
+fn from_yaml(v: serde_yaml::Value) -> Result<Value, String> {
+    use serde_yaml::Value as Y;
+
+    Ok(match v {
+        Y::Null => return Err("null values aren't allowed".to_string()),
+        Y::Bool(b) => Value::Boolean(b),
+        Y::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Float(n.as_f64().ok_or("number out of range")?),
+        },
+        Y::String(s) => Value::String(s),
+        Y::Sequence(a) => Value::Array(a.into_iter().map(from_yaml).collect::<Result<_, _>>()?),
+        Y::Mapping(m) => Value::Table(
+            m.into_iter()
+                .map(|(k, v)| match k {
+                    Y::String(k) => Ok((k, from_yaml(v)?)),
+                    k => Err(format!("table keys should be strings, not {:?}", k)),
+                })
+                .collect::<Result<_, String>>()?,
+        ),
+        Y::Tagged(t) => from_yaml(t.value)?,
+    })
+}
+
+/// Parses the text of the scene file at `path` into a scene tree. The format is picked from the extension:
+/// .json for JSON, .yaml or .yml for YAML, and TOML for anything else.
+pub fn parse(path: &Path, text: &str) -> Result<Value, String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    match ext.as_deref() {
+        Some("json") => serde_json::from_str(text)
+            .map_err(|e| e.to_string())
+            .and_then(from_json),
+        Some("yaml" | "yml") => serde_yaml::from_str(text)
+            .map_err(|e| e.to_string())
+            .and_then(from_yaml),
+        _ => text
+            .parse::<toml::Value>()
+            .map(Value::from)
+            .map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("could not parse config file {}: {}", path.display(), e))
+}
+
+/// Writes the scene tree `v` out as the text of a TOML scene file.
+pub fn to_toml(v: &Value) -> String {
+    toml::to_string(&toml::Value::from(v.clone())).expect("could not write scene")
+}
+
+/// Turns a number into a float. Whole numbers can be written without a decimal point,
+/// which is what most JSON writers do.
+pub fn to_f32(v: &Value) -> f32 {
+    v.as_float()
+        .or_else(|| v.as_integer().map(|i| i as f64))
+        .expect("could not cast into float") as f32
+}
+
+/// Turns an array in `parent` at `s` into a Vector with 3 elements.
+pub fn get_v3(parent: &Value, s: &str) -> Vector {
+    let vals = parent[s].as_array().expect("could not cast into array");
+    Vector::from_3(to_f32(&vals[0]), to_f32(&vals[1]), to_f32(&vals[2]))
+}
+
+/// Turns a Vector with 3 elements into an array.
+pub fn to_array(v: Vector) -> Value {
+    Value::Array(vec![
+        Value::Float(v.x() as f64),
+        Value::Float(v.y() as f64),
+        Value::Float(v.z() as f64),
+    ])
+}
+
+/// Returns `base` with everything in `over` laid on top of it.
+/// Tables are merged key by key, so `over` only has to list what it changes. Any other value in `over`,
+/// including animated values, replaces the one in `base` outright.
+pub fn merge(base: Value, over: Value) -> Value {
+    match (base, over) {
+        (Value::Table(mut b), Value::Table(o)) if !o.contains_key("keys") => {
+            for (k, v) in o {
+                let v = match b.remove(&k) {
+                    Some(bv) => merge(bv, v),
+                    None => v,
+                };
+                b.insert(k, v);
+            }
+            Value::Table(b)
+        }
+        (_, o) => o,
+    }
+}
+
+/// Reads the scene file at `path` along with every file listed in its optional `include` key, found relative to `path`.
+/// Included files are merged in the order they're listed, so later ones override earlier ones,
+/// and the file including them overrides them all. glTF files are shown in a scene made up around them.
+/// Files named in each scene file, like meshes and textures, are found relative to the file naming them.
+/// Returns a message saying what's wrong if a file can't be read or parsed, or if files include each other.
+pub fn load(path: &Path) -> Result<Value, String> {
+    load_included(path, &mut vec![])
+}
+
+/// Does the work of `load`. `stack` holds the files currently being read, to catch include cycles.
+fn load_included(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, String> {
+    let full = fs::canonicalize(path)
+        .map_err(|_| format!("could not read config file {}", path.display()))?;
+    if stack.contains(&full) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain([&full])
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(format!("include cycle: {}", chain.join(" -> ")));
+    }
+
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    let here = full.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    if matches!(ext.as_deref(), Some("gltf" | "glb")) {
+        let mut cfg = gltf_scene(path);
+        rebase(cfg.as_table_mut().expect("could not cast into map"), &here);
+        return Ok(cfg);
+    }
+
+    let text = fs::read_to_string(path)
+        .map_err(|_| format!("could not read config file {}", path.display()))?;
+    let mut t = parse(path, &text)?
+        .into_table()
+        .ok_or_else(|| format!("config file {} should hold a table", path.display()))?;
+    rebase(&mut t, &here);
+
+    let includes = match t.remove("include") {
+        None => vec![],
+        Some(Value::String(s)) => vec![s],
+        Some(Value::Array(a)) => a
+            .iter()
+            .map(|i| i.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .ok_or("include should be a file name or an array of them")?,
+        Some(_) => return Err("include should be a file name or an array of them".to_string()),
+    };
+
+    let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    stack.push(full);
+    let mut cfg = Value::Table(Table::new());
+    for i in includes {
+        cfg = merge(cfg, load_included(&dir.join(i), stack)?);
+    }
+    stack.pop();
+
+    Ok(merge(cfg, Value::Table(t)))
+}
+
+/// Puts `dir`, the directory of the scene file the table `t` was read from, in front of every relative file name
+/// in it: mesh, glTF and texture files of objects and prefabs, and the environment map. Files named in an included
+/// file are then still found once it's merged into a file somewhere else.
+fn rebase(t: &mut Table, dir: &Path) {
+    let join = |v: Option<&mut Value>| {
+        if let Some(Value::String(s)) = v {
+            *s = dir.join(&*s).to_string_lossy().into_owned();
+        }
+    };
+    let join_object = |ov: &mut Value| {
+        join(ov.get_mut("file"));
+        join(ov.get_mut("texture"));
+    };
+
+    if let Some(wv) = t.get_mut("world") {
+        join(wv.get_mut("background"));
+    }
+    if let Some(objects) = t.get_mut("objects").and_then(Value::as_table_mut) {
+        objects.values_mut().for_each(join_object);
+    }
+    if let Some(prefabs) = t.get_mut("prefabs").and_then(Value::as_table_mut) {
+        for group in prefabs.values_mut().filter_map(Value::as_table_mut) {
+            group.values_mut().for_each(join_object);
+        }
+    }
+}
+
+/// Makes up a scene showing the glTF file at `path` through its own camera, or from the front if it has none.
+/// Its own lights are joined by a dim ambient light and a light from above, since files often leave lights out.
+fn gltf_scene(path: &Path) -> Value {
+    let mut cfg: Value = r#"
+        [output]
+        format = "png"
+        width = 1920
+        height = 1080
+        bits = 8
+
+        [render]
+        max_reflections = 3
+        threads = "auto"
+        samples = 16
+
+        [world]
+        camera_position = [0.0, 0.0, -1.0]
+        background = [0.8, 0.8, 0.8]
+
+        [lights.ambient]
+        type = "ambient"
+        color = [0.2, 0.2, 0.2]
+
+        [lights.sun]
+        type = "directional"
+        color = [0.8, 0.8, 0.8]
+        position = [1.0, 2.0, -1.5]
+        "#
+    .parse::<toml::Value>()
+    .expect("could not parse default glTF scene")
+    .into();
+
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("model");
+    let file = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let mut obj = Table::new();
+    obj.insert("type".to_string(), "gltf".into());
+    obj.insert("file".to_string(), file.into());
+    obj.insert("camera".to_string(), true.into());
+
+    let mut objects = Table::new();
+    objects.insert(name.to_string(), Value::Table(obj));
+    cfg.as_table_mut()
+        .expect("could not cast into map")
+        .insert("objects".to_string(), Value::Table(objects));
+    cfg
+}
+
+/// Moves the object table `ov` by `off`, shifting its position (the offset of meshes and glTF files),
+/// the outermost shape of signed distance fields and volumes,
+/// and the box that density grids are stretched over.
+fn offset_object(mut ov: Value, off: Vector) -> Value {
+    let shift = |t: &mut Value, key: &str| {
+        let moved = match t.get(key) {
+            Some(_) => get_v3(t, key) + off,
+            None => off,
+        };
+        t.as_table_mut()
+            .expect("could not cast into table")
+            .insert(key.to_string(), to_array(moved));
+    };
+
+    for key in ["center", "point"] {
+        if ov.get(key).is_some() {
+            shift(&mut ov, key);
+        }
+    }
+    if matches!(
+        ov.get("type").and_then(|t| t.as_str()),
+        Some("mesh" | "gltf")
+    ) {
+        shift(&mut ov, "offset");
+    }
+    if let Some(sv) = ov.get_mut("shape") {
+        shift(sv, "center");
+    }
+    if let Some(fv) = ov.get_mut("field") {
+        if fv.get("min").is_some() && fv.get("max").is_some() {
+            shift(fv, "min");
+            shift(fv, "max");
+        }
+    }
+
+    ov
+}
+
+/// Replaces every object of type "prefab" in `cfg` with copies of the objects in the group from `[prefabs]`
+/// named by its `prefab` key, moved by its optional `offset`. Copies are named "<instance>.<object>".
+pub fn instance_prefabs(cfg: Value) -> Value {
+    let mut t = cfg.into_table().expect("could not cast into map");
+    let prefabs = t.remove("prefabs");
+    let objects = match t.remove("objects") {
+        Some(o) => o.into_table().expect("could not cast object into map"),
+        None => return Value::Table(t),
+    };
+
+    let mut expanded = Table::new();
+    for (name, ov) in objects {
+        if ov["type"].as_str() != Some("prefab") {
+            expanded.insert(name, ov);
+            continue;
+        }
+
+        let pname = ov["prefab"].as_str().expect("could not cast into string");
+        let group = prefabs
+            .as_ref()
+            .and_then(|p| p.get(pname))
+            .and_then(|g| g.as_table())
+            .unwrap_or_else(|| panic!("unknown prefab {}", pname));
+        let off = match ov.get("offset") {
+            Some(_) => get_v3(&ov, "offset"),
+            None => Vector::zero(3),
+        };
+
+        for (part, pv) in group {
+            assert!(
+                pv["type"].as_str() != Some("prefab"),
+                "prefabs can't contain other prefabs"
+            );
+            expanded.insert(format!("{}.{}", name, part), offset_object(pv.clone(), off));
+        }
+    }
+
+    t.insert("objects".to_string(), Value::Table(expanded));
+    Value::Table(t)
+}
+
+/// What a value in a scene should be.
+#[derive(Clone, Copy)]
+enum Kind {
+    /// A number, with or without a decimal point.
+    Num,
+    /// A whole number.
+    Int,
+    Bool,
+    Str,
+    /// An array of this many numbers, or of any number of them if it's 0.
+    Nums(usize),
+    /// An array of this many whole numbers.
+    Ints(usize),
+    /// One of these strings.
+    OneOf(&'static [&'static str]),
+    /// An array of any of these strings.
+    ListOf(&'static [&'static str]),
+    /// A color, or the name of an image file.
+    ColorOrFile,
+    /// A whole number, or "auto".
+    Threads,
+}
+
+impl Kind {
+    /// Returns whether `v` is this kind of value.
+    fn ok(self, v: &Value) -> bool {
+        let is_num = |v: &Value| v.is_float() || v.is_integer();
+        let all = |n: usize, ok: &dyn Fn(&Value) -> bool| {
+            v.as_array()
+                .is_some_and(|a| (n == 0 || a.len() == n) && a.iter().all(ok))
+        };
+
+        match self {
+            Kind::Num => is_num(v),
+            Kind::Int => v.is_integer(),
+            Kind::Bool => v.as_bool().is_some(),
+            Kind::Str => v.is_str(),
+            Kind::Nums(n) => all(n, &is_num),
+            Kind::Ints(n) => all(n, &Value::is_integer),
+            Kind::OneOf(names) => v.as_str().is_some_and(|s| names.contains(&s)),
+            Kind::ListOf(names) => all(0, &|p| p.as_str().is_some_and(|s| names.contains(&s))),
+            Kind::ColorOrFile => Kind::Nums(3).ok(v) || v.is_str(),
+            Kind::Threads => v.is_integer() || v.as_str() == Some("auto"),
+        }
+    }
+
+    /// Describes this kind of value, for error messages.
+    fn what(self) -> String {
+        let quoted = |names: &[&str]| {
+            names
+                .iter()
+                .map(|n| format!("\"{}\"", n))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self {
+            Kind::Num => "a number".to_string(),
+            Kind::Int => "a whole number".to_string(),
+            Kind::Bool => "true or false".to_string(),
+            Kind::Str => "a string".to_string(),
+            Kind::Nums(0) => "an array of numbers".to_string(),
+            Kind::Nums(n) => format!("an array of {} numbers", n),
+            Kind::Ints(n) => format!("an array of {} whole numbers", n),
+            Kind::OneOf(names) => format!("one of {}", quoted(names)),
+            Kind::ListOf(names) => format!("an array of any of {}", quoted(names)),
+            Kind::ColorOrFile => "a color or a file name".to_string(),
+            Kind::Threads => "a whole number or \"auto\"".to_string(),
+        }
+    }
+}
+
+use Kind::*;
+
+/// Keys a table can have: the name of each, what its value should be, and whether it has to be there.
+type Keys = &'static [(&'static str, Kind, bool)];
+
+const OUTPUT: Keys = &[
+    ("format", OneOf(&["png", "ppm"]), true),
+    ("width", Int, true),
+    ("height", Int, true),
+    ("bits", Int, true),
+    (
+        "passes",
+        ListOf(&["depth", "normal", "albedo", "object", "material", "ao"]),
+        false,
+    ),
+    ("ao_pass", Bool, false),
+    ("preview_passes", Int, false),
+    ("preview_seconds", Num, false),
+    ("progress_file", Str, false),
+    ("sample_heatmap", Bool, false),
+    ("stats", Bool, false),
+];
+
+const RENDER: Keys = &[
+    ("max_reflections", Int, true),
+    ("threads", Threads, true),
+    ("samples", Int, false),
+    ("time_limit", Num, false),
+    ("noise_threshold", Num, false),
+    ("adaptive_threshold", Num, false),
+    ("adaptive_min_samples", Int, false),
+    (
+        "sampler",
+        OneOf(&["random", "stratified", "halton", "sobol", "blue_noise"]),
+        false,
+    ),
+    (
+        "filter",
+        OneOf(&["box", "tent", "gaussian", "mitchell", "lanczos"]),
+        false,
+    ),
+    ("filter_radius", Num, false),
+    ("shutter", Nums(2), false),
+    ("light_samples", Int, false),
+    ("env_samples", Int, false),
+    ("gloss_samples", Int, false),
+    ("ao_samples", Int, false),
+    ("ao_distance", Num, false),
+];
+
+const WORLD: Keys = &[
+    ("camera_position", Nums(3), true),
+    ("camera_target", Nums(3), false),
+    ("fov", Num, false),
+    ("background", ColorOrFile, true),
+    ("background_intensity", Num, false),
+];
+
+const FOG: Keys = &[
+    ("density", Num, false),
+    ("color", Nums(3), true),
+    ("height", Num, false),
+    ("falloff", Num, false),
+];
+
+const POST: Keys = &[
+    ("denoise", Bool, false),
+    ("denoise_iterations", Int, false),
+    ("denoise_color", Num, false),
+    ("denoise_normal", Num, false),
+    ("denoise_depth", Num, false),
+    ("denoise_albedo", Num, false),
+];
+
+const ANIMATION: Keys = &[
+    ("start", Int, false),
+    ("end", Int, false),
+    ("fps", Num, false),
+];
+
+const MATERIAL: Keys = &[
+    ("color", Nums(3), true),
+    ("refl", Num, true),
+    (
+        "model",
+        OneOf(&["phong", "lambert", "blinn_phong", "ggx"]),
+        false,
+    ),
+    ("spec", Num, false),
+    ("specular", Num, false),
+    ("roughness", Num, false),
+    ("metallic", Num, false),
+    ("emission", Nums(3), false),
+    ("transmission", Nums(3), false),
+];
+
+const SHAPE_TYPES: &[&str] = &[
+    "sphere",
+    "box",
+    "torus",
+    "capsule",
+    "union",
+    "smooth_union",
+    "intersection",
+    "difference",
+    "twist",
+    "repeat",
+];
+
+/// Checks the keys of the table `t`, found at `at` in the scene, against `keys`, adding a message to `errs` for each problem.
+/// Keys that aren't listed are left alone.
+fn check(errs: &mut Vec<String>, at: &str, t: &Value, keys: Keys) {
+    for &(key, kind, required) in keys {
+        match t.get(key) {
+            None if required => errs.push(format!("{}: missing \"{}\"", at, key)),
+            Some(v) if !kind.ok(v) => {
+                errs.push(format!("{}: \"{}\" should be {}", at, key, kind.what()))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks the value at `at` is a table, adding a message to `errs` if it isn't.
+fn is_table(errs: &mut Vec<String>, at: &str, v: &Value) -> bool {
+    if !v.is_table() {
+        errs.push(format!("{}: should be a table", at));
+    }
+    v.is_table()
+}
+
+/// Checks the signed distance field shape `sv`, found at `at` in the scene, along with every shape inside it.
+fn check_shape(errs: &mut Vec<String>, at: &str, sv: &Value) {
+    if !is_table(errs, at, sv) {
+        return;
+    }
+    check(
+        errs,
+        at,
+        sv,
+        &[
+            ("type", OneOf(SHAPE_TYPES), true),
+            ("center", Nums(3), false),
+        ],
+    );
+
+    let keys: Keys = match sv.get("type").and_then(|t| t.as_str()) {
+        Some("sphere") => &[("radius", Num, true)],
+        Some("box") => &[("size", Nums(3), true)],
+        Some("torus") => &[("radii", Nums(2), true)],
+        Some("capsule") => &[
+            ("a", Nums(3), true),
+            ("b", Nums(3), true),
+            ("radius", Num, true),
+        ],
+        Some("smooth_union" | "twist") => &[("k", Num, true)],
+        Some("repeat") => &[("period", Nums(3), true)],
+        _ => &[],
+    };
+    check(errs, at, sv, keys);
+
+    match sv.get("type").and_then(|t| t.as_str()) {
+        Some("union" | "smooth_union" | "intersection" | "difference") => match sv.get("shapes") {
+            None => errs.push(format!("{}: missing \"shapes\"", at)),
+            // combining nothing has no surface, and smooth unions and differences start from the first shape
+            Some(Value::Array(list)) if list.is_empty() => {
+                errs.push(format!("{}: \"shapes\" should list at least one shape", at))
+            }
+            Some(Value::Array(list)) => {
+                for (i, s) in list.iter().enumerate() {
+                    check_shape(errs, &format!("{}.shapes[{}]", at, i), s);
+                }
+            }
+            Some(_) => errs.push(format!("{}: \"shapes\" should be an array of shapes", at)),
+        },
+        Some("twist" | "repeat") => match sv.get("shape") {
+            None => errs.push(format!("{}: missing \"shape\"", at)),
+            Some(s) => check_shape(errs, &format!("{}.shape", at), s),
+        },
+        _ => {}
+    }
+}
+
+/// Checks the table `fv` describing how the density of a medium varies, found at `at` in the scene.
+fn check_field(errs: &mut Vec<String>, at: &str, fv: &Value) {
+    if !is_table(errs, at, fv) {
+        return;
+    }
+
+    let keys: Keys = match fv.get("type").and_then(|t| t.as_str()) {
+        Some("noise") => &[
+            ("scale", Num, false),
+            ("octaves", Int, false),
+            ("threshold", Num, false),
+            ("seed", Int, false),
+        ],
+        Some("grid") => &[
+            ("resolution", Ints(3), true),
+            ("values", Nums(0), true),
+            ("min", Nums(3), true),
+            ("max", Nums(3), true),
+        ],
+        _ => &[],
+    };
+    check(
+        errs,
+        at,
+        fv,
+        &[("type", OneOf(&["constant", "noise", "grid"]), true)],
+    );
+    check(errs, at, fv, keys);
+}
+
+/// Checks the object table `ov`, found at `at` in the scene. Objects can use any of `materials`,
+/// and place any of `prefabs` unless they're inside a prefab themselves.
+fn check_object(
+    errs: &mut Vec<String>,
+    at: &str,
+    ov: &Value,
+    materials: &[&String],
+    prefabs: Option<&[&String]>,
+) {
+    if !is_table(errs, at, ov) {
+        return;
+    }
+    check(errs, at, ov, &[("type", Str, true)]);
+
+    let keys: Keys = match ov.get("type").and_then(|t| t.as_str()) {
+        Some("sphere") => &[("center", Nums(3), true), ("radius", Num, true)],
+        Some("plane") => &[("point", Nums(3), true), ("normal", Nums(3), true)],
+        Some("sdf") => &[
+            ("step", Num, false),
+            ("max_steps", Int, false),
+            ("max_dist", Num, false),
+        ],
+        Some("mesh") => &[
+            ("normals", Nums(0), false),
+            ("colors", Nums(0), false),
+            ("uvs", Nums(0), false),
+            ("scale", Num, false),
+            ("offset", Nums(3), false),
+            ("texture", Str, false),
+        ],
+        Some("gltf") => &[
+            ("file", Str, true),
+            ("offset", Nums(3), false),
+            ("scale", Num, false),
+            ("light_scale", Num, false),
+            ("camera", Bool, false),
+            ("lights", Bool, false),
+        ],
+        Some("volume") => &[
+            ("absorption", Nums(3), true),
+            ("scattering", Nums(3), true),
+            ("anisotropy", Num, false),
+            ("density", Num, false),
+            ("step", Num, false),
+        ],
+        Some("prefab") => &[("offset", Nums(3), false)],
+        Some(t) => {
+            errs.push(format!("{}: unknown object type \"{}\"", at, t));
+            return;
+        }
+        None => return,
+    };
+    check(errs, at, ov, keys);
+
+    let t = ov["type"].as_str();
+    if t != Some("volume") && t != Some("prefab") {
+        check(errs, at, ov, &[("velocity", Nums(3), false)]);
+    }
+
+    match t {
+        Some("sdf" | "volume") => match ov.get("shape") {
+            None => errs.push(format!("{}: missing \"shape\"", at)),
+            Some(sv) => check_shape(errs, &format!("{}.shape", at), sv),
+        },
+        Some("mesh") if ov.get("file").is_some() => check(errs, at, ov, &[("file", Str, true)]),
+        Some("mesh") => check(errs, at, ov, &[("positions", Nums(0), true)]),
+        Some("prefab") => match prefabs {
+            None => errs.push(format!("{}: prefabs can't contain other prefabs", at)),
+            Some(names) => match ov.get("prefab").and_then(|p| p.as_str()) {
+                Some(p) if !names.iter().any(|n| *n == p) => {
+                    errs.push(format!("{}: unknown prefab \"{}\"", at, p))
+                }
+                Some(_) => {}
+                None => check(errs, at, ov, &[("prefab", Str, true)]),
+            },
+        },
+        _ => {}
+    }
+    if let (Some("volume"), Some(fv)) = (t, ov.get("field")) {
+        check_field(errs, &format!("{}.field", at), fv);
+    }
+
+    if matches!(t, Some("sphere" | "plane" | "sdf" | "mesh")) {
+        match ov.get("material").and_then(|m| m.as_str()) {
+            Some(m) if !materials.iter().any(|n| *n == m) => {
+                errs.push(format!("{}: unknown material \"{}\"", at, m))
+            }
+            Some(_) => {}
+            None => check(errs, at, ov, &[("material", Str, true)]),
+        }
+    }
+}
+
+/// Checks the whole scene tree `cfg` in one pass, before anything is read from it: every section, material, object,
+/// shape and light is checked for the keys it needs and for what their values are.
+/// Returns a message for each problem found, so they can all be fixed at once.
+pub fn validate(cfg: &Value) -> Result<(), Vec<String>> {
+    let mut errs = vec![];
+    let empty = Table::new();
+    let section = |k: &str| cfg.get(k).and_then(|v| v.as_table()).unwrap_or(&empty);
+
+    for k in cfg.as_table().expect("could not cast into map").keys() {
+        let known = [
+            "output",
+            "render",
+            "world",
+            "post",
+            "animation",
+            "materials",
+            "objects",
+            "prefabs",
+            "lights",
+        ];
+        if !known.contains(&k.as_str()) {
+            errs.push(format!("unknown section \"{}\"", k));
+        }
+    }
+    for k in ["output", "render", "world"] {
+        if cfg.get(k).is_none() {
+            errs.push(format!("missing section \"{}\"", k));
+        }
+    }
+
+    let sections = [
+        ("output", OUTPUT),
+        ("render", RENDER),
+        ("world", WORLD),
+        ("post", POST),
+        ("animation", ANIMATION),
+    ];
+    for (k, keys) in sections {
+        if let Some(v) = cfg.get(k) {
+            if is_table(&mut errs, k, v) {
+                check(&mut errs, k, v, keys);
+            }
+        }
+    }
+
+    match cfg.get("world").and_then(|w| w.get("fog")) {
+        // scenes from before fog was exponential have a linear [start, end] range
+        Some(Value::Array(range)) => {
+            let nums: Vec<f32> = range.iter().filter(|v| Num.ok(v)).map(to_f32).collect();
+            let mid = nums.iter().sum::<f32>() / nums.len().max(1) as f32;
+            let density = std::f32::consts::LN_2 / mid.max(1e-3);
+            errs.push(format!(
+                "world: fog = [start, end] is no longer supported, fog is now a table like \
+                 fog = {{ density = {density:.3}, color = [1.0, 1.0, 1.0] }}, \
+                 where a density of {density:.3} lets half the light through {mid} units away"
+            ));
+        }
+        Some(fv) if is_table(&mut errs, "world.fog", fv) => check(&mut errs, "world.fog", fv, FOG),
+        _ => {}
+    }
+
+    let materials: Vec<&String> = section("materials").keys().collect();
+    for (name, mv) in section("materials") {
+        let at = format!("materials.{}", name);
+        if is_table(&mut errs, &at, mv) {
+            check(&mut errs, &at, mv, MATERIAL);
+        }
+    }
+
+    let prefabs: Vec<&String> = section("prefabs").keys().collect();
+    for (name, ov) in section("objects") {
+        let at = format!("objects.{}", name);
+        check_object(&mut errs, &at, ov, &materials, Some(&prefabs));
+    }
+    for (group, gv) in section("prefabs") {
+        match gv.as_table() {
+            Some(g) => {
+                for (name, ov) in g {
+                    let at = format!("prefabs.{}.{}", group, name);
+                    check_object(&mut errs, &at, ov, &materials, None);
+                }
+            }
+            None => errs.push(format!("prefabs.{}: should be a table", group)),
+        }
+    }
+
+    for (name, lv) in section("lights") {
+        let at = format!("lights.{}", name);
+        if !is_table(&mut errs, &at, lv) {
+            continue;
+        }
+        let keys: Keys = &[
+            ("type", OneOf(&["ambient", "directional", "point"]), true),
+            ("color", Nums(3), true),
+        ];
+        check(&mut errs, &at, lv, keys);
+        if matches!(
+            lv.get("type").and_then(|t| t.as_str()),
+            Some("directional" | "point")
+        ) {
+            check(&mut errs, &at, lv, &[("position", Nums(3), true)]);
+        }
+    }
+
+    match errs.is_empty() {
+        true => Ok(()),
+        false => Err(errs),
+    }
+}
@@ -0,0 +1,375 @@
+#[cfg(test)]
+mod tests {
+    use crate::scene::*;
+    use crate::vec::*;
+
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn toml(text: &str) -> Value {
+        parse(Path::new("scene.toml"), text).unwrap()
+    }
+
+    /// Writes each of `files` into a fresh directory named after `test`, and returns the directory.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("oxide_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    /// A scene with everything it needs and nothing wrong with it.
+    const SCENE: &str = r#"
+        [output]
+        format = "png"
+        width = 64
+        height = 48
+        bits = 8
+
+        [render]
+        max_reflections = 2
+        threads = "auto"
+
+        [world]
+        camera_position = [0.0, 1.0, -5.0]
+        background = [0.1, 0.1, 0.1]
+
+        [materials.white]
+        color = [1.0, 1.0, 1.0]
+        refl = 0
+
+        [objects.ball]
+        type = "sphere"
+        center = [0, 1, 0]
+        radius = 1.0
+        material = "white"
+
+        [lights.sun]
+        type = "directional"
+        color = [1.0, 1.0, 1.0]
+        position = [1.0, 1.0, -1.0]
+    "#;
+
+    #[test]
+    fn parse_test() {
+        let json = r#"{
+            "render": { "samples": 4, "threads": "auto", "denoise": true },
+            "world": { "camera_position": [0, 1.5, -5], "fov": 60.5 },
+            "objects": { "ball": { "type": "sphere", "center": [0.0, 1.0, 0.0] } }
+        }"#;
+        let yaml = "
+render:
+  samples: 4
+  threads: auto
+  denoise: true
+world:
+  camera_position: [0, 1.5, -5]
+  fov: 60.5
+objects:
+  ball:
+    type: sphere
+    center: [0.0, 1.0, 0.0]
+";
+        let text = r#"
+            [render]
+            samples = 4
+            threads = "auto"
+            denoise = true
+
+            [world]
+            camera_position = [0, 1.5, -5]
+            fov = 60.5
+
+            [objects.ball]
+            type = "sphere"
+            center = [0.0, 1.0, 0.0]
+        "#;
+
+        // every format gives the same tree, down to whole numbers staying whole
+        let t = toml(text);
+        assert_eq!(parse(Path::new("a.json"), json).unwrap(), t);
+        assert_eq!(parse(Path::new("a.YML"), yaml).unwrap(), t);
+        assert_eq!(parse(Path::new("a.yaml"), yaml).unwrap(), t);
+        assert_eq!(t["render"]["samples"], Value::Integer(4));
+        assert_eq!(t["world"]["fov"], Value::Float(60.5));
+
+        // scenes have no use for nulls, and parse errors say which file they're in
+        let err = parse(Path::new("a.json"), r#"{ "world": { "fov": null } }"#).unwrap_err();
+        assert!(err.contains("a.json") && err.contains("null"), "{}", err);
+        assert!(parse(Path::new("a.yaml"), "world:\n  fov:\n").is_err());
+        assert!(parse(Path::new("a.toml"), "[world").is_err());
+    }
+
+    #[test]
+    fn validate_test() {
+        assert_eq!(validate(&toml(SCENE)), Ok(()));
+
+        let broken = toml(&format!(
+            r#"
+            {SCENE}
+            [post]
+            denoise = "yes"
+
+            [camera]
+            fov = 40
+
+            [objects.blob]
+            type = "sdf"
+            material = "white"
+            shape = {{ type = "union", shapes = [
+                {{ type = "sphere", radius = 1.0 }},
+                {{ type = "twist", k = 1, shape = {{ type = "box", size = [1, 2] }} }},
+                {{ type = "smooth_union", k = 0.5, shapes = [] }},
+            ] }}
+
+            [objects.cloud]
+            type = "volume"
+            shape = {{ type = "sphere", radius = 2.0 }}
+            absorption = [0.1, 0.1, 0.1]
+            scattering = [0.5, 0.5, 0.5]
+            field = {{ type = "grid", resolution = [2, 2, 2.5], values = [0, 1], min = [0, 0, 0], max = [1, 1, 1] }}
+
+            [objects.crate]
+            type = "prefab"
+            prefab = "boxes"
+
+            [lights.lamp]
+            type = "spot"
+            color = [1.0, 1.0, 1.0]
+            "#
+        ));
+        let broken = merge(
+            broken,
+            toml(
+                r#"
+                output = { passes = ["depth", "shadow"] }
+                render = { threads = "many", sampler = "best" }
+                world = { fog = [10.0, 30.0] }
+                materials.white = { model = "glass", emission = 1.0 }
+                objects.ball = { radius = "big", velocity = [1, 0, 0] }
+                "#,
+            ),
+        );
+
+        // every problem is listed at once, each with where it is in the scene
+        let errs = validate(&broken).unwrap_err();
+        assert_eq!(
+            errs,
+            vec![
+                "unknown section \"camera\"",
+                "output: \"passes\" should be an array of any of \"depth\", \"normal\", \"albedo\", \"object\", \"material\", \"ao\"",
+                "render: \"threads\" should be a whole number or \"auto\"",
+                "render: \"sampler\" should be one of \"random\", \"stratified\", \"halton\", \"sobol\", \"blue_noise\"",
+                "post: \"denoise\" should be true or false",
+                "world: fog = [start, end] is no longer supported, fog is now a table like \
+                 fog = { density = 0.035, color = [1.0, 1.0, 1.0] }, \
+                 where a density of 0.035 lets half the light through 20 units away",
+                "materials.white: \"model\" should be one of \"phong\", \"lambert\", \"blinn_phong\", \"ggx\"",
+                "materials.white: \"emission\" should be an array of 3 numbers",
+                "objects.ball: \"radius\" should be a number",
+                "objects.blob.shape.shapes[1].shape: \"size\" should be an array of 3 numbers",
+                "objects.blob.shape.shapes[2]: \"shapes\" should list at least one shape",
+                "objects.cloud.field: \"resolution\" should be an array of 3 whole numbers",
+                "objects.crate: unknown prefab \"boxes\"",
+                "lights.lamp: \"type\" should be one of \"ambient\", \"directional\", \"point\"",
+            ]
+        );
+
+        // so is every missing key and section
+        let errs = validate(&toml("[materials.white]\ncolor = [1, 1, 1]\n")).unwrap_err();
+        assert_eq!(
+            errs,
+            vec![
+                "missing section \"output\"",
+                "missing section \"render\"",
+                "missing section \"world\"",
+                "materials.white: missing \"refl\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn include_test() {
+        let dir = write_files(
+            "include_test",
+            &[
+                (
+                    "lib/common.toml",
+                    "include = \"colors.toml\"\n[materials.white]\nrefl = 0.0\n",
+                ),
+                (
+                    "lib/colors.toml",
+                    "[materials.white]\ncolor = [1, 1, 1]\n[materials.red]\ncolor = [1, 0, 0]\nrefl = 0.0\n",
+                ),
+                (
+                    "lib/shiny.json",
+                    r#"{ "materials": { "white": { "refl": 0.5 }, "red": { "refl": 0.5 } } }"#,
+                ),
+                (
+                    "scene.toml",
+                    "include = [\"lib/common.toml\", \"lib/shiny.json\"]\n[materials.red]\nrefl = 0.9\n",
+                ),
+            ],
+        );
+
+        // files are found relative to the one including them, later includes override earlier ones,
+        // the including file overrides them all, and tables are merged key by key along the way
+        let cfg = load(&dir.join("scene.toml")).unwrap();
+        let white = &cfg["materials"]["white"];
+        let red = &cfg["materials"]["red"];
+        assert!(cfg.get("include").is_none());
+        assert_eq!(white["color"], toml("v = [1, 1, 1]")["v"]);
+        assert_eq!(white["refl"], Value::Float(0.5));
+        assert_eq!(red["color"], toml("v = [1, 0, 0]")["v"]);
+        assert_eq!(red["refl"], Value::Float(0.9));
+
+        let err = load(&dir.join("missing.toml")).unwrap_err();
+        assert!(err.contains("missing.toml"), "{}", err);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn asset_test() {
+        let dir = write_files(
+            "asset_test",
+            &[
+                (
+                    "lib/props.toml",
+                    "[objects.crate]\ntype = \"mesh\"\nfile = \"crate.ply\"\ntexture = \"wood.png\"\nmaterial = \"white\"\n\
+                     [prefabs.lamp.shade]\ntype = \"mesh\"\nfile = \"meshes/shade.stl\"\nmaterial = \"white\"\n\
+                     [world]\nbackground = \"sky.hdr\"\n",
+                ),
+                (
+                    "scene.toml",
+                    "include = \"lib/props.toml\"\n[objects.ball]\ntype = \"mesh\"\nfile = \"ball.ply\"\nmaterial = \"white\"\n",
+                ),
+            ],
+        );
+
+        // files named in an included file are found next to it, not next to the file including it
+        let cfg = load(&dir.join("scene.toml")).unwrap();
+        let full = fs::canonicalize(&dir).unwrap();
+        let file = |v: &Value| PathBuf::from(v.as_str().unwrap());
+        assert_eq!(
+            file(&cfg["objects"]["crate"]["file"]),
+            full.join("lib/crate.ply")
+        );
+        assert_eq!(
+            file(&cfg["objects"]["crate"]["texture"]),
+            full.join("lib/wood.png")
+        );
+        assert_eq!(
+            file(&cfg["prefabs"]["lamp"]["shade"]["file"]),
+            full.join("lib/meshes/shade.stl")
+        );
+        assert_eq!(file(&cfg["world"]["background"]), full.join("lib/sky.hdr"));
+        assert_eq!(file(&cfg["objects"]["ball"]["file"]), full.join("ball.ply"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cycle_test() {
+        let dir = write_files(
+            "cycle_test",
+            &[
+                ("a.toml", "include = \"b/b.toml\"\n"),
+                ("b/b.toml", "include = [\"c.toml\", \"../a.toml\"]\n"),
+                ("b/c.toml", "[render]\nsamples = 2\n"),
+                ("self.yaml", "include: self.yaml\n"),
+                ("twice.toml", "include = [\"b/c.toml\", \"b/c.toml\"]\n"),
+            ],
+        );
+
+        let err = load(&dir.join("a.toml")).unwrap_err();
+        assert!(err.starts_with("include cycle: "), "{}", err);
+        let chain: Vec<&str> = err["include cycle: ".len()..].split(" -> ").collect();
+        assert_eq!(chain.len(), 3);
+        assert!(chain[0].ends_with("a.toml") && chain[1].ends_with("b.toml"));
+        assert!(chain[2].ends_with("a.toml"));
+
+        assert!(load(&dir.join("self.yaml"))
+            .unwrap_err()
+            .starts_with("include cycle"));
+
+        // including the same file twice isn't a cycle
+        assert!(load(&dir.join("twice.toml")).is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn prefab_test() {
+        let cfg = toml(
+            r#"
+            [prefabs.lamp.bulb]
+            type = "sphere"
+            center = [0.0, 2.0, 0.0]
+            radius = 0.5
+            material = "white"
+
+            [prefabs.lamp.base]
+            type = "sdf"
+            material = "white"
+            shape = { type = "box", size = [1, 1, 1] }
+
+            [prefabs.lamp.cord]
+            type = "mesh"
+            material = "white"
+            positions = [0, 0, 0, 1, 0, 0, 0, 1, 0]
+
+            [objects.floor]
+            type = "plane"
+            point = [0.0, 0.0, 0.0]
+            normal = [0.0, 1.0, 0.0]
+            material = "white"
+
+            [objects.left]
+            type = "prefab"
+            prefab = "lamp"
+            offset = [-3.0, 0.0, 1.0]
+
+            [objects.right]
+            type = "prefab"
+            prefab = "lamp"
+            "#,
+        );
+
+        let cfg = instance_prefabs(cfg);
+        assert!(cfg.get("prefabs").is_none());
+
+        let objects = cfg["objects"].as_table().unwrap();
+        let names: Vec<&String> = objects.keys().collect();
+        assert_eq!(
+            names,
+            [
+                "floor",
+                "left.base",
+                "left.bulb",
+                "left.cord",
+                "right.base",
+                "right.bulb",
+                "right.cord"
+            ]
+        );
+
+        // each copy is moved by its instance's offset, whether or not its position was written out
+        let v3 = |o: &str, k: &str| get_v3(&objects[o], k);
+        assert_eq!(v3("left.bulb", "center"), Vector::from_3(-3.0, 2.0, 1.0));
+        assert_eq!(
+            get_v3(&objects["left.base"]["shape"], "center"),
+            Vector::from_3(-3.0, 0.0, 1.0)
+        );
+        assert_eq!(v3("left.cord", "offset"), Vector::from_3(-3.0, 0.0, 1.0));
+        assert_eq!(v3("right.bulb", "center"), Vector::from_3(0.0, 2.0, 0.0));
+        assert_eq!(objects["left.bulb"]["radius"], Value::Float(0.5));
+
+        // objects that aren't prefabs are left alone
+        assert_eq!(v3("floor", "point"), Vector::zero(3));
+    }
+}
@@ -0,0 +1,179 @@
+//! Defines signed distance field (SDF) shapes and how rays march through them.
+//!
+//! Shapes are built as a tree: primitives sit at the leaves and operators (unions, twists, repetition, ...)
+//! transform the point being sampled or combine the distances of their children.
+
+use crate::ray::*;
+use crate::vec::*;
+
+/// A node in a signed distance field tree.
+pub enum Shape {
+    /// A sphere of radius `r` centered on the origin.
+    Sphere { r: f32 },
+    /// An axis aligned box centered on the origin with half extents `b`.
+    Cuboid { b: Vector },
+    /// A torus lying in the xz plane with major radius `r.0` and minor radius `r.1`.
+    Torus { r: (f32, f32) },
+    /// A capsule (a line segment from `a` to `b` with thickness `r`).
+    Capsule { a: Vector, b: Vector, r: f32 },
+    /// Moves `s` so that its origin sits at `off`.
+    Translate { off: Vector, s: Box<Shape> },
+    /// The union of all shapes.
+    Union(Vec<Shape>),
+    /// The union of all shapes, blended together over a distance `k`.
+    SmoothUnion { k: f32, s: Vec<Shape> },
+    /// The intersection of all shapes.
+    Intersect(Vec<Shape>),
+    /// The first shape with all following shapes cut out of it.
+    Subtract(Vec<Shape>),
+    /// Twists `s` around the y axis by `k` radians per unit of height.
+    Twist { k: f32, s: Box<Shape> },
+    /// Repeats `s` infinitely with spacing `period` along each axis. A period of 0 disables repetition along that axis.
+    Repeat { period: Vector, s: Box<Shape> },
+}
+
+/// Returns the element-wise absolute value of `v`.
+fn abs(v: Vector) -> Vector {
+    let a = v.get();
+    Vector::from_3(a[0].abs(), a[1].abs(), a[2].abs())
+}
+
+/// Returns the length of `v`.
+fn mag(v: Vector) -> f32 {
+    v.dot(v).sqrt()
+}
+
+impl Shape {
+    /// Returns the signed distance from point `p` to the surface of the shape.
+    /// Negative distances are inside the shape.
+    pub fn dist(&self, p: Vector) -> f32 {
+        match self {
+            Shape::Sphere { r } => mag(p) - r,
+            Shape::Cuboid { b } => {
+                let q = abs(p) - *b;
+                mag(q.max(Vector::zero(3))) + q.x().max(q.y()).max(q.z()).min(0.0)
+            }
+            Shape::Torus { r } => {
+                let q = (p.x() * p.x() + p.z() * p.z()).sqrt() - r.0;
+                (q * q + p.y() * p.y()).sqrt() - r.1
+            }
+            Shape::Capsule { a, b, r } => {
+                let pa = p - *a;
+                let ba = *b - *a;
+                let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+                mag(pa - ba * Vector::from_s(h, 3)) - r
+            }
+            Shape::Translate { off, s } => s.dist(p - *off),
+            Shape::Union(s) => s.iter().fold(f32::INFINITY, |d, s| d.min(s.dist(p))),
+            Shape::SmoothUnion { k, s } => s.iter().skip(1).fold(s[0].dist(p), |d, s| {
+                // polynomial smooth minimum, which falls back to a regular minimum once shapes are `k` apart
+                let d2 = s.dist(p);
+                let h = (0.5 + 0.5 * (d2 - d) / k).clamp(0.0, 1.0);
+                d2 * (1.0 - h) + d * h - k * h * (1.0 - h)
+            }),
+            Shape::Intersect(s) => s.iter().fold(f32::NEG_INFINITY, |d, s| d.max(s.dist(p))),
+            Shape::Subtract(s) => s
+                .iter()
+                .skip(1)
+                .fold(s[0].dist(p), |d, s| d.max(-s.dist(p))),
+            Shape::Twist { k, s } => {
+                let (sin, cos) = (k * p.y()).sin_cos();
+                let q = Vector::from_3(cos * p.x() - sin * p.z(), p.y(), sin * p.x() + cos * p.z());
+                s.dist(q)
+            }
+            Shape::Repeat { period, s } => {
+                let mut q = p.get();
+                for (i, c) in period.get().iter().take(3).enumerate() {
+                    if *c > 0.0 {
+                        q[i] -= c * (q[i] / c).round();
+                    }
+                }
+                s.dist(Vector::from_3(q[0], q[1], q[2]))
+            }
+        }
+    }
+}
+
+/// An object whose surface is defined by a signed distance field and rendered by sphere tracing.
+pub struct Sdf {
+    /// The shape of the object.
+    pub shape: Shape,
+    /// The material of the object.
+    pub mat: Material,
+    /// Fraction of the distance bound to step each iteration.
+    /// Operators like `Twist` distort distances, so values below 1 are needed to avoid stepping through the surface.
+    pub step: f32,
+    /// Maximum number of steps to take before giving up on a ray.
+    pub max_steps: u32,
+    /// Maximum distance to march before giving up on a ray.
+    pub max_dist: f32,
+    /// How far the whole shape moves while the shutter is open.
+    pub motion: Vector,
+}
+
+/// Distance from the surface at which a marched ray counts as a hit.
+const SDF_EPS: f32 = 1e-4;
+
+impl RayInteraction for Sdf {
+    fn hit(&self, r: &Ray, t: (f32, f32)) -> HitType {
+        // distances are measured in world units but t is measured in multiples of r.d
+        let dl = mag(r.d);
+        let end = t.1.min(self.max_dist / dl);
+
+        // march through the shape where it sits at the time of the ray by moving the ray the other way instead
+        let o = r.o - self.motion * Vector::from_s(r.time, 3);
+
+        // rays starting inside the shape hit it where they come back out, like they do for other objects,
+        // so they march on the distance to the surface from the inside
+        let sign = match self.shape.dist(o + Vector::from_s(t.0, 3) * r.d) < 0.0 {
+            true => -1.0,
+            false => 1.0,
+        };
+
+        let mut ct = t.0;
+        for _ in 0..self.max_steps {
+            if ct >= end {
+                break;
+            }
+
+            let p = o + Vector::from_s(ct, 3) * r.d;
+            let d = sign * self.shape.dist(p);
+
+            if d < SDF_EPS {
+                return HitType::Hit(ct);
+            }
+
+            ct += self.step * d / dl;
+        }
+
+        HitType::Miss()
+    }
+
+    fn normal(&self, p: &Vector, time: f32) -> Vector {
+        // estimate the gradient of the distance field with central differences
+        let p = &(*p - self.motion * Vector::from_s(time, 3));
+        let h = 1e-3;
+        let dx = Vector::from_3(h, 0.0, 0.0);
+        let dy = Vector::from_3(0.0, h, 0.0);
+        let dz = Vector::from_3(0.0, 0.0, h);
+
+        Vector::from_3(
+            self.shape.dist(*p + dx) - self.shape.dist(*p - dx),
+            self.shape.dist(*p + dy) - self.shape.dist(*p - dy),
+            self.shape.dist(*p + dz) - self.shape.dist(*p - dz),
+        )
+        .norm()
+    }
+
+    fn material(&self, _p: &Vector, _time: f32) -> Material {
+        self.mat
+    }
+
+    fn name(&self) -> &'static str {
+        "sdf"
+    }
+
+    fn describe(&self) -> Object<'_> {
+        Object::Sdf(self)
+    }
+}
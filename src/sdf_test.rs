@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod tests {
+    use crate::bsdf::*;
+    use crate::ray::*;
+    use crate::sdf::*;
+    use crate::vec::*;
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn sphere_test() {
+        let s = Shape::Sphere { r: 1.0 };
+
+        assert!(approx(s.dist(Vector::from_3(3.0, 0.0, 0.0)), 2.0));
+        assert!(approx(s.dist(Vector::zero(3)), -1.0));
+    }
+
+    #[test]
+    fn cuboid_test() {
+        let s = Shape::Cuboid {
+            b: Vector::from_3(1.0, 2.0, 3.0),
+        };
+
+        assert!(approx(s.dist(Vector::from_3(0.0, 4.0, 0.0)), 2.0));
+        assert!(approx(s.dist(Vector::zero(3)), -1.0));
+    }
+
+    // two unit spheres touching at the origin
+    fn pair() -> Vec<Shape> {
+        [-1.0, 1.0]
+            .iter()
+            .map(|x| Shape::Translate {
+                off: Vector::from_3(*x, 0.0, 0.0),
+                s: Box::new(Shape::Sphere { r: 1.0 }),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn smooth_union_test() {
+        let hard = Shape::Union(pair());
+        let soft = Shape::SmoothUnion { k: 0.5, s: pair() };
+
+        // blending fills in the crease where the two spheres touch
+        let p = Vector::from_3(0.0, 0.5, 0.0);
+        assert!(soft.dist(p) < hard.dist(p));
+
+        // far away from the crease the shape is unchanged
+        let p = Vector::from_3(-3.0, 0.0, 0.0);
+        assert!(approx(soft.dist(p), hard.dist(p)));
+    }
+
+    #[test]
+    fn repeat_test() {
+        let s = Shape::Repeat {
+            period: Vector::from_3(4.0, 0.0, 0.0),
+            s: Box::new(Shape::Sphere { r: 1.0 }),
+        };
+
+        assert!(approx(s.dist(Vector::from_3(8.0, 0.0, 0.0)), -1.0));
+        assert!(approx(s.dist(Vector::from_3(8.0, 3.0, 0.0)), 2.0));
+    }
+
+    #[test]
+    fn march_test() {
+        let obj = Sdf {
+            shape: Shape::Translate {
+                off: Vector::from_3(0.0, 0.0, 5.0),
+                s: Box::new(Shape::Sphere { r: 1.0 }),
+            },
+            mat: Material {
+                color: Vector::from_s(1.0, 3),
+                bsdf: Model::Lambert(Lambert),
+                refl: 0.0,
+                roughness: 0.0,
+                emission: Vector::zero(3),
+                transmission: Vector::zero(3),
+                id: 0,
+            },
+            step: 1.0,
+            max_steps: 256,
+            max_dist: 100.0,
+            motion: Vector::zero(3),
+        };
+
+        let r = Ray {
+            o: Vector::zero(3),
+            d: Vector::from_3(0.0, 0.0, 1.0),
+            time: 0.0,
+        };
+
+        match obj.hit(&r, (0.0, f32::INFINITY)) {
+            HitType::Hit(t) => assert!(approx(t, 4.0)),
+            HitType::Miss() => panic!("ray should hit the sphere"),
+        }
+
+        // a ray starting inside the sphere hits it on the way out
+        match obj.hit(&r, (4.5, f32::INFINITY)) {
+            HitType::Hit(t) => assert!(approx(t, 6.0)),
+            HitType::Miss() => panic!("ray should leave the sphere"),
+        }
+
+        let n = obj.normal(&Vector::from_3(0.0, 0.0, 4.0), 0.0);
+        assert!(approx(n.z(), -1.0));
+    }
+
+    #[test]
+    fn motion_test() {
+        let obj = Sdf {
+            shape: Shape::Sphere { r: 1.0 },
+            mat: Material {
+                color: Vector::from_s(1.0, 3),
+                bsdf: Model::Lambert(Lambert),
+                refl: 0.0,
+                roughness: 0.0,
+                emission: Vector::zero(3),
+                transmission: Vector::zero(3),
+                id: 0,
+            },
+            step: 1.0,
+            max_steps: 256,
+            max_dist: 100.0,
+            motion: Vector::from_3(0.0, 0.0, 4.0),
+        };
+
+        // the sphere moves from the origin to z = 4 while the shutter is open, so it's at z = 2 halfway through
+        let r = Ray {
+            o: Vector::from_3(0.0, 0.0, -5.0),
+            d: Vector::from_3(0.0, 0.0, 1.0),
+            time: 0.5,
+        };
+
+        match obj.hit(&r, (0.0, f32::INFINITY)) {
+            HitType::Hit(t) => assert!(approx(t, 6.0)),
+            HitType::Miss() => panic!("ray should hit the sphere"),
+        }
+
+        let n = obj.normal(&Vector::from_3(1.0, 0.0, 2.0), 0.5);
+        assert!(approx(n.x(), 1.0));
+    }
+}
@@ -0,0 +1,175 @@
+//! Counts the work done while rendering, to help figure out why a scene is slow.
+//!
+//! Each thread keeps its own counters, so recording is cheap and needs no locking.
+//! Threads hand their counters over with `take` once they're done, and the results are combined with `Stats::merge`.
+
+use std::cell::RefCell;
+use std::time::Duration;
+
+/// Counters for the work done while rendering.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Stats {
+    /// Rays cast from the camera.
+    pub primary_rays: u64,
+    /// Rays cast towards light sources to check for shadows.
+    pub shadow_rays: u64,
+    /// Rays cast off reflective surfaces.
+    pub reflection_rays: u64,
+    /// Rays cast to estimate ambient occlusion.
+    pub occlusion_rays: u64,
+    /// Number of ray-object intersection tests, by type of object.
+    pub tests: Vec<(&'static str, u64)>,
+    /// Number of reflection rays cast at each bounce, starting from the first at index 0.
+    pub depths: Vec<u64>,
+    /// Time spent in each stage of producing the image, in the order the stages ran.
+    pub stages: Vec<(&'static str, Duration)>,
+}
+
+thread_local! {
+    static STATS: RefCell<Stats> = RefCell::new(Stats::default());
+}
+
+/// Updates the counters of the current thread with `f`.
+pub fn record<F: FnOnce(&mut Stats)>(f: F) {
+    STATS.with(|s| f(&mut s.borrow_mut()));
+}
+
+/// Returns the number of rays the current thread has cast since its counters were last taken.
+pub fn rays() -> u64 {
+    STATS.with(|s| s.borrow().total_rays())
+}
+
+/// Returns the counters of the current thread, resetting them to 0.
+pub fn take() -> Stats {
+    STATS.with(|s| s.take())
+}
+
+impl Stats {
+    /// Counts an intersection test against an object of type `name`.
+    pub fn add_test(&mut self, name: &'static str) {
+        match self.tests.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, c)) => *c += 1,
+            None => self.tests.push((name, 1)),
+        }
+    }
+
+    /// Counts a reflection ray cast at bounce `depth`, starting from 0.
+    pub fn add_reflection(&mut self, depth: usize) {
+        self.reflection_rays += 1;
+
+        if self.depths.len() <= depth {
+            self.depths.resize(depth + 1, 0);
+        }
+        self.depths[depth] += 1;
+    }
+
+    /// Adds all counters in `other` to these ones.
+    pub fn merge(&mut self, other: &Stats) {
+        self.primary_rays += other.primary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.reflection_rays += other.reflection_rays;
+        self.occlusion_rays += other.occlusion_rays;
+
+        for &(name, c) in &other.tests {
+            match self.tests.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, sc)) => *sc += c,
+                None => self.tests.push((name, c)),
+            }
+        }
+
+        if self.depths.len() < other.depths.len() {
+            self.depths.resize(other.depths.len(), 0);
+        }
+        for (d, c) in self.depths.iter_mut().zip(other.depths.iter()) {
+            *d += c;
+        }
+
+        self.stages.extend_from_slice(&other.stages);
+    }
+
+    /// Returns the total number of rays cast.
+    pub fn total_rays(&self) -> u64 {
+        self.primary_rays + self.shadow_rays + self.reflection_rays + self.occlusion_rays
+    }
+
+    /// Formats the counters as a human readable table. Counters are left out if nothing was counted.
+    pub fn table(&self) -> String {
+        let total = self.total_rays().max(1) as f64;
+        let mut out = String::new();
+
+        if self.total_rays() > 0 {
+            out += &self.counter_table(total);
+        }
+
+        if !self.stages.is_empty() {
+            out += "time:\n";
+            for (name, t) in &self.stages {
+                out += &format!("  {:<12}{:>13.3}s\n", name, t.as_secs_f64());
+            }
+        }
+
+        out
+    }
+
+    /// Formats the ray and intersection test counters as a human readable table, given the `total` number of rays.
+    fn counter_table(&self, total: f64) -> String {
+        let mut out = String::new();
+
+        out += "rays:\n";
+        for (name, c) in [
+            ("primary", self.primary_rays),
+            ("shadow", self.shadow_rays),
+            ("reflection", self.reflection_rays),
+            ("occlusion", self.occlusion_rays),
+            ("total", self.total_rays()),
+        ] {
+            out += &format!(
+                "  {:<12}{:>14}{:>8.1}%\n",
+                name,
+                c,
+                c as f64 * 100.0 / total
+            );
+        }
+
+        out += "intersection tests:\n";
+        for (name, c) in &self.tests {
+            out += &format!("  {:<12}{:>14}\n", name, c);
+        }
+
+        if !self.depths.is_empty() {
+            out += "reflection rays by bounce:\n";
+            for (d, c) in self.depths.iter().enumerate() {
+                out += &format!("  {:<12}{:>14}\n", d + 1, c);
+            }
+        }
+
+        out
+    }
+
+    /// Formats the counters as a JSON object.
+    pub fn json(&self) -> String {
+        let tests: Vec<String> = self
+            .tests
+            .iter()
+            .map(|(n, c)| format!("\"{}\": {}", n, c))
+            .collect();
+        let depths: Vec<String> = self.depths.iter().map(|c| c.to_string()).collect();
+        let stages: Vec<String> = self
+            .stages
+            .iter()
+            .map(|(n, t)| format!("\"{}\": {:.6}", n, t.as_secs_f64()))
+            .collect();
+
+        format!(
+            "{{\n  \"rays\": {{\"primary\": {}, \"shadow\": {}, \"reflection\": {}, \"occlusion\": {}, \"total\": {}}},\n  \"tests\": {{{}}},\n  \"reflection_depths\": [{}],\n  \"seconds\": {{{}}}\n}}\n",
+            self.primary_rays,
+            self.shadow_rays,
+            self.reflection_rays,
+            self.occlusion_rays,
+            self.total_rays(),
+            tests.join(", "),
+            depths.join(", "),
+            stages.join(", ")
+        )
+    }
+}
@@ -0,0 +1,46 @@
+#[cfg(test)]
+mod tests {
+    use crate::stats::{self, Stats};
+
+    #[test]
+    fn merge_test() {
+        // threads count from the start, whether or not the counters end up written to a file
+        stats::record(|s| {
+            s.primary_rays += 2;
+            s.add_test("sphere");
+            s.add_test("plane");
+            s.add_test("sphere");
+            s.add_reflection(1);
+        });
+
+        let a = stats::take();
+        assert_eq!(a.tests, vec![("sphere", 2), ("plane", 1)]);
+        assert_eq!(a.depths, vec![0, 1]);
+
+        // taking the counters resets them
+        assert_eq!(stats::take(), Stats::default());
+
+        let mut b = Stats::default();
+        b.add_test("plane");
+        b.add_reflection(0);
+        b.merge(&a);
+
+        assert_eq!(b.primary_rays, 2);
+        assert_eq!(b.reflection_rays, 2);
+        assert_eq!(b.tests, vec![("plane", 2), ("sphere", 2)]);
+        assert_eq!(b.depths, vec![1, 1]);
+        assert_eq!(b.total_rays(), 4);
+    }
+
+    #[test]
+    fn table_test() {
+        // only timings are shown when nothing was counted
+        let mut s = Stats::default();
+        s.stages
+            .push(("render", std::time::Duration::from_millis(1500)));
+        assert_eq!(s.table(), "time:\n  render              1.500s\n");
+
+        s.primary_rays = 3;
+        assert!(s.table().starts_with("rays:\n"));
+    }
+}
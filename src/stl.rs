@@ -0,0 +1,60 @@
+//! Reads triangle meshes from STL files, in ASCII or binary form.
+
+use crate::mesh::Triangle;
+use crate::vec::Vector;
+
+/// Reads the STL file at `path`.
+pub fn load(path: &str) -> Vec<Triangle> {
+    let bytes = std::fs::read(path).unwrap_or_else(|e| panic!("couldn't read {path}: {e}"));
+    parse(&bytes)
+}
+
+/// Reads an STL file from `bytes`.
+/// The normals stored with each triangle are ignored, since many programs leave them out,
+/// so triangles face whichever way their corners wind.
+pub fn parse(bytes: &[u8]) -> Vec<Triangle> {
+    // binary files can start with "solid" too, so they're told apart by whether their size matches their triangle count
+    let binary = bytes.len() >= 84 && {
+        let n = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        bytes.len() == 84 + n * 50
+    };
+
+    let corners: Vec<Vector> = if binary {
+        // each triangle is a normal, three corners and two spare bytes
+        let f = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        bytes[84..]
+            .chunks_exact(50)
+            .flat_map(|t| t[12..48].chunks_exact(12))
+            .map(|v| Vector::from_3(f(&v[0..]), f(&v[4..]), f(&v[8..])))
+            .collect()
+    } else {
+        let text = String::from_utf8_lossy(bytes);
+        let mut words = text.split_whitespace();
+        let mut corners = vec![];
+        while let Some(w) = words.next() {
+            if w == "vertex" {
+                let mut c = || {
+                    words
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .expect("invalid STL vertex")
+                };
+                corners.push(Vector::from_3(c(), c(), c()));
+            }
+        }
+        corners
+    };
+
+    if !corners.len().is_multiple_of(3) {
+        panic!("STL file has a triangle without 3 corners");
+    }
+    corners
+        .chunks_exact(3)
+        .map(|c| Triangle {
+            p: [c[0], c[1], c[2]],
+            n: None,
+            uv: None,
+            color: None,
+        })
+        .collect()
+}
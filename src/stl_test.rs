@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod tests {
+    use crate::stl::*;
+    use crate::vec::*;
+
+    #[test]
+    fn ascii_test() {
+        let text = "solid part
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1.5 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 1
+      vertex 1e0 0 1
+      vertex 0 -1 1
+    endloop
+  endfacet
+endsolid part
+";
+        let tris = parse(text.as_bytes());
+        assert_eq!(tris.len(), 2);
+        assert_eq!(tris[0].p[2], Vector::from_3(0.0, 1.5, 0.0));
+        assert_eq!(tris[1].p[1], Vector::from_3(1.0, 0.0, 1.0));
+        assert_eq!(tris[0].face_normal(), Vector::from_3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn binary_test() {
+        // binary files may start with "solid" as well
+        let mut bytes = b"solid but actually binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend(1u32.to_le_bytes());
+        for x in [
+            0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+        ] {
+            bytes.extend(x.to_le_bytes());
+        }
+        bytes.extend([0, 0]);
+
+        let tris = parse(&bytes);
+        assert_eq!(tris.len(), 1);
+        assert_eq!(tris[0].p[0], Vector::from_3(0.0, 0.0, 0.0));
+        assert_eq!(tris[0].p[1], Vector::from_3(1.0, 0.0, 0.0));
+        assert_eq!(tris[0].p[2], Vector::from_3(0.0, 1.0, 0.0));
+    }
+}
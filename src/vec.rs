@@ -104,6 +104,18 @@ impl Vector {
         sum
     }
 
+    /// Returns the cross product of `self` and `rhs`. Both vectors must have a length of 3.
+    pub fn cross(self, rhs: Vector) -> Vector {
+        assert_eq!(self.len, 3);
+        assert_eq!(rhs.len, 3);
+
+        Vector::from_3(
+            self.v[1] * rhs.v[2] - self.v[2] * rhs.v[1],
+            self.v[2] * rhs.v[0] - self.v[0] * rhs.v[2],
+            self.v[0] * rhs.v[1] - self.v[1] * rhs.v[0],
+        )
+    }
+
     /// Returns the normalized version of `self`.
     pub fn norm(self) -> Vector {
         let mag = self.dot(self).sqrt();
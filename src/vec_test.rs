@@ -34,6 +34,15 @@ mod tests {
         assert_eq!(v1.dot(v2), 30.0);
     }
 
+    #[test]
+    fn cross_test() {
+        let v1 = Vector::from_3(1.0, 0.0, 0.0);
+        let v2 = Vector::from_3(0.0, 1.0, 0.0);
+
+        assert_eq!(v1.cross(v2), Vector::from_3(0.0, 0.0, 1.0));
+        assert_eq!(v2.cross(v1), Vector::from_3(0.0, 0.0, -1.0));
+    }
+
     #[test]
     fn norm_test() {
         let v = Vector::from_v([5.0, 0.0, 0.0, 0.0], 4);